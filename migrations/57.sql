@@ -0,0 +1,6 @@
+CREATE TABLE sync_status(
+    id INTEGER PRIMARY KEY NOT NULL,
+    mode TEXT NOT NULL,
+    timestamp_osm_base TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ'))
+) STRICT;
@@ -0,0 +1,35 @@
+CREATE VIEW element_coord AS
+SELECT
+    id,
+    coalesce(
+        json_extract(overpass_data, '$.lon'),
+        (json_extract(overpass_data, '$.bounds.minlon') + json_extract(overpass_data, '$.bounds.maxlon')) / 2
+    ) AS lon,
+    coalesce(
+        json_extract(overpass_data, '$.lat'),
+        (json_extract(overpass_data, '$.bounds.minlat') + json_extract(overpass_data, '$.bounds.maxlat')) / 2
+    ) AS lat
+FROM element;
+
+CREATE VIRTUAL TABLE element_rtree USING rtree(id, min_lon, max_lon, min_lat, max_lat);
+
+INSERT INTO element_rtree
+SELECT id, lon, lon, lat, lat FROM element_coord WHERE lon IS NOT NULL AND lat IS NOT NULL;
+
+CREATE TRIGGER element_rtree_insert AFTER INSERT ON element
+BEGIN
+    INSERT INTO element_rtree
+    SELECT id, lon, lon, lat, lat FROM element_coord WHERE id = new.id AND lon IS NOT NULL AND lat IS NOT NULL;
+END;
+
+CREATE TRIGGER element_rtree_update AFTER UPDATE OF overpass_data ON element
+BEGIN
+    DELETE FROM element_rtree WHERE id = old.id;
+    INSERT INTO element_rtree
+    SELECT id, lon, lon, lat, lat FROM element_coord WHERE id = new.id AND lon IS NOT NULL AND lat IS NOT NULL;
+END;
+
+CREATE TRIGGER element_rtree_delete AFTER DELETE ON element
+BEGIN
+    DELETE FROM element_rtree WHERE id = old.id;
+END;
@@ -0,0 +1 @@
+CREATE INDEX area_url_alias ON area(json_extract(tags, '$.url_alias'));
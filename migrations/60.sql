@@ -0,0 +1,4 @@
+CREATE TABLE deleted_element_notification(
+    element_id INTEGER PRIMARY KEY NOT NULL REFERENCES element(id),
+    notified_at TEXT NOT NULL
+) STRICT;
@@ -0,0 +1,26 @@
+ALTER TABLE element ADD COLUMN lat REAL;
+
+ALTER TABLE element ADD COLUMN lon REAL;
+
+UPDATE element SET
+    lat = (SELECT lat FROM element_coord WHERE element_coord.id = element.id),
+    lon = (SELECT lon FROM element_coord WHERE element_coord.id = element.id);
+
+DROP TRIGGER element_rtree_insert;
+
+DROP TRIGGER element_rtree_update;
+
+DROP VIEW element_coord;
+
+CREATE TRIGGER element_rtree_insert AFTER INSERT ON element
+WHEN new.lat IS NOT NULL AND new.lon IS NOT NULL
+BEGIN
+    INSERT INTO element_rtree VALUES (new.id, new.lon, new.lon, new.lat, new.lat);
+END;
+
+CREATE TRIGGER element_rtree_update AFTER UPDATE OF lat, lon ON element
+BEGIN
+    DELETE FROM element_rtree WHERE id = old.id;
+    INSERT INTO element_rtree
+    SELECT new.id, new.lon, new.lon, new.lat, new.lat WHERE new.lat IS NOT NULL AND new.lon IS NOT NULL;
+END;
@@ -0,0 +1,42 @@
+CREATE VIRTUAL TABLE element_fts USING fts5(name, tags);
+
+INSERT INTO element_fts (rowid, name, tags)
+SELECT
+    id,
+    coalesce(json_extract(overpass_data, '$.tags.name'), ''),
+    coalesce((
+        SELECT group_concat(value, ' ')
+        FROM json_each(overpass_data, '$.tags')
+        WHERE key IN ('amenity', 'shop', 'cuisine', 'craft', 'tourism', 'office', 'brand', 'description')
+    ), '')
+FROM element
+WHERE deleted_at IS NULL;
+
+CREATE TRIGGER element_fts_insert AFTER INSERT ON element
+WHEN new.deleted_at IS NULL
+BEGIN
+    INSERT INTO element_fts (rowid, name, tags)
+    SELECT
+        new.id,
+        coalesce(json_extract(new.overpass_data, '$.tags.name'), ''),
+        coalesce((
+            SELECT group_concat(value, ' ')
+            FROM json_each(new.overpass_data, '$.tags')
+            WHERE key IN ('amenity', 'shop', 'cuisine', 'craft', 'tourism', 'office', 'brand', 'description')
+        ), '');
+END;
+
+CREATE TRIGGER element_fts_update AFTER UPDATE OF overpass_data, deleted_at ON element
+BEGIN
+    DELETE FROM element_fts WHERE rowid = old.id;
+    INSERT INTO element_fts (rowid, name, tags)
+    SELECT
+        new.id,
+        coalesce(json_extract(new.overpass_data, '$.tags.name'), ''),
+        coalesce((
+            SELECT group_concat(value, ' ')
+            FROM json_each(new.overpass_data, '$.tags')
+            WHERE key IN ('amenity', 'shop', 'cuisine', 'craft', 'tourism', 'office', 'brand', 'description')
+        ), '')
+    WHERE new.deleted_at IS NULL;
+END;
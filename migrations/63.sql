@@ -0,0 +1,15 @@
+DROP TABLE element_fts;
+
+CREATE VIRTUAL TABLE element_fts USING fts5(name, tags, tokenize = 'unicode61 remove_diacritics 2');
+
+INSERT INTO element_fts (rowid, name, tags)
+SELECT
+    id,
+    coalesce(json_extract(overpass_data, '$.tags.name'), ''),
+    coalesce((
+        SELECT group_concat(value, ' ')
+        FROM json_each(overpass_data, '$.tags')
+        WHERE key IN ('amenity', 'shop', 'cuisine', 'craft', 'tourism', 'office', 'brand', 'description')
+    ), '')
+FROM element
+WHERE deleted_at IS NULL;
@@ -0,0 +1,4 @@
+CREATE TABLE lock(
+    name TEXT PRIMARY KEY NOT NULL,
+    acquired_at TEXT NOT NULL
+) STRICT;
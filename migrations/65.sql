@@ -0,0 +1,3 @@
+ALTER TABLE sync_status ADD COLUMN duration_secs REAL;
+ALTER TABLE sync_status ADD COLUMN elements INTEGER;
+ALTER TABLE sync_status ADD COLUMN elements_per_second REAL;
@@ -0,0 +1,11 @@
+CREATE TABLE element_tag_change(
+    id INTEGER PRIMARY KEY NOT NULL,
+    element_id INTEGER NOT NULL REFERENCES element(id),
+    tag TEXT NOT NULL,
+    old_value TEXT,
+    new_value TEXT,
+    changed_by TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ'))
+) STRICT;
+
+CREATE INDEX element_tag_change_element_id ON element_tag_change(element_id);
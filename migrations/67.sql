@@ -0,0 +1,9 @@
+CREATE TABLE area_stats(
+    area_id INTEGER PRIMARY KEY NOT NULL REFERENCES area(id),
+    elements INTEGER NOT NULL,
+    up_to_date_elements INTEGER NOT NULL,
+    onchain_elements INTEGER NOT NULL,
+    lightning_elements INTEGER NOT NULL,
+    lightning_contactless_elements INTEGER NOT NULL,
+    updated_at TEXT NOT NULL
+) STRICT;
@@ -0,0 +1 @@
+ALTER TABLE lock ADD COLUMN owner TEXT NOT NULL DEFAULT '';
@@ -5,9 +5,10 @@ use crate::{
 };
 use actix_web::{
     delete, patch, post,
-    web::{Data, Json, Path},
+    web::{Data, Json, Path, Query},
     HttpRequest,
 };
+use geojson::GeoJson;
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
 use time::OffsetDateTime;
@@ -55,6 +56,9 @@ async fn post(
             "This url_alias is already in use".into(),
         ))?
     }
+    if let Some(geo_json) = args.tags.get("geo_json") {
+        validate_geo_json(geo_json)?;
+    }
     let area = repo.insert(&args.tags).await?;
     let log_message = format!(
         "{} created a new area: https://api.btcmap.org/v2/areas/{}",
@@ -66,16 +70,32 @@ async fn post(
     Ok(area.into())
 }
 
+fn validate_geo_json(geo_json: &Value) -> Result<(), Error> {
+    serde_json::to_string(geo_json)?
+        .parse::<GeoJson>()
+        .map_err(|e| Error::HttpUnprocessableEntity(format!("Invalid geo_json: {e}")))?;
+    Ok(())
+}
+
 #[derive(Serialize, Deserialize)]
 struct PatchArgs {
     tags: Map<String, Value>,
 }
 
+#[derive(Deserialize)]
+struct ForceQuery {
+    force: Option<bool>,
+}
+
+// Overwriting these by mistake breaks area URLs or loses hand crafted geometry
+const PROTECTED_TAGS: [&str; 2] = ["geo_json", "url_alias"];
+
 #[patch("{id}")]
 async fn patch(
     req: HttpRequest,
     id: Path<String>,
     args: Json<PatchArgs>,
+    query: Query<ForceQuery>,
     auth: Data<AuthService>,
     repo: Data<AreaRepo>,
 ) -> Result<Json<AreaView>, Error> {
@@ -89,6 +109,20 @@ async fn patch(
         "There is no area with id or url_alias = {}",
         id,
     )))?;
+    if let Some(geo_json) = args.tags.get("geo_json") {
+        validate_geo_json(geo_json)?;
+    }
+    if !query.force.unwrap_or(false) {
+        for tag in PROTECTED_TAGS {
+            if let (Some(old), Some(new)) = (area.tags.get(tag), args.tags.get(tag)) {
+                if old != new {
+                    Err(Error::HttpConflict(format!(
+                        "Protected tag can't be modified without force=true: {tag}"
+                    )))?
+                }
+            }
+        }
+    }
     let area = repo.patch_tags(area.id, &args.tags).await?;
     let log_message = format!(
         "{} updated area https://api.btcmap.org/v2/areas/{}",
@@ -130,6 +164,57 @@ async fn delete(
     Ok(area.into())
 }
 
+// Same as patching the tag with null, but doesn't require building a JSON body
+#[delete("{id}/tags/{name}")]
+async fn delete_tag(
+    req: HttpRequest,
+    path: Path<(String, String)>,
+    query: Query<ForceQuery>,
+    auth: Data<AuthService>,
+    repo: Data<AreaRepo>,
+) -> Result<Json<AreaView>, Error> {
+    let token = auth.check(&req).await?;
+    let (id, name) = path.into_inner();
+    // Tag names end up in a JSON path, where quotes can't be escaped
+    if name.contains('"') {
+        Err(Error::HttpBadRequest(format!("Invalid tag name: {name}")))?
+    }
+    if name == "url_alias" {
+        Err(Error::HttpBadRequest(
+            "Mandatory tag can't be deleted: url_alias".into(),
+        ))?
+    }
+    if PROTECTED_TAGS.contains(&name.as_str()) && !query.force.unwrap_or(false) {
+        Err(Error::HttpConflict(format!(
+            "Protected tag can't be deleted without force=true: {name}"
+        )))?
+    }
+    let int_id = id.parse::<i64>();
+    let area = match int_id {
+        Ok(id) => repo.select_by_id(id).await,
+        Err(_) => repo.select_by_url_alias(&id).await,
+    }?
+    .ok_or(Error::HttpNotFound(format!(
+        "There is no area with id or url_alias = {}",
+        id,
+    )))?;
+    if !area.tags.contains_key(&name) {
+        Err(Error::HttpNotFound(format!(
+            "Area {id} has no tag named {name}"
+        )))?
+    }
+    let area = repo.remove_tag(area.id, &name).await?;
+    let log_message = format!(
+        "{} removed tag {} from area https://api.btcmap.org/v2/areas/{}",
+        token.owner,
+        name,
+        area.tags["url_alias"].as_str().unwrap(),
+    );
+    warn!(log_message);
+    discord::send_message_to_channel(&log_message, discord::CHANNEL_API).await;
+    Ok(area.into())
+}
+
 impl Into<AreaView> for Area {
     fn into(self) -> AreaView {
         AreaView {
@@ -205,6 +290,53 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    async fn post_with_geo_json() -> Result<()> {
+        let state = mock_state().await;
+        let token = state.auth.mock_token("test").await.secret;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.auth))
+                .app_data(Data::new(state.area_repo.clone()))
+                .service(scope("/").service(super::post)),
+        )
+        .await;
+        let geo_json = json!({
+            "type": "Polygon",
+            "coordinates": [[[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0], [0.0, 0.0]]],
+        });
+        let req = TestRequest::post()
+            .uri("/")
+            .append_header(("Authorization", format!("Bearer {token}")))
+            .set_json(json!({"tags": {
+                "url_alias": "test",
+                "name": "Test",
+                "geo_json": geo_json,
+            }}))
+            .to_request();
+        let res: AreaView = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(geo_json, res.tags["geo_json"]);
+        let area = state.area_repo.select_by_url_alias("test").await?.unwrap();
+        assert_eq!(json!("Test"), area.tags["name"]);
+        assert_eq!(geo_json, area.tags["geo_json"]);
+        let req = TestRequest::post()
+            .uri("/")
+            .append_header(("Authorization", format!("Bearer {token}")))
+            .set_json(json!({"tags": {
+                "url_alias": "test2",
+                "geo_json": {"type": "Polygon"},
+            }}))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(state
+            .area_repo
+            .select_by_url_alias("test2")
+            .await?
+            .is_none());
+        Ok(())
+    }
+
     #[test]
     async fn patch_unauthorized() -> Result<()> {
         let state = mock_state().await;
@@ -270,6 +402,70 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    async fn patch_protected_tag() -> Result<()> {
+        let state = mock_state().await;
+        let token = state.auth.mock_token("test").await.secret;
+        let geo_json = json!({"type": "Point", "coordinates": [1.0, 2.0]});
+        let mut tags = Map::new();
+        tags.insert("url_alias".into(), Value::String("test".into()));
+        tags.insert("geo_json".into(), geo_json.clone());
+        state.area_repo.insert(&tags).await?;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.auth))
+                .app_data(Data::new(AreaRepo::new(&state.pool)))
+                .service(super::patch),
+        )
+        .await;
+        let new_geo_json = json!({"type": "Point", "coordinates": [3.0, 4.0]});
+        let req = TestRequest::patch()
+            .uri("/test")
+            .append_header(("Authorization", format!("Bearer {token}")))
+            .set_json(json!({"tags": {"geo_json": new_geo_json}}))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+        let area = state.area_repo.select_by_url_alias("test").await?.unwrap();
+        assert_eq!(geo_json, area.tags["geo_json"]);
+        let req = TestRequest::patch()
+            .uri("/test?force=true")
+            .append_header(("Authorization", format!("Bearer {token}")))
+            .set_json(json!({"tags": {"geo_json": new_geo_json}}))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let area = state.area_repo.select_by_url_alias("test").await?.unwrap();
+        assert_eq!(new_geo_json, area.tags["geo_json"]);
+        Ok(())
+    }
+
+    #[test]
+    async fn patch_invalid_geo_json() -> Result<()> {
+        let state = mock_state().await;
+        let token = state.auth.mock_token("test").await.secret;
+        let mut tags = Map::new();
+        tags.insert("url_alias".into(), Value::String("test".into()));
+        state.area_repo.insert(&tags).await?;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.auth))
+                .app_data(Data::new(AreaRepo::new(&state.pool)))
+                .service(super::patch),
+        )
+        .await;
+        let req = TestRequest::patch()
+            .uri("/test")
+            .append_header(("Authorization", format!("Bearer {token}")))
+            .set_json(json!({"tags": {"geo_json": r#"{\"type\":\"Point\"}"#}}))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let area = state.area_repo.select_by_url_alias("test").await?.unwrap();
+        assert!(area.tags.get("geo_json").is_none());
+        Ok(())
+    }
+
     #[test]
     async fn delete_unauthorized() -> Result<()> {
         let state = mock_state().await;
@@ -318,4 +514,93 @@ mod tests {
         assert!(area.unwrap().deleted_at != None);
         Ok(())
     }
+
+    #[test]
+    async fn delete_tag() -> Result<()> {
+        let state = mock_state().await;
+        let token = state.auth.mock_token("test").await.secret;
+        let mut tags = Map::new();
+        tags.insert("url_alias".into(), Value::String("test".into()));
+        tags.insert("icon:square".into(), Value::String("foo".into()));
+        state.area_repo.insert(&tags).await?;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.auth))
+                .app_data(Data::new(AreaRepo::new(&state.pool)))
+                .service(super::delete_tag),
+        )
+        .await;
+        let req = TestRequest::delete()
+            .uri("/test/tags/icon:square")
+            .append_header(("Authorization", format!("Bearer {token}")))
+            .to_request();
+        let res: AreaView = test::call_and_read_body_json(&app, req).await;
+        assert!(!res.tags.contains_key("icon:square"));
+        let area = state.area_repo.select_by_url_alias("test").await?.unwrap();
+        assert!(!area.tags.contains_key("icon:square"));
+        assert!(area.tags.contains_key("url_alias"));
+        let req = TestRequest::delete()
+            .uri("/test/tags/icon:square")
+            .append_header(("Authorization", format!("Bearer {token}")))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        let req = TestRequest::delete()
+            .uri("/unknown/tags/icon:square")
+            .append_header(("Authorization", format!("Bearer {token}")))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        let req = TestRequest::delete()
+            .uri("/test/tags/url_alias")
+            .append_header(("Authorization", format!("Bearer {token}")))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        Ok(())
+    }
+
+    #[test]
+    async fn delete_protected_tag() -> Result<()> {
+        let state = mock_state().await;
+        let token = state.auth.mock_token("test").await.secret;
+        let mut tags = Map::new();
+        tags.insert("url_alias".into(), Value::String("test".into()));
+        tags.insert(
+            "geo_json".into(),
+            json!({"type": "Point", "coordinates": [1.0, 2.0]}),
+        );
+        let area = state.area_repo.insert(&tags).await?;
+        assert!(area.tags.contains_key("box:north"));
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.auth))
+                .app_data(Data::new(AreaRepo::new(&state.pool)))
+                .service(super::delete_tag),
+        )
+        .await;
+        let req = TestRequest::delete()
+            .uri("/test/tags/geo_json")
+            .append_header(("Authorization", format!("Bearer {token}")))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+        let req = TestRequest::delete()
+            .uri("/test/tags/geo_json?force=true")
+            .append_header(("Authorization", format!("Bearer {token}")))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let area = state.area_repo.select_by_url_alias("test").await?.unwrap();
+        assert!(!area.tags.contains_key("geo_json"));
+        assert!(!area.tags.contains_key("box:north"));
+        assert!(!area.tags.contains_key("box:west"));
+        let req = TestRequest::delete()
+            .uri("/test/tags/a%22b")
+            .append_header(("Authorization", format!("Bearer {token}")))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        Ok(())
+    }
 }
@@ -2,5 +2,6 @@ pub mod model;
 pub use model::Area;
 pub use model::AreaRepo;
 pub mod admin;
+pub mod stats;
 pub mod v2;
 pub mod v3;
\ No newline at end of file
@@ -1,5 +1,8 @@
+use crate::area::stats::AreaStats;
 use crate::{Error, Result};
 use deadpool_sqlite::Pool;
+use geo::{BoundingRect, GeometryCollection};
+use geojson::GeoJson;
 use rusqlite::{named_params, Connection, OptionalExtension, Row};
 use serde_json::{Map, Value};
 use std::sync::Arc;
@@ -9,6 +12,7 @@ use tracing::debug;
 #[derive(Clone)]
 pub struct AreaRepo {
     pool: Arc<Pool>,
+    read_pool: Arc<Pool>,
 }
 
 #[derive(PartialEq, Debug)]
@@ -22,7 +26,17 @@ pub struct Area {
 
 impl AreaRepo {
     pub fn new(pool: &Arc<Pool>) -> Self {
-        Self { pool: pool.clone() }
+        Self {
+            pool: pool.clone(),
+            read_pool: pool.clone(),
+        }
+    }
+
+    pub fn with_read_pool(self, read_pool: &Arc<Pool>) -> Self {
+        Self {
+            read_pool: read_pool.clone(),
+            ..self
+        }
     }
 
     pub async fn insert(&self, tags: &Map<String, Value>) -> Result<Area> {
@@ -35,7 +49,7 @@ impl AreaRepo {
     }
 
     pub async fn select_all(&self, limit: Option<i64>) -> Result<Vec<Area>> {
-        self.pool
+        self.read_pool
             .get()
             .await?
             .interact(move |conn| Area::select_all(limit, conn))
@@ -48,30 +62,71 @@ impl AreaRepo {
         limit: Option<i64>,
     ) -> Result<Vec<Area>> {
         let updated_since = updated_since.clone();
-        self.pool
+        self.read_pool
             .get()
             .await?
             .interact(move |conn| Area::select_updated_since(&updated_since, limit, conn))
             .await?
     }
 
+    pub async fn select_count(&self, updated_since: Option<OffsetDateTime>) -> Result<i64> {
+        self.read_pool
+            .get()
+            .await?
+            .interact(move |conn| Area::select_count(updated_since.as_ref(), conn))
+            .await?
+    }
+
+    pub async fn select_max_updated_at(&self) -> Result<Option<OffsetDateTime>> {
+        self.read_pool
+            .get()
+            .await?
+            .interact(|conn| Area::select_max_updated_at(conn))
+            .await?
+    }
+
+    pub async fn select_by_name_like(&self, name: &str, limit: i64) -> Result<Vec<Area>> {
+        let name = name.to_string();
+        self.read_pool
+            .get()
+            .await?
+            .interact(move |conn| Area::select_by_name_like(&name, limit, conn))
+            .await?
+    }
+
     pub async fn select_by_id(&self, id: i64) -> Result<Option<Area>> {
-        self.pool
+        self.read_pool
             .get()
             .await?
             .interact(move |conn| Area::select_by_id(id, conn))
             .await?
     }
 
+    pub async fn select_by_ids(&self, ids: Vec<i64>) -> Result<Vec<Area>> {
+        self.read_pool
+            .get()
+            .await?
+            .interact(move |conn| Area::select_by_ids(&ids, conn))
+            .await?
+    }
+
     pub async fn select_by_url_alias(&self, url_alias: &str) -> Result<Option<Area>> {
         let url_alias = url_alias.to_string();
-        self.pool
+        self.read_pool
             .get()
             .await?
             .interact(move |conn| Area::select_by_url_alias(&url_alias, conn))
             .await?
     }
 
+    pub async fn select_stats(&self, id: i64) -> Result<Option<AreaStats>> {
+        self.read_pool
+            .get()
+            .await?
+            .interact(move |conn| AreaStats::select_by_area_id(id, conn))
+            .await?
+    }
+
     pub async fn patch_tags(&self, id: i64, tags: &Map<String, Value>) -> Result<Area> {
         let tags = tags.clone();
         self.pool
@@ -81,6 +136,15 @@ impl AreaRepo {
             .await?
     }
 
+    pub async fn remove_tag(&self, id: i64, name: &str) -> Result<Area> {
+        let name = name.to_string();
+        self.pool
+            .get()
+            .await?
+            .interact(move |conn| Area::_remove_tag(id, &name, conn))
+            .await?
+    }
+
     #[cfg(test)]
     pub async fn set_updated_at(&self, id: i64, updated_at: &OffsetDateTime) -> Result<Area> {
         let updated_at = updated_at.clone();
@@ -114,6 +178,11 @@ const COL_DELETED_AT: &str = "deleted_at";
 
 impl Area {
     pub fn insert(tags: &Map<String, Value>, conn: &Connection) -> Result<Area> {
+        let mut tags = tags.clone();
+        if let Some(geo_json) = tags.get("geo_json") {
+            let bbox_tags = bbox_tags(geo_json);
+            tags.extend(bbox_tags.into_iter().filter(|(_, v)| !v.is_null()));
+        }
         let query = format!(
             r#"
                 INSERT INTO {TABLE} ({COL_TAGS}) 
@@ -123,7 +192,7 @@ impl Area {
         debug!(query);
         conn.execute(
             &query,
-            named_params! { ":tags": serde_json::to_string(tags)? },
+            named_params! { ":tags": serde_json::to_string(&tags)? },
         )?;
         Ok(Area::select_by_id(conn.last_insert_rowid(), conn)?
             .ok_or(Error::Rusqlite(rusqlite::Error::QueryReturnedNoRows))?)
@@ -175,6 +244,69 @@ impl Area {
             .collect::<Result<Vec<_>, _>>()?)
     }
 
+    // Newest change in the whole table, deleted rows included
+    pub fn select_max_updated_at(conn: &Connection) -> Result<Option<OffsetDateTime>> {
+        let query = format!(
+            r#"
+                SELECT max({COL_UPDATED_AT})
+                FROM {TABLE}
+            "#
+        );
+        debug!(query);
+        Ok(conn.query_row(&query, [], |row| row.get(0))?)
+    }
+
+    pub fn select_count(updated_since: Option<&OffsetDateTime>, conn: &Connection) -> Result<i64> {
+        let query = format!(
+            r#"
+                SELECT count(*)
+                FROM {TABLE}
+                WHERE {COL_UPDATED_AT} > :updated_since
+            "#
+        );
+        let updated_since = match updated_since {
+            Some(updated_since) => updated_since.format(&Rfc3339)?,
+            None => "".into(),
+        };
+        debug!(query);
+        Ok(conn.query_row(
+            &query,
+            named_params! { ":updated_since": updated_since },
+            |row| row.get(0),
+        )?)
+    }
+
+    // SQLite LIKE is case-insensitive for ASCII characters. Areas without url_alias can't be
+    // opened by clients, so they're skipped here rather than after the limit is applied
+    pub fn select_by_name_like(name: &str, limit: i64, conn: &Connection) -> Result<Vec<Area>> {
+        let query = format!(
+            r#"
+                SELECT {ALL_COLUMNS}
+                FROM {TABLE}
+                WHERE json_extract({COL_TAGS}, '$.name') LIKE :name ESCAPE '\'
+                AND json_extract({COL_TAGS}, '$.url_alias') IS NOT NULL
+                AND {COL_DELETED_AT} IS NULL
+                ORDER BY json_extract({COL_TAGS}, '$.name'), {COL_ROWID}
+                LIMIT :limit
+            "#
+        );
+        debug!(query);
+        let name = name
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_");
+        Ok(conn
+            .prepare(&query)?
+            .query_map(
+                named_params! {
+                    ":name": format!("%{name}%"),
+                    ":limit": limit,
+                },
+                Self::mapper(),
+            )?
+            .collect::<Result<Vec<_>, _>>()?)
+    }
+
     pub fn select_by_id(id: i64, conn: &Connection) -> Result<Option<Area>> {
         let query = format!(
             r#"
@@ -189,6 +321,25 @@ impl Area {
             .optional()?)
     }
 
+    // Unknown ids are left out, the order of the result doesn't follow the order of ids
+    pub fn select_by_ids(ids: &[i64], conn: &Connection) -> Result<Vec<Area>> {
+        let query = format!(
+            r#"
+                SELECT {ALL_COLUMNS}
+                FROM {TABLE}
+                WHERE {COL_ROWID} IN (SELECT value FROM json_each(:ids))
+            "#
+        );
+        debug!(query);
+        Ok(conn
+            .prepare(&query)?
+            .query_map(
+                named_params! { ":ids": serde_json::to_string(ids)? },
+                Self::mapper(),
+            )?
+            .collect::<Result<Vec<_>, _>>()?)
+    }
+
     pub fn select_by_url_alias(url_alias: &str, conn: &Connection) -> Result<Option<Area>> {
         let query = format!(
             r#"
@@ -212,6 +363,11 @@ impl Area {
     }
 
     pub fn _patch_tags(id: i64, tags: &Map<String, Value>, conn: &Connection) -> Result<Area> {
+        let mut tags = tags.clone();
+        if let Some(geo_json) = tags.get("geo_json") {
+            let bbox_tags = bbox_tags(geo_json);
+            tags.extend(bbox_tags);
+        }
         let query = format!(
             r#"
                 UPDATE {TABLE}
@@ -224,7 +380,33 @@ impl Area {
             &query,
             named_params! {
                 ":id": id,
-                ":tags": serde_json::to_string(tags)?,
+                ":tags": serde_json::to_string(&tags)?,
+            },
+        )?;
+        Ok(Area::select_by_id(id, &conn)?
+            .ok_or(Error::Rusqlite(rusqlite::Error::QueryReturnedNoRows))?)
+    }
+
+    pub fn _remove_tag(id: i64, name: &str, conn: &Connection) -> Result<Area> {
+        // Box tags are derived from geo_json, so they have to go away with it
+        if name == "geo_json" {
+            let mut tags = Map::new();
+            tags.insert("geo_json".into(), Value::Null);
+            return Area::_patch_tags(id, &tags, conn);
+        }
+        let query = format!(
+            r#"
+                UPDATE {TABLE}
+                SET {COL_TAGS} = json_remove({COL_TAGS}, :name)
+                WHERE {COL_ROWID} = :id
+            "#
+        );
+        debug!(query);
+        conn.execute(
+            &query,
+            named_params! {
+                ":id": id,
+                ":name": format!("$.\"{name}\""),
             },
         )?;
         Ok(Area::select_by_id(id, &conn)?
@@ -322,13 +504,29 @@ impl Area {
     }
 }
 
+// Legacy clients read area bounds from the box:* tags, so they have to follow geo_json. Invalid
+// or missing geo_json yields nulls, which remove the stale bounds when patched
+fn bbox_tags(geo_json: &Value) -> Map<String, Value> {
+    let bbox = GeoJson::from_json_value(geo_json.clone())
+        .ok()
+        .and_then(|it| geojson::quick_collection(&it).ok())
+        .and_then(|it: GeometryCollection<f64>| it.bounding_rect());
+    let mut tags = Map::new();
+    tags.insert("box:north".into(), bbox.map(|it| it.max().y).into());
+    tags.insert("box:south".into(), bbox.map(|it| it.min().y).into());
+    tags.insert("box:east".into(), bbox.map(|it| it.max().x).into());
+    tags.insert("box:west".into(), bbox.map(|it| it.min().x).into());
+    tags
+}
+
 #[cfg(test)]
 mod test {
+    use super::Area;
     use crate::{
         test::{mock_state, mock_tags},
         Result,
     };
-    use serde_json::{json, Map};
+    use serde_json::{json, Map, Value};
     use time::{macros::datetime, OffsetDateTime};
     use tokio::test;
 
@@ -392,6 +590,21 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    async fn select_by_ids() -> Result<()> {
+        let state = mock_state().await;
+        let area_1 = state.area_repo.insert(&Map::new()).await?;
+        state.area_repo.insert(&Map::new()).await?;
+        let area_3 = state.area_repo.insert(&Map::new()).await?;
+        let mut res = state
+            .area_repo
+            .select_by_ids(vec![area_3.id, area_1.id, 100])
+            .await?;
+        res.sort_by_key(|it| it.id);
+        assert_eq!(vec![area_1, area_3], res);
+        Ok(())
+    }
+
     #[test]
     async fn select_by_url_alias() -> Result<()> {
         let state = mock_state().await;
@@ -409,6 +622,39 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    async fn select_by_name_like() -> Result<()> {
+        let state = mock_state().await;
+        for name in ["Berlin", "Berliner Umland", "100%_bitcoin"] {
+            let mut tags = Map::new();
+            tags.insert("name".into(), json!(name));
+            tags.insert("url_alias".into(), json!(name.to_lowercase()));
+            state.area_repo.insert(&tags).await?;
+        }
+        let mut tags = Map::new();
+        tags.insert("name".into(), json!("Berl"));
+        state.area_repo.insert(&tags).await?;
+        let names = |areas: Vec<Area>| -> Vec<String> {
+            areas
+                .into_iter()
+                .map(|it| it.tags["name"].as_str().unwrap().to_string())
+                .collect()
+        };
+        assert_eq!(
+            vec!["Berlin", "Berliner Umland"],
+            names(state.area_repo.select_by_name_like("BERL", 10).await?),
+        );
+        assert_eq!(
+            vec!["100%_bitcoin"],
+            names(state.area_repo.select_by_name_like("%_", 10).await?),
+        );
+        assert_eq!(
+            vec!["Berlin"],
+            names(state.area_repo.select_by_name_like("berl", 1).await?),
+        );
+        Ok(())
+    }
+
     #[test]
     async fn patch_tags() -> Result<()> {
         let state = mock_state().await;
@@ -440,4 +686,42 @@ mod test {
         assert!(area.deleted_at.is_none());
         Ok(())
     }
+
+    #[test]
+    async fn bbox_tags() -> Result<()> {
+        let state = mock_state().await;
+        let polygon = json!({
+            "type": "Polygon",
+            "coordinates": [[[10.0, 50.0], [12.5, 49.0], [11.0, 53.0], [10.0, 50.0]]]
+        });
+        let mut tags = Map::new();
+        tags.insert("geo_json".into(), polygon.clone());
+        let area = state.area_repo.insert(&tags).await?;
+        for point in polygon["coordinates"][0].as_array().unwrap() {
+            let (lon, lat) = (point[0].as_f64().unwrap(), point[1].as_f64().unwrap());
+            assert!(lat <= area.tags["box:north"].as_f64().unwrap());
+            assert!(lat >= area.tags["box:south"].as_f64().unwrap());
+            assert!(lon <= area.tags["box:east"].as_f64().unwrap());
+            assert!(lon >= area.tags["box:west"].as_f64().unwrap());
+        }
+        assert_eq!(json!(53.0), area.tags["box:north"]);
+        assert_eq!(json!(49.0), area.tags["box:south"]);
+        assert_eq!(json!(12.5), area.tags["box:east"]);
+        assert_eq!(json!(10.0), area.tags["box:west"]);
+
+        let mut patch = Map::new();
+        patch.insert(
+            "geo_json".into(),
+            json!({ "type": "Point", "coordinates": [1.0, 2.0] }),
+        );
+        let area = state.area_repo.patch_tags(area.id, &patch).await?;
+        assert_eq!(json!(2.0), area.tags["box:north"]);
+        assert_eq!(json!(1.0), area.tags["box:west"]);
+
+        let mut patch = Map::new();
+        patch.insert("geo_json".into(), Value::Null);
+        let area = state.area_repo.patch_tags(area.id, &patch).await?;
+        assert!(!area.tags.contains_key("box:north"));
+        Ok(())
+    }
 }
@@ -0,0 +1,141 @@
+use crate::area::Area;
+use crate::element::Element;
+use crate::Result;
+use rusqlite::{named_params, Connection, OptionalExtension, Row};
+use std::collections::HashMap;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tracing::debug;
+use tracing::info;
+
+// Counting elements takes a pass over the whole element table, so it's done once per sync
+// instead of on every request
+#[derive(PartialEq, Debug)]
+pub struct AreaStats {
+    pub area_id: i64,
+    pub elements: i64,
+    pub up_to_date_elements: i64,
+    pub onchain_elements: i64,
+    pub lightning_elements: i64,
+    pub lightning_contactless_elements: i64,
+    pub updated_at: OffsetDateTime,
+}
+
+const TABLE: &str = "area_stats";
+const ALL_COLUMNS: &str = "area_id, elements, up_to_date_elements, onchain_elements, lightning_elements, lightning_contactless_elements, updated_at";
+const COL_AREA_ID: &str = "area_id";
+
+impl AreaStats {
+    // Relies on the element areas tag, so it should run after areas are assigned
+    pub fn recompute(now: &OffsetDateTime, conn: &Connection) -> Result<()> {
+        let mut stats: HashMap<i64, AreaStats> = Area::select_all(None, conn)?
+            .into_iter()
+            .filter(|it| it.deleted_at.is_none())
+            .map(|it| (it.id, AreaStats::empty(it.id, now)))
+            .collect();
+        let elements: Vec<Element> = Element::select_all(None, conn)?
+            .into_iter()
+            .filter(|it| it.deleted_at.is_none())
+            .collect();
+        for element in &elements {
+            let Some(areas) = element.tag("areas").as_array() else {
+                continue;
+            };
+            for area_id in areas.iter().filter_map(|it| it["id"].as_i64()) {
+                let Some(area_stats) = stats.get_mut(&area_id) else {
+                    continue;
+                };
+                area_stats.add(element);
+            }
+        }
+        conn.execute(&format!("DELETE FROM {TABLE}"), [])?;
+        for area_stats in stats.values() {
+            area_stats.insert(conn)?;
+        }
+        info!(areas = stats.len(), "Recomputed area stats");
+        Ok(())
+    }
+
+    fn empty(area_id: i64, now: &OffsetDateTime) -> AreaStats {
+        AreaStats {
+            area_id,
+            elements: 0,
+            up_to_date_elements: 0,
+            onchain_elements: 0,
+            lightning_elements: 0,
+            lightning_contactless_elements: 0,
+            updated_at: *now,
+        }
+    }
+
+    fn add(&mut self, element: &Element) {
+        let tag = |name| element.overpass_data.tag(name) == "yes";
+        self.elements += 1;
+        self.up_to_date_elements += element.up_to_date() as i64;
+        self.onchain_elements += tag("payment:onchain") as i64;
+        self.lightning_elements += tag("payment:lightning") as i64;
+        self.lightning_contactless_elements += tag("payment:lightning_contactless") as i64;
+    }
+
+    fn insert(&self, conn: &Connection) -> Result<()> {
+        let query = format!(
+            r#"
+                INSERT INTO {TABLE} ({ALL_COLUMNS})
+                VALUES (
+                    :area_id,
+                    :elements,
+                    :up_to_date_elements,
+                    :onchain_elements,
+                    :lightning_elements,
+                    :lightning_contactless_elements,
+                    :updated_at
+                )
+            "#
+        );
+        debug!(query);
+        conn.execute(
+            &query,
+            named_params! {
+                ":area_id": self.area_id,
+                ":elements": self.elements,
+                ":up_to_date_elements": self.up_to_date_elements,
+                ":onchain_elements": self.onchain_elements,
+                ":lightning_elements": self.lightning_elements,
+                ":lightning_contactless_elements": self.lightning_contactless_elements,
+                ":updated_at": self.updated_at.format(&Rfc3339)?,
+            },
+        )?;
+        Ok(())
+    }
+
+    pub fn select_by_area_id(area_id: i64, conn: &Connection) -> Result<Option<AreaStats>> {
+        let query = format!(
+            r#"
+                SELECT {ALL_COLUMNS}
+                FROM {TABLE}
+                WHERE {COL_AREA_ID} = :area_id
+            "#
+        );
+        debug!(query);
+        Ok(conn
+            .query_row(
+                &query,
+                named_params! { ":area_id": area_id },
+                Self::mapper(),
+            )
+            .optional()?)
+    }
+
+    const fn mapper() -> fn(&Row) -> rusqlite::Result<AreaStats> {
+        |row: &Row| -> rusqlite::Result<AreaStats> {
+            Ok(AreaStats {
+                area_id: row.get(0)?,
+                elements: row.get(1)?,
+                up_to_date_elements: row.get(2)?,
+                onchain_elements: row.get(3)?,
+                lightning_elements: row.get(4)?,
+                lightning_contactless_elements: row.get(5)?,
+                updated_at: row.get(6)?,
+            })
+        }
+    }
+}
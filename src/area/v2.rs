@@ -1,35 +1,135 @@
+use crate::area::stats::AreaStats;
 use crate::area::Area;
 use crate::area::AreaRepo;
+use crate::element;
+use crate::element::find_areas;
+use crate::element::ElementRepo;
+use crate::event;
+use crate::event::model::EventRepo;
+use crate::report::model::ReportRepo;
+use crate::report::v2::parse_date;
+use crate::server::last_modified;
+use crate::server::limit::resolve_limit;
+use crate::server::limit::resolve_sync_limit;
+use crate::server::strict;
+use crate::server::timestamp;
+use crate::server::TOTAL_COUNT_HEADER;
 use crate::Error;
 use actix_web::get;
+use actix_web::route;
 use actix_web::web::Data;
 use actix_web::web::Json;
 use actix_web::web::Path;
 use actix_web::web::Query;
 use actix_web::web::Redirect;
+use actix_web::CustomizeResponder;
 use actix_web::Either;
+use actix_web::HttpRequest;
+use actix_web::Responder;
+use geo::coord;
 use serde::Deserialize;
 use serde::Serialize;
 use serde_json::Map;
 use serde_json::Value;
-use time::format_description::well_known::Rfc3339;
+use time::Duration;
 use time::OffsetDateTime;
 
 #[derive(Deserialize)]
 pub struct GetArgs {
     #[serde(default)]
-    #[serde(with = "time::serde::rfc3339::option")]
+    #[serde(with = "crate::server::updated_since::option")]
     updated_since: Option<OffsetDateTime>,
     limit: Option<i64>,
 }
 
+const GET_ARGS: [&str; 2] = ["updated_since", "limit"];
+
+#[derive(Deserialize)]
+pub struct SearchArgs {
+    q: String,
+    limit: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct GetEventsArgs {
+    limit: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct GetElementsArgs {
+    limit: Option<i64>,
+    #[serde(default)]
+    offset: i64,
+    payment: Option<String>,
+}
+
+const GET_ELEMENTS_ARGS: [&str; 3] = ["limit", "offset", "payment"];
+
+// Matched against payment:{method}=yes tags
+const PAYMENT_METHODS: [&str; 3] = ["onchain", "lightning", "lightning_contactless"];
+
+#[derive(Serialize, Deserialize)]
+pub struct GetStatsItem {
+    pub elements: i64,
+    pub up_to_date_elements: i64,
+    pub onchain_elements: i64,
+    pub lightning_elements: i64,
+    pub lightning_contactless_elements: i64,
+    #[serde(with = "crate::server::timestamp")]
+    pub updated_at: OffsetDateTime,
+}
+
+impl Into<GetStatsItem> for AreaStats {
+    fn into(self) -> GetStatsItem {
+        GetStatsItem {
+            elements: self.elements,
+            up_to_date_elements: self.up_to_date_elements,
+            onchain_elements: self.onchain_elements,
+            lightning_elements: self.lightning_elements,
+            lightning_contactless_elements: self.lightning_contactless_elements,
+            updated_at: self.updated_at,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct GetReportSeriesArgs {
+    tag: String,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct GetReportSeriesItem {
+    pub date: String,
+    pub value: Value,
+}
+
+#[derive(Deserialize)]
+pub struct GetReportChangesArgs {
+    from: String,
+    to: String,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct GetReportChangesItem {
+    pub created: Vec<String>,
+    pub deleted: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ContainingArgs {
+    lat: f64,
+    lon: f64,
+}
+
+const MAX_SEARCH_LIMIT: i64 = 100;
+
 #[derive(Serialize, Deserialize)]
 pub struct GetItem {
     pub id: String,
     pub tags: Map<String, Value>,
-    #[serde(with = "time::serde::rfc3339")]
+    #[serde(with = "crate::server::timestamp")]
     pub created_at: OffsetDateTime,
-    #[serde(with = "time::serde::rfc3339")]
+    #[serde(with = "crate::server::timestamp")]
     pub updated_at: OffsetDateTime,
     pub deleted_at: String,
 }
@@ -43,7 +143,7 @@ impl Into<GetItem> for Area {
             updated_at: self.updated_at,
             deleted_at: self
                 .deleted_at
-                .map(|it| it.format(&Rfc3339).unwrap())
+                .map(|it| timestamp::format(&it))
                 .unwrap_or_default()
                 .into(),
         }
@@ -56,31 +156,266 @@ impl Into<Json<GetItem>> for Area {
     }
 }
 
-#[get("")]
+#[route("", method = "GET", method = "HEAD")]
 async fn get(
+    req: HttpRequest,
     args: Query<GetArgs>,
     repo: Data<AreaRepo>,
-) -> Result<Either<Json<Vec<GetItem>>, Redirect>, Error> {
+) -> Result<Either<CustomizeResponder<Json<Vec<GetItem>>>, Redirect>, Error> {
+    strict::check(req.query_string(), &GET_ARGS)?;
     if args.limit.is_none() && args.updated_since.is_none() {
         return Ok(Either::Right(
             Redirect::to("https://static.btcmap.org/api/v2/areas.json").permanent(),
         ));
     }
 
-    Ok(Either::Left(Json(match &args.updated_since {
+    let limit = match args.updated_since {
+        Some(_) => resolve_sync_limit(args.limit),
+        None => Some(resolve_limit(args.limit)),
+    };
+
+    let total = repo.select_count(args.updated_since).await?;
+
+    let items: Vec<GetItem> = match &args.updated_since {
         Some(updated_since) => repo
-            .select_updated_since(updated_since, args.limit)
+            .select_updated_since(updated_since, limit)
             .await?
             .into_iter()
             .map(|it| it.into())
             .collect(),
         None => repo
-            .select_all(args.limit)
+            .select_all(limit)
+            .await?
+            .into_iter()
+            .map(|it| it.into())
+            .collect(),
+    };
+
+    let last_modified = repo.select_max_updated_at().await?;
+    Ok(Either::Left(
+        last_modified::customize(Json(items), last_modified)
+            .insert_header((TOTAL_COUNT_HEADER, total)),
+    ))
+}
+
+#[get("search")]
+async fn search(
+    args: Query<SearchArgs>,
+    repo: Data<AreaRepo>,
+) -> Result<Json<Vec<GetItem>>, Error> {
+    let q = args.q.trim();
+    if q.is_empty() {
+        Err(Error::HttpBadRequest("Search query can't be empty".into()))?
+    }
+    let limit = args
+        .limit
+        .unwrap_or(MAX_SEARCH_LIMIT)
+        .clamp(1, MAX_SEARCH_LIMIT);
+    Ok(Json(
+        repo.select_by_name_like(q, limit)
+            .await?
+            .into_iter()
+            .map(|it| it.into())
+            .collect(),
+    ))
+}
+
+#[get("containing")]
+async fn get_containing(
+    args: Query<ContainingArgs>,
+    repo: Data<AreaRepo>,
+) -> Result<Json<Vec<GetItem>>, Error> {
+    if !(-90.0..=90.0).contains(&args.lat) || !(-180.0..=180.0).contains(&args.lon) {
+        Err(Error::HttpBadRequest(format!(
+            "Invalid coordinate: lat = {}, lon = {}",
+            args.lat, args.lon,
+        )))?
+    }
+    let areas: Vec<Area> = repo
+        .select_all(None)
+        .await?
+        .into_iter()
+        .filter(|it| it.deleted_at.is_none() && it.tags.contains_key("url_alias"))
+        .collect();
+    let coord = coord! { x: args.lon, y: args.lat };
+    let ids: Vec<i64> = find_areas::find_areas_containing(&coord, &areas)?
+        .iter()
+        .map(|it| it.id)
+        .collect();
+    let mut areas: Vec<Area> = areas
+        .into_iter()
+        .filter(|it| ids.contains(&it.id))
+        .collect();
+    areas.sort_by_key(|it| ids.iter().position(|id| *id == it.id));
+    Ok(Json(areas.into_iter().map(|it| it.into()).collect()))
+}
+
+#[get("{url_alias}/events")]
+async fn get_events(
+    url_alias: Path<String>,
+    args: Query<GetEventsArgs>,
+    area_repo: Data<AreaRepo>,
+    event_repo: Data<EventRepo>,
+) -> Result<Json<Vec<event::v2::GetItem>>, Error> {
+    let area = area_repo
+        .select_by_url_alias(&url_alias)
+        .await?
+        .ok_or(Error::HttpNotFound(format!(
+            "Area with url_alias = {url_alias} doesn't exist"
+        )))?;
+    let limit = resolve_limit(args.limit);
+    Ok(Json(
+        event_repo
+            .select_by_area_id(area.id, limit)
+            .await?
+            .into_iter()
+            .map(|it| it.into())
+            .collect(),
+    ))
+}
+
+#[get("{url_alias}/elements")]
+async fn get_elements(
+    req: HttpRequest,
+    url_alias: Path<String>,
+    args: Query<GetElementsArgs>,
+    area_repo: Data<AreaRepo>,
+    element_repo: Data<ElementRepo>,
+) -> Result<Json<Vec<element::v2::GetItem>>, Error> {
+    strict::check(req.query_string(), &GET_ELEMENTS_ARGS)?;
+    if let Some(payment) = &args.payment {
+        if !PAYMENT_METHODS.contains(&payment.as_str()) {
+            return Err(Error::HttpBadRequest(format!(
+                "Invalid payment: {payment}, expected one of {}",
+                PAYMENT_METHODS.join(", "),
+            )));
+        }
+    }
+    let area = area_repo
+        .select_by_url_alias(&url_alias)
+        .await?
+        .ok_or(Error::HttpNotFound(format!(
+            "Area with url_alias = {url_alias} doesn't exist"
+        )))?;
+    Ok(Json(
+        element_repo
+            .select_by_area_id(
+                area.id,
+                args.payment.as_deref(),
+                resolve_limit(args.limit),
+                args.offset.max(0),
+            )
             .await?
             .into_iter()
             .map(|it| it.into())
             .collect(),
-    })))
+    ))
+}
+
+// Stats are recomputed at the end of every sync
+#[get("{url_alias}/stats")]
+async fn get_stats(
+    url_alias: Path<String>,
+    repo: Data<AreaRepo>,
+) -> Result<Json<GetStatsItem>, Error> {
+    let area = repo
+        .select_by_url_alias(&url_alias)
+        .await?
+        .ok_or(Error::HttpNotFound(format!(
+            "Area with url_alias = {url_alias} doesn't exist"
+        )))?;
+    repo.select_stats(area.id)
+        .await?
+        .ok_or(Error::HttpNotFound(format!(
+            "Stats for area {url_alias} haven't been computed yet"
+        )))
+        .map(|it| Json(it.into()))
+}
+
+#[get("{url_alias}/reports/series")]
+async fn get_report_series(
+    url_alias: Path<String>,
+    args: Query<GetReportSeriesArgs>,
+    area_repo: Data<AreaRepo>,
+    report_repo: Data<ReportRepo>,
+) -> Result<Json<Vec<GetReportSeriesItem>>, Error> {
+    if args.tag.trim().is_empty() {
+        return Err(Error::HttpBadRequest("Tag can't be empty".into()));
+    }
+    let area = area_repo
+        .select_by_url_alias(&url_alias)
+        .await?
+        .ok_or(Error::HttpNotFound(format!(
+            "Area with url_alias = {url_alias} doesn't exist"
+        )))?;
+    Ok(Json(
+        report_repo
+            .select_tag_series(area.id, &args.tag)
+            .await?
+            .into_iter()
+            .map(|(date, value)| GetReportSeriesItem {
+                date: date.to_string(),
+                value,
+            })
+            .collect(),
+    ))
+}
+
+// Both dates are inclusive, just like report dates
+#[get("{url_alias}/reports/changes")]
+async fn get_report_changes(
+    url_alias: Path<String>,
+    args: Query<GetReportChangesArgs>,
+    area_repo: Data<AreaRepo>,
+    event_repo: Data<EventRepo>,
+) -> Result<Json<GetReportChangesItem>, Error> {
+    let from = parse_date(&args.from)?;
+    let to = parse_date(&args.to)?;
+    if from > to {
+        return Err(Error::HttpBadRequest(
+            "The from date can't be later than the to date".into(),
+        ));
+    }
+    let area = area_repo
+        .select_by_url_alias(&url_alias)
+        .await?
+        .ok_or(Error::HttpNotFound(format!(
+            "Area with url_alias = {url_alias} doesn't exist"
+        )))?;
+    let events = event_repo
+        .select_by_area_id_and_period(
+            area.id,
+            &from.midnight().assume_utc(),
+            &(to.midnight().assume_utc() + Duration::days(1)),
+        )
+        .await?;
+    let mut res = GetReportChangesItem {
+        created: vec![],
+        deleted: vec![],
+    };
+    for event in events {
+        let bucket = match event.r#type.as_str() {
+            "create" | "restore" => &mut res.created,
+            "delete" => &mut res.deleted,
+            _ => continue,
+        };
+        let element_id = format!("{}:{}", event.element_osm_type, event.element_osm_id);
+        if !bucket.contains(&element_id) {
+            bucket.push(element_id);
+        }
+    }
+    Ok(Json(res))
+}
+
+// Country areas use ISO 3166-1 alpha-2 codes as url aliases
+pub fn parse_country_code(code: &str) -> Result<String, Error> {
+    if code.len() == 2 && code.chars().all(|it| it.is_ascii_alphabetic()) {
+        Ok(code.to_uppercase())
+    } else {
+        Err(Error::HttpBadRequest(format!(
+            "Invalid country code: {code}, expected ISO 3166-1 alpha-2 code"
+        )))
+    }
 }
 
 #[get("{url_alias}")]
@@ -99,11 +434,19 @@ async fn get_by_url_alias(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::element::Element;
+    use crate::event::Event;
+    use crate::osm::osm::OsmUser;
+    use crate::osm::overpass::OverpassElement;
+    use crate::server::limit::MAX_LIMIT;
+    use crate::test::mock_osm_tags;
     use crate::test::mock_state;
+    use crate::user::User;
     use crate::Result;
     use actix_web::test::TestRequest;
     use actix_web::web::scope;
     use actix_web::{test, App};
+    use time::macros::date;
 
     #[test]
     async fn get_empty_table() -> Result<()> {
@@ -155,6 +498,242 @@ mod tests {
         let req = TestRequest::get().uri("/?limit=2").to_request();
         let res: Value = test::call_and_read_body_json(&app, req).await;
         assert_eq!(res.as_array().unwrap().len(), 2);
+        let req = TestRequest::get()
+            .uri("/?updated_since=2020-01-01T00:00:00Z&limit=2")
+            .to_request();
+        let res: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(res.as_array().unwrap().len(), 2);
+        // Limits which aren't positive fall back to the default, larger ones are capped
+        let req = TestRequest::get().uri("/?limit=-1").to_request();
+        let res: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(res.as_array().unwrap().len(), 3);
+        let req = TestRequest::get()
+            .uri(&format!("/?limit={}", MAX_LIMIT + 1))
+            .to_request();
+        let res: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(res.as_array().unwrap().len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    async fn search() -> Result<()> {
+        let state = mock_state().await;
+        for (url_alias, name) in [
+            ("berlin", "Berlin"),
+            ("berliner-umland", "Berliner Umland"),
+            ("paris", "Paris"),
+        ] {
+            let mut tags = Map::new();
+            tags.insert("url_alias".into(), Value::String(url_alias.into()));
+            tags.insert("name".into(), Value::String(name.into()));
+            state.area_repo.insert(&tags).await?;
+        }
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(AreaRepo::new(&state.pool)))
+                .service(super::search),
+        )
+        .await;
+        let req = TestRequest::get().uri("/search?q=berl").to_request();
+        let res: Vec<GetItem> = test::call_and_read_body_json(&app, req).await;
+        let ids: Vec<String> = res.into_iter().map(|it| it.id).collect();
+        assert_eq!(vec!["berlin", "berliner-umland"], ids);
+        let req = TestRequest::get()
+            .uri("/search?q=berl&limit=1")
+            .to_request();
+        let res: Vec<GetItem> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(1, res.len());
+        let req = TestRequest::get().uri("/search?q=").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(400, res.status().as_u16());
+        Ok(())
+    }
+
+    #[test]
+    async fn get_containing() -> Result<()> {
+        let state = mock_state().await;
+        let square = |url_alias: &str, size: f64| {
+            let mut tags = Map::new();
+            tags.insert("url_alias".into(), Value::String(url_alias.into()));
+            tags.insert(
+                "geo_json".into(),
+                serde_json::json!({
+                    "type": "Polygon",
+                    "coordinates": [[
+                        [-size, -size],
+                        [size, -size],
+                        [size, size],
+                        [-size, size],
+                        [-size, -size],
+                    ]],
+                }),
+            );
+            tags
+        };
+        state.area_repo.insert(&square("country", 10.0)).await?;
+        state.area_repo.insert(&square("city", 1.0)).await?;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(AreaRepo::new(&state.pool)))
+                .service(super::get_containing),
+        )
+        .await;
+        let req = TestRequest::get()
+            .uri("/containing?lat=0.5&lon=0.5")
+            .to_request();
+        let res: Vec<GetItem> = test::call_and_read_body_json(&app, req).await;
+        let ids: Vec<String> = res.into_iter().map(|it| it.id).collect();
+        assert_eq!(vec!["city", "country"], ids);
+        let req = TestRequest::get()
+            .uri("/containing?lat=5&lon=5")
+            .to_request();
+        let res: Vec<GetItem> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(1, res.len());
+        let req = TestRequest::get()
+            .uri("/containing?lat=91&lon=5")
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(400, res.status().as_u16());
+        Ok(())
+    }
+
+    #[test]
+    async fn get_events() -> Result<()> {
+        let state = mock_state().await;
+        let mut tags = Map::new();
+        tags.insert("url_alias".into(), Value::String("test".into()));
+        tags.insert(
+            "geo_json".into(),
+            serde_json::json!({
+                "type": "Polygon",
+                "coordinates": [[[0, 0], [1, 0], [1, 1], [0, 1], [0, 0]]],
+            }),
+        );
+        state.area_repo.insert(&tags).await?;
+        let inside = state
+            .pool
+            .get()
+            .await?
+            .interact(|conn| -> Result<Event> {
+                let user = User::insert(1, &OsmUser::mock(), conn)?;
+                let areas = Area::select_all(None, conn)?;
+                let mut events = vec![];
+                for (id, coord) in [(1, 0.5), (2, 5.0)] {
+                    let element = Element::insert(
+                        &OverpassElement {
+                            lat: Some(coord),
+                            lon: Some(coord),
+                            ..OverpassElement::mock(id)
+                        },
+                        conn,
+                    )?;
+                    find_areas::find_and_save(&element, &areas, conn)?;
+                    events.push(Event::insert(user.id, element.id, "create", conn)?);
+                }
+                Ok(events.remove(0))
+            })
+            .await??;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.area_repo))
+                .app_data(Data::new(state.event_repo))
+                .service(super::get_events),
+        )
+        .await;
+        let req = TestRequest::get().uri("/test/events").to_request();
+        let res: Vec<event::v2::GetItem> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(1, res.len());
+        assert_eq!(inside.id, res[0].id);
+        let req = TestRequest::get().uri("/unknown/events").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(404, res.status().as_u16());
+        Ok(())
+    }
+
+    #[test]
+    async fn get_elements() -> Result<()> {
+        let state = mock_state().await;
+        let mut tags = Map::new();
+        tags.insert("url_alias".into(), Value::String("test".into()));
+        tags.insert(
+            "geo_json".into(),
+            serde_json::json!({
+                "type": "Polygon",
+                "coordinates": [[[0, 0], [1, 0], [1, 1], [0, 1], [0, 0]]],
+            }),
+        );
+        state.area_repo.insert(&tags).await?;
+        state
+            .pool
+            .get()
+            .await?
+            .interact(|conn| -> Result<()> {
+                let areas = Area::select_all(None, conn)?;
+                for (id, coord, payment) in [
+                    (1, 0.5, "payment:lightning"),
+                    (2, 0.5, "payment:onchain"),
+                    (3, 0.5, "payment:lightning"),
+                    (4, 5.0, "payment:lightning"),
+                ] {
+                    let element = Element::insert(
+                        &OverpassElement {
+                            lat: Some(coord),
+                            lon: Some(coord),
+                            tags: Some(mock_osm_tags(&[payment, "yes"])),
+                            ..OverpassElement::mock(id)
+                        },
+                        conn,
+                    )?;
+                    find_areas::find_and_save(&element, &areas, conn)?;
+                }
+                Ok(())
+            })
+            .await??;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.area_repo))
+                .app_data(Data::new(state.element_repo))
+                .service(super::get_elements),
+        )
+        .await;
+        let ids =
+            |res: Vec<element::v2::GetItem>| res.into_iter().map(|it| it.id).collect::<Vec<_>>();
+        let req = TestRequest::get().uri("/test/elements").to_request();
+        let res = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(vec!["node:1", "node:2", "node:3"], ids(res));
+        let req = TestRequest::get()
+            .uri("/test/elements?limit=2")
+            .to_request();
+        let res = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(vec!["node:1", "node:2"], ids(res));
+        let req = TestRequest::get()
+            .uri("/test/elements?limit=2&offset=2")
+            .to_request();
+        let res = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(vec!["node:3"], ids(res));
+        let req = TestRequest::get()
+            .uri("/test/elements?payment=lightning&offset=1")
+            .to_request();
+        let res = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(vec!["node:3"], ids(res));
+        let req = TestRequest::get()
+            .uri("/test/elements?payment=cash")
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(400, res.status().as_u16());
+        let req = TestRequest::get()
+            .uri("/test/elements?limit=2&strict=true")
+            .to_request();
+        let res = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(vec!["node:1", "node:2"], ids(res));
+        let req = TestRequest::get()
+            .uri("/test/elements?limti=2&strict=true")
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(400, res.status().as_u16());
+        let req = TestRequest::get().uri("/unknown/elements").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(404, res.status().as_u16());
         Ok(())
     }
 
@@ -178,4 +757,159 @@ mod tests {
         assert_eq!(res.id, area_url_alias);
         Ok(())
     }
+
+    #[test]
+    async fn get_by_url_alias() -> Result<()> {
+        let state = mock_state().await;
+        let mut tags = Map::new();
+        tags.insert("url_alias".into(), Value::String("berlin".into()));
+        tags.insert("name".into(), Value::String("Berlin".into()));
+        state.area_repo.insert(&tags).await?;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(AreaRepo::new(&state.pool)))
+                .service(super::get_by_url_alias),
+        )
+        .await;
+        let req = TestRequest::get().uri("/berlin").to_request();
+        let res: GetItem = test::call_and_read_body_json(&app, req).await;
+        assert_eq!("berlin", res.id);
+        assert_eq!("Berlin", res.tags["name"]);
+        let req = TestRequest::get().uri("/paris").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(404, res.status().as_u16());
+        Ok(())
+    }
+
+    #[test]
+    async fn get_report_series() -> Result<()> {
+        let state = mock_state().await;
+        let mut tags = Map::new();
+        tags.insert("url_alias".into(), Value::String("test".into()));
+        let area = state.area_repo.insert(&tags).await?;
+        for (date, up_to_date_percent) in [
+            (date!(2024 - 01 - 02), Some(50)),
+            (date!(2024 - 01 - 01), Some(40)),
+            (date!(2024 - 01 - 03), None),
+        ] {
+            let mut report_tags = Map::new();
+            report_tags.insert("total_elements".into(), 10.into());
+            if let Some(up_to_date_percent) = up_to_date_percent {
+                report_tags.insert("up_to_date_percent".into(), up_to_date_percent.into());
+            }
+            state
+                .report_repo
+                .insert(area.id, &date, &report_tags)
+                .await?;
+        }
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.area_repo))
+                .app_data(Data::new(state.report_repo))
+                .service(super::get_report_series),
+        )
+        .await;
+        let req = TestRequest::get()
+            .uri("/test/reports/series?tag=up_to_date_percent")
+            .to_request();
+        let res: Vec<GetReportSeriesItem> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            vec![
+                GetReportSeriesItem {
+                    date: "2024-01-01".into(),
+                    value: 40.into(),
+                },
+                GetReportSeriesItem {
+                    date: "2024-01-02".into(),
+                    value: 50.into(),
+                },
+            ],
+            res,
+        );
+        let req = TestRequest::get()
+            .uri("/missing/reports/series?tag=up_to_date_percent")
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(404, res.status().as_u16());
+        Ok(())
+    }
+
+    #[test]
+    async fn get_report_changes() -> Result<()> {
+        let state = mock_state().await;
+        let mut tags = Map::new();
+        tags.insert("url_alias".into(), Value::String("test".into()));
+        tags.insert(
+            "geo_json".into(),
+            serde_json::json!({
+                "type": "Polygon",
+                "coordinates": [[[0, 0], [1, 0], [1, 1], [0, 1], [0, 0]]],
+            }),
+        );
+        state.area_repo.insert(&tags).await?;
+        state
+            .pool
+            .get()
+            .await?
+            .interact(|conn| -> Result<()> {
+                let user = User::insert(1, &OsmUser::mock(), conn)?;
+                let areas = Area::select_all(None, conn)?;
+                for (id, coord, types) in [
+                    (1, 0.5, vec!["create", "update"]),
+                    (2, 0.5, vec!["create"]),
+                    (3, 0.5, vec!["delete"]),
+                    (4, 5.0, vec!["create", "delete"]),
+                ] {
+                    let element = Element::insert(
+                        &OverpassElement {
+                            lat: Some(coord),
+                            lon: Some(coord),
+                            ..OverpassElement::mock(id)
+                        },
+                        conn,
+                    )?;
+                    find_areas::find_and_save(&element, &areas, conn)?;
+                    for r#type in types {
+                        Event::insert(user.id, element.id, r#type, conn)?;
+                    }
+                }
+                Ok(())
+            })
+            .await??;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.area_repo))
+                .app_data(Data::new(state.event_repo))
+                .service(super::get_report_changes),
+        )
+        .await;
+        let today = OffsetDateTime::now_utc().date();
+        let req = TestRequest::get()
+            .uri(&format!("/test/reports/changes?from={today}&to={today}"))
+            .to_request();
+        let res: GetReportChangesItem = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            GetReportChangesItem {
+                created: vec!["node:1".into(), "node:2".into()],
+                deleted: vec!["node:3".into()],
+            },
+            res,
+        );
+        let yesterday = today.previous_day().unwrap();
+        let req = TestRequest::get()
+            .uri(&format!(
+                "/test/reports/changes?from={yesterday}&to={yesterday}"
+            ))
+            .to_request();
+        let res: GetReportChangesItem = test::call_and_read_body_json(&app, req).await;
+        assert!(res.created.is_empty() && res.deleted.is_empty());
+        let req = TestRequest::get()
+            .uri(&format!(
+                "/test/reports/changes?from={today}&to={yesterday}"
+            ))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(400, res.status().as_u16());
+        Ok(())
+    }
 }
@@ -1,10 +1,12 @@
 use crate::area::Area;
 use crate::area::AreaRepo;
+use crate::server::strict;
 use crate::Error;
 use actix_web::get;
 use actix_web::web::Data;
 use actix_web::web::Json;
 use actix_web::web::Query;
+use actix_web::HttpRequest;
 use serde::Deserialize;
 use serde::Serialize;
 use serde_json::Map;
@@ -18,6 +20,8 @@ pub struct GetArgs {
     limit: i64,
 }
 
+const GET_ARGS: [&str; 2] = ["updated_since", "limit"];
+
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
 pub struct GetItem {
     pub id: i64,
@@ -54,7 +58,12 @@ impl Into<Json<GetItem>> for Area {
 }
 
 #[get("")]
-async fn get(args: Query<GetArgs>, repo: Data<AreaRepo>) -> Result<Json<Vec<GetItem>>, Error> {
+async fn get(
+    req: HttpRequest,
+    args: Query<GetArgs>,
+    repo: Data<AreaRepo>,
+) -> Result<Json<Vec<GetItem>>, Error> {
+    strict::check(req.query_string(), &GET_ARGS)?;
     Ok(Json(
         repo.select_updated_since(&args.updated_since, Some(args.limit))
             .await?
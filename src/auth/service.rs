@@ -3,16 +3,32 @@ use crate::{discord, Error};
 use actix_web::{http::header::HeaderMap, HttpRequest};
 use deadpool_sqlite::Pool;
 use rusqlite::Connection;
+use std::env;
 use std::sync::Arc;
 use tracing::warn;
 
+// Lets admins bootstrap a fresh database before any tokens are stored in it
+const BOOTSTRAP_TOKEN: &str = "BOOTSTRAP_TOKEN";
+const BOOTSTRAP_TOKEN_OWNER: &str = "bootstrap";
+
 pub struct AuthService {
     pool: Arc<Pool>,
+    read_pool: Arc<Pool>,
 }
 
 impl AuthService {
     pub fn new(pool: &Arc<Pool>) -> Self {
-        Self { pool: pool.clone() }
+        Self {
+            pool: pool.clone(),
+            read_pool: pool.clone(),
+        }
+    }
+
+    pub fn with_read_pool(self, read_pool: &Arc<Pool>) -> Self {
+        Self {
+            read_pool: read_pool.clone(),
+            ..self
+        }
     }
 
     #[cfg(test)]
@@ -30,7 +46,7 @@ impl AuthService {
 
     pub async fn check(&self, req: &HttpRequest) -> Result<Token, Error> {
         let headers = req.headers().clone();
-        let guard = self.pool.get().await.unwrap();
+        let guard = self.read_pool.get().await.unwrap();
         let conn = guard.lock().unwrap();
         get_admin_token(&conn, &headers).await
     }
@@ -52,7 +68,15 @@ pub async fn get_admin_token(db: &Connection, headers: &HeaderMap) -> Result<Tok
             "Authorization header is invalid".into(),
         ))?
     }
+    if !auth_header_parts[0].eq_ignore_ascii_case("Bearer") {
+        Err(Error::HttpUnauthorized(
+            "Authorization scheme should be Bearer".into(),
+        ))?
+    }
     let secret = auth_header_parts[1];
+    if let Some(token) = bootstrap_token(secret, env::var(BOOTSTRAP_TOKEN).ok().as_deref()) {
+        return Ok(token);
+    }
     let token = Token::select_by_secret(secret, db)?;
     match token {
         Some(token) => {
@@ -67,6 +91,30 @@ pub async fn get_admin_token(db: &Connection, headers: &HeaderMap) -> Result<Tok
     }
 }
 
+fn bootstrap_token(secret: &str, bootstrap_secret: Option<&str>) -> Option<Token> {
+    let bootstrap_secret = bootstrap_secret.filter(|it| !it.is_empty())?;
+    if !constant_time_eq(secret.as_bytes(), bootstrap_secret.as_bytes()) {
+        return None;
+    }
+    Some(Token {
+        id: 0,
+        owner: BOOTSTRAP_TOKEN_OWNER.into(),
+        secret: secret.into(),
+        created_at: "".into(),
+        updated_at: "".into(),
+        deleted_at: None,
+    })
+}
+
+// Stored tokens are matched by SQLite, but the bootstrap secret is compared in memory, where an
+// early return would tell how many leading bytes were right. Only the length can leak
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
 #[cfg(test)]
 mod tests {
     use crate::auth::AuthService;
@@ -116,6 +164,49 @@ mod tests {
         Ok(())
     }
 
+    #[actix_web::test]
+    async fn auth_scheme() -> Result<()> {
+        let state = mock_state().await;
+        state.auth.mock_token("test").await;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.auth))
+                .service(scope("/").service(get)),
+        )
+        .await;
+        for (header, status) in [
+            ("Basic test", 401),
+            ("bearer test", 200),
+            ("Bearer test", 200),
+        ] {
+            let req = TestRequest::get()
+                .uri("/")
+                .append_header(("Authorization", header))
+                .to_request();
+            let res = test::call_service(&app, req).await;
+            assert_eq!(status, res.status().as_u16(), "{header}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn bootstrap_token() {
+        let token = super::bootstrap_token("secret", Some("secret")).unwrap();
+        assert_eq!("bootstrap", token.owner);
+        assert!(super::bootstrap_token("secreT", Some("secret")).is_none());
+        assert!(super::bootstrap_token("secret1", Some("secret")).is_none());
+        assert!(super::bootstrap_token("secret", None).is_none());
+        assert!(super::bootstrap_token("", Some("")).is_none());
+    }
+
+    #[test]
+    fn constant_time_eq() {
+        assert!(super::constant_time_eq(b"abc", b"abc"));
+        assert!(!super::constant_time_eq(b"abc", b"abd"));
+        assert!(!super::constant_time_eq(b"abc", b"ab"));
+        assert!(super::constant_time_eq(b"", b""));
+    }
+
     #[get("")]
     async fn get(req: HttpRequest, auth: Data<AuthService>) -> Result<impl Responder, Error> {
         auth.check(&req).await?;
@@ -1,20 +1,21 @@
 use rusqlite::Connection;
 use serde_json::Value;
 use time::{format_description::well_known::Iso8601, Duration, OffsetDateTime};
+use tracing::{error, info};
 
 use crate::{element::Element, Result};
 
 pub fn run(osm_type: &str, osm_id: i64, days: i64, conn: &Connection) -> Result<()> {
-    println!("Boosting element {}:{} for {} days", osm_type, osm_id, days);
+    info!(osm_type, osm_id, days, "Boosting element");
 
     let element = Element::select_by_osm_type_and_id(osm_type, osm_id, conn)?;
 
     match element {
         Some(element) => {
-            println!("Found element {}:{}", osm_type, osm_id);
+            info!(osm_type, osm_id, "Found element");
 
             let boost_expires = element.tag("boost:expires");
-            println!("Existing boost expires on {}", boost_expires);
+            info!(%boost_expires, "Existing boost");
 
             let boost_expires = match boost_expires {
                 Value::String(v) => {
@@ -24,7 +25,8 @@ pub fn run(osm_type: &str, osm_id: i64, days: i64, conn: &Connection) -> Result<
             };
 
             let boost_expires = boost_expires.checked_add(Duration::days(days)).unwrap();
-            println!("New boost expires on {}", boost_expires.format(&Iso8601::DEFAULT)?);
+            let new_boost_expires = boost_expires.format(&Iso8601::DEFAULT)?;
+            info!(%new_boost_expires, "New boost");
 
             element.set_tag(
                 "boost:expires",
@@ -33,7 +35,7 @@ pub fn run(osm_type: &str, osm_id: i64, days: i64, conn: &Connection) -> Result<
             )?;
         }
         None => {
-            eprintln!("Can't find element {}:{}", osm_type, osm_id);
+            error!(osm_type, osm_id, "Can't find element");
         }
     }
 
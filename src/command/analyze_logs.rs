@@ -7,6 +7,7 @@ use std::{
     collections::{BTreeMap, HashMap},
     io,
 };
+use time::{format_description::well_known::Rfc3339, Duration, OffsetDateTime};
 
 #[derive(Debug, Serialize, Deserialize)]
 struct LogEntry {
@@ -17,6 +18,16 @@ struct LogEntry {
     fields: Map<String, Value>,
 }
 
+#[derive(Debug, PartialEq)]
+struct SyncRun {
+    started_at: OffsetDateTime,
+    duration: Option<Duration>,
+    created: u64,
+    updated: u64,
+    deleted: u64,
+    failed: bool,
+}
+
 pub async fn run() -> Result<()> {
     let mut entries: Vec<LogEntry> = Vec::new();
 
@@ -38,6 +49,12 @@ pub async fn run() -> Result<()> {
         }
     }
 
+    print!("{}", format_sync_runs(&sync_runs(&entries)));
+
+    if entries.is_empty() {
+        return Ok(());
+    }
+
     let period_start = entries.first().unwrap().timestamp;
     let period_end = entries.last().unwrap().timestamp;
     let period_duration = period_end - period_start;
@@ -76,6 +93,10 @@ pub async fn run() -> Result<()> {
         .collect();
     let most_freq_req = serde_json::to_string(&most_frequent_req).unwrap();
 
+    if http_requests.is_empty() {
+        return Ok(());
+    }
+
     let mut res_times_seconds: Vec<f64> = http_requests
         .iter()
         .map(|it| it.fields["res_time_sec"].as_f64().unwrap())
@@ -118,3 +139,165 @@ fn mean(numbers: &Vec<f64>) -> f64 {
     let sum: f64 = numbers.iter().sum();
     sum / numbers.len() as f64
 }
+
+fn message(entry: &LogEntry) -> &str {
+    entry
+        .fields
+        .get("message")
+        .and_then(|it| it.as_str())
+        .unwrap_or_default()
+}
+
+// A run starts with "Starting sync" and ends with either "Finished sync" or a sync error. Runs
+// which never finished (the process was killed, for instance) are counted as failed
+fn sync_runs(entries: &[LogEntry]) -> Vec<SyncRun> {
+    let mut runs = vec![];
+    let mut current: Option<SyncRun> = None;
+
+    for entry in entries {
+        let message = message(entry);
+
+        if message.starts_with("Starting sync") {
+            if let Some(mut unfinished) = current.take() {
+                unfinished.failed = true;
+                runs.push(unfinished);
+            }
+
+            current = Some(SyncRun {
+                started_at: entry.timestamp,
+                duration: None,
+                created: 0,
+                updated: 0,
+                deleted: 0,
+                failed: false,
+            });
+            continue;
+        }
+
+        let Some(mut run) = current.take() else {
+            continue;
+        };
+
+        let count = |field: &str| entry.fields.get(field).and_then(|it| it.as_u64());
+
+        if message == "Finished sync" {
+            run.duration = Some(entry.timestamp - run.started_at);
+            run.created = count("created").unwrap_or_default();
+            run.updated = count("updated").unwrap_or_default();
+            run.deleted = count("deleted").unwrap_or_default();
+            runs.push(run);
+        } else if message == "Failed to sync elements" {
+            run.duration = Some(entry.timestamp - run.started_at);
+            run.failed = true;
+            runs.push(run);
+        } else {
+            current = Some(run);
+        }
+    }
+
+    if let Some(mut unfinished) = current {
+        unfinished.failed = true;
+        runs.push(unfinished);
+    }
+
+    runs.sort_by_key(|it| it.started_at);
+    runs
+}
+
+fn format_sync_runs(runs: &[SyncRun]) -> String {
+    let mut res = format!(
+        "{:<20} {:>10} {:>8} {:>8} {:>8} {:>7}\n",
+        "date", "duration", "created", "updated", "deleted", "status",
+    );
+
+    for run in runs {
+        let date = run
+            .started_at
+            .replace_nanosecond(0)
+            .unwrap_or(run.started_at)
+            .format(&Rfc3339)
+            .unwrap_or_default();
+        let duration = run
+            .duration
+            .map(|it| format!("{:.1}s", it.as_seconds_f64()))
+            .unwrap_or("-".into());
+        let status = if run.failed { "failed" } else { "ok" };
+        res.push_str(&format!(
+            "{:<20} {:>10} {:>8} {:>8} {:>8} {:>7}\n",
+            date, duration, run.created, run.updated, run.deleted, status,
+        ));
+    }
+
+    let failed = runs.iter().filter(|it| it.failed).count();
+    res.push_str(&format!("{} runs, {} failed\n", runs.len(), failed));
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use super::{LogEntry, SyncRun};
+    use time::{macros::datetime, Duration};
+
+    fn entries(lines: &[&str]) -> Vec<LogEntry> {
+        lines
+            .iter()
+            .map(|it| serde_json::from_str(it).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn sync_runs() {
+        let entries = entries(&[
+            r#"{"timestamp":"2024-05-02T10:00:00Z","level":"INFO","target":"btcmap_api::command::sync","fields":{"message":"Starting sync","incremental":false}}"#,
+            r#"{"timestamp":"2024-05-02T10:00:30Z","level":"INFO","target":"btcmap_api::command::sync","fields":{"message":"Finished sync","created":2,"updated":5,"deleted":1}}"#,
+            r#"{"timestamp":"2024-05-01T10:00:00Z","level":"INFO","target":"btcmap_api::command::sync","fields":{"message":"Starting sync","incremental":true}}"#,
+            r#"{"timestamp":"2024-05-01T10:00:10Z","level":"ERROR","target":"btcmap_api","fields":{"message":"Failed to sync elements","e":"OverpassApi"}}"#,
+            r#"{"timestamp":"2024-05-03T10:00:00Z","level":"INFO","target":"btcmap_api::command::sync","fields":{"message":"Starting sync from file","path":"a.json"}}"#,
+        ]);
+        assert_eq!(
+            vec![
+                SyncRun {
+                    started_at: datetime!(2024-05-01 10:00:00 UTC),
+                    duration: Some(Duration::seconds(10)),
+                    created: 0,
+                    updated: 0,
+                    deleted: 0,
+                    failed: true,
+                },
+                SyncRun {
+                    started_at: datetime!(2024-05-02 10:00:00 UTC),
+                    duration: Some(Duration::seconds(30)),
+                    created: 2,
+                    updated: 5,
+                    deleted: 1,
+                    failed: false,
+                },
+                SyncRun {
+                    started_at: datetime!(2024-05-03 10:00:00 UTC),
+                    duration: None,
+                    created: 0,
+                    updated: 0,
+                    deleted: 0,
+                    failed: true,
+                },
+            ],
+            super::sync_runs(&entries),
+        );
+    }
+
+    #[test]
+    fn format_sync_runs() {
+        let entries = entries(&[
+            r#"{"timestamp":"2024-05-02T10:00:00Z","level":"INFO","target":"btcmap_api::command::sync","fields":{"message":"Starting sync"}}"#,
+            r#"{"timestamp":"2024-05-02T10:00:30Z","level":"INFO","target":"btcmap_api::command::sync","fields":{"message":"Finished sync","created":2,"updated":5,"deleted":1}}"#,
+        ]);
+        let table = super::format_sync_runs(&super::sync_runs(&entries));
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(3, lines.len());
+        assert_eq!(
+            "2024-05-02T10:00:00Z      30.0s        2        5        1      ok",
+            lines[1],
+        );
+        assert_eq!("1 runs, 0 failed", lines[2]);
+    }
+}
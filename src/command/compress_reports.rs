@@ -1,32 +1,81 @@
 use crate::{report::Report, Result};
 use rusqlite::Connection;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use tracing::info;
 
-pub fn run(conn: &Connection) -> Result<()> {
+pub fn run(conn: &mut Connection) -> Result<()> {
+    let tx = conn.transaction()?;
+    let deleted_reports = compress(&tx)?;
+    tx.commit()?;
+    info!(deleted_reports, "finished report compression");
+    Ok(())
+}
+
+// Keeps the first and the last report of each run of identical reports, so we still know when
+// the area stopped changing and when it changed again
+fn compress(conn: &Connection) -> Result<usize> {
     let reports: Vec<Report> = Report::select_all(None, conn)?
         .into_iter()
         .filter(|it| it.deleted_at.is_none())
         .collect();
     info!(count = reports.len(), "loaded reports");
-    let mut deleted_reports = 0;
-    let mut map: HashMap<i64, Vec<Report>> = HashMap::new();
+    let mut areas: BTreeMap<i64, Vec<Report>> = BTreeMap::new();
 
     for report in reports {
-        if !map.contains_key(&report.area_id) {
-            map.insert(report.area_id.clone(), vec![]);
-        }
+        areas.entry(report.area_id).or_default().push(report);
+    }
 
-        let prev_entries = map.get_mut(&report.area_id).unwrap();
+    let mut deleted_reports = 0;
 
-        if prev_entries.last().is_none() || prev_entries.last().unwrap().tags != report.tags {
-            prev_entries.push(report);
-        } else {
-            Report::delete_permanently(report.id, conn)?;
-            deleted_reports = deleted_reports + 1;
+    for (area_id, mut reports) in areas {
+        reports.sort_by_key(|it| (it.date, it.id));
+        let mut deleted_area_reports = 0;
+
+        for window in reports.windows(3) {
+            if window[0].tags == window[1].tags && window[1].tags == window[2].tags {
+                Report::delete_permanently(window[1].id, conn)?;
+                deleted_area_reports += 1;
+            }
         }
+
+        if deleted_area_reports > 0 {
+            info!(area_id, deleted_area_reports, "compressed area reports");
+        }
+
+        deleted_reports += deleted_area_reports;
     }
 
-    info!(deleted_reports, "finished report compression");
-    Ok(())
+    Ok(deleted_reports)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{area::Area, report::Report, test::mock_conn, Result};
+    use serde_json::{json, Map, Value};
+    use time::{macros::date, Duration};
+
+    #[test]
+    fn run() -> Result<()> {
+        let mut conn = mock_conn();
+        let mut area_tags = Map::new();
+        area_tags.insert("url_alias".into(), Value::String("test".into()));
+        let area = Area::insert(&area_tags, &conn)?;
+        let mut tags = Map::new();
+        tags.insert("total_elements".into(), json!(5));
+        let mut changed_tags = Map::new();
+        changed_tags.insert("total_elements".into(), json!(6));
+        let start = date!(2024 - 01 - 01);
+        let mut ids = vec![];
+        for day in 0..5 {
+            ids.push(Report::insert(area.id, &(start + Duration::days(day)), &tags, &conn)?.id);
+        }
+        let changed = Report::insert(area.id, &(start + Duration::days(5)), &changed_tags, &conn)?;
+        super::run(&mut conn)?;
+        let remaining: Vec<i64> = Report::select_all(None, &conn)?
+            .into_iter()
+            .map(|it| it.id)
+            .collect();
+        assert_eq!(vec![ids[0], ids[4], changed.id], remaining);
+        Ok(())
+    }
 }
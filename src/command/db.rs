@@ -39,9 +39,7 @@ pub fn pool() -> Result<Pool> {
         .builder(Runtime::Tokio1)?
         .post_create(Hook::Fn(Box::new(|conn, _| {
             let conn = conn.lock().unwrap();
-            conn.pragma_update(None, "journal_mode", "WAL").unwrap();
-            conn.pragma_update(None, "synchronous", "NORMAL").unwrap();
-            conn.pragma_update(None, "foreign_keys", "ON").unwrap();
+            setup_connection(&conn).unwrap();
             // > The default suggested cache size is -2000, which means the cache size is limited to 2048000 bytes of memory
             // Source: https://www.sqlite.org/pragma.html#pragma_cache_size
             // The default page size is 4096 bytes, cache_size sets the number of pages
@@ -51,11 +49,66 @@ pub fn pool() -> Result<Pool> {
         .build()?)
 }
 
+// Sync can hold a write transaction for a long time, server reads use a separate pool of
+// read-only connections and WAL lets them see the last committed state in the meantime
+pub fn read_only_pool() -> Result<Pool> {
+    Ok(Config::new(read_only_uri(&get_file_path()?))
+        .builder(Runtime::Tokio1)?
+        .post_create(Hook::Fn(Box::new(|conn, _| {
+            let conn = conn.lock().unwrap();
+            setup_read_only_connection(&conn).unwrap();
+            Ok(())
+        })))
+        .build()?)
+}
+
 pub fn open_connection() -> Result<Connection> {
     let conn = Connection::open(get_file_path()?)?;
+    setup_connection(&conn)?;
+    Ok(conn)
+}
+
+pub fn setup_connection(conn: &Connection) -> Result<()> {
     conn.pragma_update(None, "journal_mode", "WAL")?;
     conn.pragma_update(None, "synchronous", "NORMAL")?;
-    Ok(conn)
+    // Readers shouldn't fail right away when sync holds a write lock
+    conn.pragma_update(None, "busy_timeout", 5000)?;
+    conn.pragma_update(None, "foreign_keys", "ON")?;
+    Ok(())
+}
+
+pub fn setup_read_only_connection(conn: &Connection) -> Result<()> {
+    conn.pragma_update(None, "busy_timeout", 5000)?;
+    conn.pragma_update(None, "query_only", "ON")?;
+    Ok(())
+}
+
+fn read_only_uri(path: &Path) -> String {
+    let path = path
+        .to_string_lossy()
+        .replace('%', "%25")
+        .replace(' ', "%20")
+        .replace('?', "%3F")
+        .replace('#', "%23");
+    format!("file:{path}?mode=ro")
+}
+
+pub fn vacuum(conn: &Connection) -> Result<()> {
+    let size_before = file_size(conn)?;
+    info!(size_before, "Vacuuming database");
+    conn.execute_batch("VACUUM; ANALYZE;")?;
+    // VACUUM goes through WAL, so the main file doesn't shrink until checkpoint
+    conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+    let size_after = file_size(conn)?;
+    info!(size_before, size_after, "Finished vacuuming database");
+    Ok(())
+}
+
+fn file_size(conn: &Connection) -> Result<u64> {
+    match conn.path() {
+        Some(path) if !path.is_empty() => Ok(std::fs::metadata(path)?.len()),
+        _ => Ok(0),
+    }
 }
 
 pub fn get_file_path() -> Result<PathBuf> {
@@ -124,11 +177,13 @@ fn get_migrations() -> Result<Vec<Migration>> {
     Ok(res)
 }
 
+use std::path::Path;
 use std::path::PathBuf;
 
 #[cfg(test)]
 pub mod test {
     use rusqlite::Connection;
+    use rusqlite::TransactionBehavior;
 
     use crate::Result;
 
@@ -154,4 +209,103 @@ pub mod test {
         assert_eq!(2, schema_ver);
         Ok(())
     }
+
+    #[test]
+    fn setup_connection() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("btcmap-{}.db", std::process::id()));
+        let conn = Connection::open(&path)?;
+        super::setup_connection(&conn)?;
+        let journal_mode: String = conn.query_row("PRAGMA journal_mode", [], |row| row.get(0))?;
+        assert_eq!("wal", journal_mode);
+        let busy_timeout: i64 = conn.query_row("PRAGMA busy_timeout", [], |row| row.get(0))?;
+        assert_eq!(5000, busy_timeout);
+        let foreign_keys: i64 = conn.query_row("PRAGMA foreign_keys", [], |row| row.get(0))?;
+        assert_eq!(1, foreign_keys);
+        drop(conn);
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn read_during_write_transaction() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("btcmap-ro-{}.db", std::process::id()));
+        let mut writer = Connection::open(&path)?;
+        super::setup_connection(&writer)?;
+        writer.execute_batch("CREATE TABLE foo(bar); INSERT INTO foo VALUES (1);")?;
+        let tx = writer.transaction_with_behavior(TransactionBehavior::Exclusive)?;
+        tx.execute("INSERT INTO foo VALUES (2)", [])?;
+        let reader = Connection::open(super::read_only_uri(&path))?;
+        super::setup_read_only_connection(&reader)?;
+        let count: i64 = reader.query_row("SELECT count(*) FROM foo", [], |row| row.get(0))?;
+        assert_eq!(1, count);
+        assert!(reader.execute("INSERT INTO foo VALUES (3)", []).is_err());
+        tx.commit()?;
+        let count: i64 = reader.query_row("SELECT count(*) FROM foo", [], |row| row.get(0))?;
+        assert_eq!(2, count);
+        drop(reader);
+        drop(writer);
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn read_only_uri() {
+        assert_eq!(
+            "file:/tmp/BTC%20Map/a%3Fb%23c%25.db?mode=ro",
+            super::read_only_uri(std::path::Path::new("/tmp/BTC Map/a?b#c%.db")),
+        );
+    }
+
+    #[test]
+    fn vacuum() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("btcmap-vacuum-{}.db", std::process::id()));
+        let conn = Connection::open(&path)?;
+        conn.execute_batch(
+            r#"
+                CREATE TABLE foo(bar TEXT);
+                WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 10000)
+                INSERT INTO foo SELECT hex(randomblob(100)) FROM n;
+                DELETE FROM foo;
+            "#,
+        )?;
+        let size_before = super::file_size(&conn)?;
+        super::vacuum(&conn)?;
+        assert!(super::file_size(&conn)? < size_before);
+        drop(conn);
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn backfill_element_coords() -> Result<()> {
+        let mut conn = Connection::open_in_memory()?;
+        let migrations: Vec<super::Migration> = super::get_migrations()?
+            .into_iter()
+            .filter(|it| it.0 <= 60)
+            .collect();
+        super::execute_migrations(&migrations, &mut conn)?;
+        conn.execute_batch(
+            r#"
+                INSERT INTO element (overpass_data)
+                VALUES (json('{"type": "node", "id": 1, "lat": 1.5, "lon": 2.5}'));
+                INSERT INTO element (overpass_data)
+                VALUES (json('{"type": "way", "id": 2, "bounds": {"minlat": 1.0, "minlon": 2.0, "maxlat": 3.0, "maxlon": 4.0}}'));
+                INSERT INTO element (overpass_data)
+                VALUES (json('{"type": "relation", "id": 3}'));
+            "#,
+        )?;
+        super::execute_migrations(&super::get_migrations()?, &mut conn)?;
+        let coords: Vec<(Option<f64>, Option<f64>)> = conn
+            .prepare("SELECT lat, lon FROM element ORDER BY id")?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(
+            vec![(Some(1.5), Some(2.5)), (Some(2.0), Some(3.0)), (None, None)],
+            coords,
+        );
+        let indexed: i64 =
+            conn.query_row("SELECT count(*) FROM element_rtree", [], |row| row.get(0))?;
+        assert_eq!(2, indexed);
+        Ok(())
+    }
 }
@@ -0,0 +1,185 @@
+use crate::area::Area;
+use crate::element::Element;
+use crate::event::Event;
+use crate::report::Report;
+use crate::user::User;
+use crate::{area, element, event, report, user, Result};
+use rusqlite::Connection;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use tracing::info;
+
+const TABLES: [&str; 5] = ["elements", "users", "areas", "events", "reports"];
+
+#[derive(PartialEq, Debug)]
+enum Format {
+    Json,
+    NdJson,
+}
+
+#[derive(PartialEq, Debug)]
+struct Args {
+    format: Format,
+    out: String,
+    tables: Vec<String>,
+}
+
+// Usage: export --out=snapshot.json [--format=json|ndjson] [--tables=elements,users]
+pub fn run(args: &[String], conn: &Connection) -> Result<()> {
+    let args = parse_args(args)?;
+    info!(?args, "Exporting snapshot");
+    export(&args, conn)?;
+    info!(out = args.out, "Finished export");
+    Ok(())
+}
+
+fn parse_args(args: &[String]) -> Result<Args> {
+    let mut format = Format::Json;
+    let mut out = None;
+    let mut tables: Vec<String> = TABLES.iter().map(|it| it.to_string()).collect();
+
+    for arg in args {
+        match arg.split_once('=') {
+            Some(("--format", "json")) => format = Format::Json,
+            Some(("--format", "ndjson")) => format = Format::NdJson,
+            Some(("--out", value)) => out = Some(value.to_string()),
+            Some(("--tables", value)) => {
+                tables = value.split(',').map(|it| it.trim().to_string()).collect();
+                if let Some(table) = tables.iter().find(|it| !TABLES.contains(&it.as_str())) {
+                    Err(invalid_input(format!("Unknown table: {table}")))?
+                }
+            }
+            _ => Err(invalid_input(format!("Unknown argument: {arg}")))?,
+        }
+    }
+
+    Ok(Args {
+        format,
+        out: out.ok_or(invalid_input("Missing --out argument".into()))?,
+        tables,
+    })
+}
+
+fn invalid_input(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidInput, message)
+}
+
+fn export(args: &Args, conn: &Connection) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(&args.out)?);
+
+    match args.format {
+        Format::Json => {
+            let mut snapshot = Map::new();
+            for table in &args.tables {
+                snapshot.insert(table.clone(), Value::Array(select_table(table, conn)?));
+            }
+            serde_json::to_writer(&mut writer, &snapshot)?;
+        }
+        Format::NdJson => {
+            for table in &args.tables {
+                for row in select_table(table, conn)? {
+                    writeln!(writer, "{}", json!({ "table": table, "data": row }))?;
+                }
+            }
+        }
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+fn select_table(table: &str, conn: &Connection) -> Result<Vec<Value>> {
+    match table {
+        "elements" => to_values::<_, element::v2::GetItem>(Element::select_all(None, conn)?),
+        "users" => to_values::<_, user::v2::GetItem>(User::select_all(None, conn)?),
+        "areas" => to_values::<_, area::v2::GetItem>(Area::select_all(None, conn)?),
+        "events" => to_values::<_, event::v2::GetItem>(Event::select_all(None, conn)?),
+        "reports" => to_values::<_, report::v2::GetItem>(Report::select_all(None, conn)?),
+        _ => Err(invalid_input(format!("Unknown table: {table}")))?,
+    }
+}
+
+fn to_values<T: Into<I>, I: Serialize>(rows: Vec<T>) -> Result<Vec<Value>> {
+    Ok(rows
+        .into_iter()
+        .map(|it| serde_json::to_value(it.into()))
+        .collect::<Result<Vec<_>, _>>()?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Args, Format};
+    use crate::element::Element;
+    use crate::event::Event;
+    use crate::osm::osm::OsmUser;
+    use crate::osm::overpass::OverpassElement;
+    use crate::test::mock_conn;
+    use crate::user::User;
+    use crate::Result;
+    use serde_json::Value;
+    use std::fs;
+
+    #[test]
+    fn parse_args() -> Result<()> {
+        let args = super::parse_args(&[
+            "--format=ndjson".into(),
+            "--out=snapshot.json".into(),
+            "--tables=elements,users".into(),
+        ])?;
+        assert_eq!(
+            Args {
+                format: Format::NdJson,
+                out: "snapshot.json".into(),
+                tables: vec!["elements".into(), "users".into()],
+            },
+            args,
+        );
+        assert!(super::parse_args(&["--format=json".into()]).is_err());
+        assert!(super::parse_args(&["--out=a".into(), "--tables=foo".into()]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn export() -> Result<()> {
+        let conn = mock_conn();
+        let user = User::insert(1, &OsmUser::mock(), &conn)?;
+        let element = Element::insert(&OverpassElement::mock(1), &conn)?;
+        Element::insert(&OverpassElement::mock(2), &conn)?;
+        Event::insert(user.id, element.id, "create", &conn)?;
+
+        let out = std::env::temp_dir().join(format!("btcmap-export-{}.json", std::process::id()));
+        let out = out.to_str().unwrap().to_string();
+
+        super::run(&[format!("--out={out}")], &conn)?;
+        let snapshot: Value = serde_json::from_str(&fs::read_to_string(&out)?)?;
+        assert_eq!(2, snapshot["elements"].as_array().unwrap().len());
+        assert_eq!(1, snapshot["users"].as_array().unwrap().len());
+        assert_eq!(0, snapshot["areas"].as_array().unwrap().len());
+        assert_eq!(1, snapshot["events"].as_array().unwrap().len());
+        assert_eq!(0, snapshot["reports"].as_array().unwrap().len());
+
+        super::run(
+            &[
+                format!("--out={out}"),
+                "--format=ndjson".into(),
+                "--tables=elements,events".into(),
+            ],
+            &conn,
+        )?;
+        let rows: Vec<Value> = fs::read_to_string(&out)?
+            .lines()
+            .map(|it| serde_json::from_str(it).unwrap())
+            .collect();
+        assert_eq!(3, rows.len());
+        assert_eq!(
+            2,
+            rows.iter().filter(|it| it["table"] == "elements").count()
+        );
+        assert_eq!(1, rows.iter().filter(|it| it["table"] == "events").count());
+
+        fs::remove_file(&out)?;
+        Ok(())
+    }
+}
@@ -1,21 +1,63 @@
 use crate::{area::Area, Result};
 use rusqlite::Connection;
 use serde_json::{Map, Value};
-use tracing::warn;
+use tracing::{info, warn};
 
-pub async fn run(conn: &Connection) -> Result<()> {
+pub async fn run(dry_run: bool, conn: &Connection) -> Result<()> {
+    let changes = fix_tags(dry_run, conn)?;
+    info!(changes = changes.len(), dry_run, "Finished fixing tags");
+    Ok(())
+}
+
+// Returns a list of changes formatted as id: key: old -> new
+fn fix_tags(dry_run: bool, conn: &Connection) -> Result<Vec<String>> {
+    let mut changes = vec![];
     for area in Area::select_all(None, conn)? {
         if let Some(geo_json) = area.tags.get("geo_json") {
             if geo_json.is_string() {
                 warn!(area.id, "Found improperly formatted geo_json tag");
                 let unescaped = geo_json.as_str().unwrap().replace("\\\"", "\"");
-                let geo_json: Value = serde_json::from_str(&unescaped)?;
-                let mut patch_set = Map::new();
-                patch_set.insert("geo_json".into(), geo_json);
-                area.patch_tags(&patch_set, &conn)?;
-                warn!(area.id, "Fixed geo_json tag");
+                let fixed_geo_json: Value = serde_json::from_str(&unescaped)?;
+                let change = format!("{}: geo_json: {} -> {}", area.id, geo_json, fixed_geo_json);
+                info!(change);
+                changes.push(change);
+                if !dry_run {
+                    let mut patch_set = Map::new();
+                    patch_set.insert("geo_json".into(), fixed_geo_json);
+                    area.patch_tags(&patch_set, &conn)?;
+                    warn!(area.id, "Fixed geo_json tag");
+                }
             }
         }
     }
-    Ok(())
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{area::Area, test::mock_conn, Result};
+    use serde_json::{json, Map, Value};
+
+    #[test]
+    fn dry_run() -> Result<()> {
+        let conn = mock_conn();
+        let mut tags = Map::new();
+        tags.insert("url_alias".into(), Value::String("test".into()));
+        tags.insert(
+            "geo_json".into(),
+            Value::String(r#"{\"type\":\"Point\"}"#.into()),
+        );
+        let area = Area::insert(&tags, &conn)?;
+        let changes = super::fix_tags(true, &conn)?;
+        assert_eq!(1, changes.len());
+        assert!(changes[0].starts_with(&format!("{}: geo_json: ", area.id)));
+        assert_eq!(area, Area::select_by_id(area.id, &conn)?.unwrap());
+        super::fix_tags(false, &conn)?;
+        assert_eq!(
+            json!({"type": "Point"}),
+            Area::select_by_id(area.id, &conn)?.unwrap().tags["geo_json"],
+        );
+        assert!(super::fix_tags(true, &conn)?.is_empty());
+        Ok(())
+    }
 }
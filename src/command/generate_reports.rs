@@ -2,15 +2,19 @@ use crate::area::Area;
 use crate::element::Element;
 use crate::report::Report;
 use crate::Result;
+use geo::coord;
+use geo::BoundingRect;
 use geo::Contains;
 use geo::LineString;
 use geo::MultiPolygon;
 use geo::Polygon;
+use geo::Rect;
 use geojson::GeoJson;
 use geojson::Geometry;
 use rusqlite::Connection;
 use serde_json::Map;
 use serde_json::Value;
+use std::collections::BTreeMap;
 use std::collections::HashSet;
 use time::format_description::well_known::Iso8601;
 use time::OffsetDateTime;
@@ -57,6 +61,7 @@ pub async fn run(mut conn: Connection) -> Result<()> {
             continue;
         }
 
+        let mut candidates: Vec<Element> = vec![];
         let mut area_elements: Vec<&Element> = vec![];
         let geo_json = area.tags.get("geo_json").unwrap_or(&Value::Null);
 
@@ -93,7 +98,32 @@ pub async fn run(mut conn: Connection) -> Result<()> {
                 GeoJson::Geometry(v) => geometries.push(v),
             };
 
-            for element in &elements {
+            // Narrow down the elements using the spatial index before doing precise checks
+            let bbox = geometries
+                .iter()
+                .filter_map(|it| geo::Geometry::<f64>::try_from(&it.value).ok())
+                .filter_map(|it| it.bounding_rect())
+                .reduce(|a, b| {
+                    Rect::new(
+                        coord! { x: a.min().x.min(b.min().x), y: a.min().y.min(b.min().y) },
+                        coord! { x: a.max().x.max(b.max().x), y: a.max().y.max(b.max().y) },
+                    )
+                });
+
+            if let Some(bbox) = bbox {
+                candidates = Element::select_by_bbox(
+                    bbox.min().x,
+                    bbox.min().y,
+                    bbox.max().x,
+                    bbox.max().y,
+                    &tx,
+                )?
+                .into_iter()
+                .filter(|it| it.deleted_at.is_none())
+                .collect();
+            }
+
+            for element in &candidates {
                 for geometry in &geometries {
                     match &geometry.value {
                         geojson::Value::MultiPolygon(_) => {
@@ -207,20 +237,21 @@ fn generate_report_tags(elements: &[&Element]) -> Result<Map<String, Value>> {
         .filter(|it| it.overpass_data.tag("payment:bitcoin") == "yes")
         .collect();
 
-    let up_to_date_elements: Vec<_> = elements
-        .iter()
-        .filter(|it| it.overpass_data.up_to_date())
-        .collect();
+    let up_to_date_elements: Vec<_> = elements.iter().filter(|it| it.up_to_date()).collect();
 
     let outdated_elements: Vec<_> = elements
         .iter()
-        .filter(|it| !it.overpass_data.up_to_date())
+        .filter(|it| !it.up_to_date())
         .copied()
         .collect();
 
     let up_to_date_percent: f64 = up_to_date_elements.len() as f64 / elements.len() as f64 * 100.0;
     let up_to_date_percent: i64 = up_to_date_percent as i64;
 
+    // Tracks the migration from payment:bitcoin to the newer payment tags
+    let legacy_percent: f64 = legacy_elements.len() as f64 / elements.len() as f64 * 100.0;
+    let legacy_percent: i64 = legacy_percent as i64;
+
     let mut tags: Map<String, Value> = Map::new();
     tags.insert("total_elements".into(), elements.len().into());
     tags.insert("total_atms".into(), atms.len().into());
@@ -242,20 +273,42 @@ fn generate_report_tags(elements: &[&Element]) -> Result<Map<String, Value>> {
     );
     tags.insert("outdated_elements".into(), outdated_elements.len().into());
     tags.insert("legacy_elements".into(), legacy_elements.len().into());
+    tags.insert("legacy_percent".into(), (legacy_percent as usize).into());
     tags.insert(
         "up_to_date_percent".into(),
         (up_to_date_percent as usize).into(),
     );
 
+    // Category to (total, up to date) element counts
+    let mut categories: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+
+    for element in elements {
+        let counts = categories
+            .entry(element.overpass_data.generate_category())
+            .or_default();
+        counts.0 += 1;
+        if element.up_to_date() {
+            counts.1 += 1;
+        }
+    }
+
+    let up_to_date_by_category: Map<String, Value> = categories
+        .into_iter()
+        .map(|(category, (total, up_to_date))| {
+            let percent = (up_to_date as f64 / total as f64 * 100.0) as usize;
+            (category, percent.into())
+        })
+        .collect();
+    tags.insert(
+        "up_to_date_by_category".into(),
+        up_to_date_by_category.into(),
+    );
+
     let now = OffsetDateTime::now_utc();
 
-    let verification_dates: Vec<i64> = elements
+    let mut verification_dates: Vec<i64> = elements
         .iter()
-        .filter_map(|it| {
-            it.overpass_data
-                .verification_date()
-                .map(|it| it.unix_timestamp())
-        })
+        .filter_map(|it| it.verification_date().map(|it| it.unix_timestamp()))
         .filter_map(|it| {
             if it > now.unix_timestamp() {
                 None
@@ -280,11 +333,36 @@ fn generate_report_tags(elements: &[&Element]) -> Result<Map<String, Value>> {
                     .into(),
             );
         }
+
+        // A few long abandoned elements can drag the average years back, the median isn't affected
+        let median_verification_date = median(&mut verification_dates);
+        let median_verification_date =
+            OffsetDateTime::from_unix_timestamp(median_verification_date);
+
+        if let Ok(median_verification_date) = median_verification_date {
+            tags.insert(
+                "median_verification_date".into(),
+                median_verification_date
+                    .format(&Iso8601::DEFAULT)
+                    .unwrap()
+                    .into(),
+            );
+        }
     }
 
     Ok(tags)
 }
 
+fn median(values: &mut [i64]) -> i64 {
+    values.sort();
+    let mid = values.len() / 2;
+    if values.len() % 2 == 0 {
+        (values[mid - 1] + values[mid]) / 2
+    } else {
+        values[mid]
+    }
+}
+
 async fn insert_report(area_id: i64, tags: &Map<String, Value>, conn: &Connection) -> Result<()> {
     let date = OffsetDateTime::now_utc().date();
     info!(area_id, ?date, ?tags, "Inserting new report");
@@ -296,7 +374,10 @@ async fn insert_report(area_id: i64, tags: &Map<String, Value>, conn: &Connectio
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::{osm::overpass::OverpassElement, test::mock_state};
+    use crate::{
+        osm::overpass::OverpassElement,
+        test::{mock_osm_tags, mock_state},
+    };
     use serde_json::{json, Map};
     use std::collections::HashMap;
     use time::{macros::date, Duration};
@@ -425,4 +506,107 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    async fn median_verification_date() -> Result<()> {
+        let elements: Vec<Element> = [
+            "2013-01-01",
+            "2023-01-01",
+            "2023-01-02",
+            "2023-01-03",
+            "2023-01-04",
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, date)| {
+            let mut overpass_data = OverpassElement::mock(i as i64);
+            overpass_data
+                .tags
+                .as_mut()
+                .unwrap()
+                .insert("check_date:currency:XBT".into(), date.to_string());
+            Element {
+                id: i as i64,
+                overpass_data,
+                tags: HashMap::new(),
+                created_at: OffsetDateTime::now_utc(),
+                updated_at: OffsetDateTime::now_utc(),
+                deleted_at: None,
+            }
+        })
+        .collect();
+        let report_tags = super::generate_report_tags(&elements.iter().collect::<Vec<_>>())?;
+        let median = report_tags["median_verification_date"].as_str().unwrap();
+        let avg = report_tags["avg_verification_date"].as_str().unwrap();
+        assert_eq!("2023-01-02T00:00:00.000000000Z", median);
+        assert!(avg.starts_with("2021-01-01"));
+        Ok(())
+    }
+
+    #[test]
+    async fn up_to_date_by_category() -> Result<()> {
+        let fresh = OffsetDateTime::now_utc().date().to_string();
+        let elements: Vec<Element> = [
+            ("atm", Some(fresh.as_str())),
+            ("atm", Some(fresh.as_str())),
+            ("atm", Some("2013-01-01")),
+            ("restaurant", None),
+            ("restaurant", Some(fresh.as_str())),
+            ("cafe", None),
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, (amenity, check_date))| {
+            let mut overpass_data = OverpassElement::mock(i as i64);
+            let tags = overpass_data.tags.as_mut().unwrap();
+            tags.insert("amenity".into(), amenity.to_string());
+            if let Some(check_date) = check_date {
+                tags.insert("check_date".into(), check_date.to_string());
+            }
+            Element {
+                id: i as i64,
+                overpass_data,
+                tags: HashMap::new(),
+                created_at: OffsetDateTime::now_utc(),
+                updated_at: OffsetDateTime::now_utc(),
+                deleted_at: None,
+            }
+        })
+        .collect();
+        let report_tags = super::generate_report_tags(&elements.iter().collect::<Vec<_>>())?;
+        assert_eq!(
+            json!({"atm": 66, "cafe": 0, "restaurant": 50}),
+            report_tags["up_to_date_by_category"],
+        );
+        Ok(())
+    }
+
+    #[test]
+    async fn legacy_percent() -> Result<()> {
+        let elements: Vec<Element> = [("payment:bitcoin", 1), ("payment:onchain", 2)]
+            .iter()
+            .map(|(tag, id)| Element {
+                id: *id,
+                overpass_data: OverpassElement {
+                    tags: Some(mock_osm_tags(&[tag, "yes"])),
+                    ..OverpassElement::mock(*id)
+                },
+                tags: HashMap::new(),
+                created_at: OffsetDateTime::now_utc(),
+                updated_at: OffsetDateTime::now_utc(),
+                deleted_at: None,
+            })
+            .collect();
+        let report_tags = super::generate_report_tags(&elements.iter().collect::<Vec<_>>())?;
+        assert_eq!(1, report_tags["legacy_elements"].as_i64().unwrap());
+        assert_eq!(50, report_tags["legacy_percent"].as_i64().unwrap());
+        Ok(())
+    }
+
+    #[test]
+    async fn median() -> Result<()> {
+        assert_eq!(2, super::median(&mut [3, 1, 2]));
+        assert_eq!(2, super::median(&mut [4, 1, 3, 1]));
+        Ok(())
+    }
 }
@@ -1,3 +1,5 @@
+use crate::element::find_areas::find_areas;
+use crate::element::Element;
 use crate::{area::Area, Result};
 use rusqlite::Connection;
 use serde::Deserialize;
@@ -27,6 +29,7 @@ pub async fn run(path: &str, conn: &mut Connection) -> Result<()> {
         ))?
     }
     let tx = conn.transaction()?;
+    let mut countries = vec![];
     for dir_entry in path.read_dir().expect("Failed to read files") {
         if let Ok(dir_entry) = dir_entry {
             if !dir_entry.path().is_file() {
@@ -47,18 +50,128 @@ pub async fn run(path: &str, conn: &mut Connection) -> Result<()> {
             let reader = BufReader::new(file);
             let json: CountryJson = serde_json::from_reader(reader)?;
 
-            match Area::select_by_url_alias(&json.id, &tx)? {
+            let country = match Area::select_by_url_alias(&json.id, &tx)? {
                 Some(area) => {
-                    area.patch_tags(&json.tags, &tx)?;
+                    let area = area.patch_tags(&json.tags, &tx)?;
                     info!(json.id, "Patched tags for an existing area");
+                    area
                 }
                 None => {
-                    Area::insert(&json.tags, &tx)?;
+                    let area = Area::insert(&json.tags, &tx)?;
                     info!(json.id, "Inserted area");
+                    area
                 }
-            }
+            };
+            countries.push(country);
         }
     }
+    assign_countries(&countries, &tx)?;
     tx.commit()?;
     Ok(())
 }
+
+// Country areas use ISO 3166-1 alpha-2 codes as url aliases
+fn assign_countries(countries: &Vec<Area>, conn: &Connection) -> Result<()> {
+    let elements: Vec<Element> = Element::select_all(None, conn)?
+        .into_iter()
+        .filter(|it| it.deleted_at.is_none())
+        .collect();
+    info!(
+        elements = elements.len(),
+        countries = countries.len(),
+        "Assigning countries to elements"
+    );
+    let imported_codes: Vec<String> = countries
+        .iter()
+        .filter_map(|it| it.tags.get("url_alias"))
+        .filter_map(|it| it.as_str())
+        .map(|it| it.to_uppercase())
+        .collect();
+    let mut updated_elements = 0;
+    for element in elements {
+        let country = find_areas(&element, countries)?
+            .first()
+            .and_then(|it| it.tags.get("url_alias"))
+            .and_then(|it| it.as_str())
+            .map(|it| Value::String(it.to_uppercase()));
+        match country {
+            Some(country) => {
+                if element.tag("country") != &country {
+                    element.set_tag("country", &country, conn)?;
+                    updated_elements += 1;
+                }
+            }
+            // Imports can be partial, elements of countries which weren't imported should be left alone
+            None => {
+                let old_country = element.tag("country").as_str().unwrap_or_default();
+                if imported_codes.iter().any(|it| it == old_country) {
+                    element.remove_tag("country", conn)?;
+                    updated_elements += 1;
+                }
+            }
+        }
+    }
+    info!(updated_elements, "Assigned countries to elements");
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{area::Area, element::Element, osm::overpass::OverpassElement};
+    use crate::{test::mock_conn, Result};
+    use serde_json::{json, Map, Value};
+
+    #[test]
+    fn assign_countries() -> Result<()> {
+        let conn = mock_conn();
+        let mut tags = Map::new();
+        tags.insert("url_alias".into(), Value::String("de".into()));
+        tags.insert(
+            "geo_json".into(),
+            json!({
+                "type": "Polygon",
+                "coordinates": [[[-1.0, -1.0], [1.0, -1.0], [1.0, 1.0], [-1.0, 1.0], [-1.0, -1.0]]]
+            }),
+        );
+        let country = Area::insert(&tags, &conn)?;
+        let inside = Element::insert(&OverpassElement::mock(1), &conn)?;
+        let outside = Element::insert(
+            &OverpassElement {
+                lat: Some(50.0),
+                lon: Some(50.0),
+                ..OverpassElement::mock(2)
+            },
+            &conn,
+        )?;
+        let outside = outside.set_tag("country", &json!("DE"), &conn)?;
+        let other_country = Element::insert(
+            &OverpassElement {
+                lat: Some(40.0),
+                lon: Some(40.0),
+                ..OverpassElement::mock(3)
+            },
+            &conn,
+        )?;
+        let other_country = other_country.set_tag("country", &json!("FR"), &conn)?;
+        super::assign_countries(&vec![country], &conn)?;
+        assert_eq!(
+            Some("DE"),
+            Element::select_by_id(inside.id, &conn)?
+                .unwrap()
+                .tag("country")
+                .as_str(),
+        );
+        assert!(Element::select_by_id(outside.id, &conn)?
+            .unwrap()
+            .tag("country")
+            .is_null());
+        assert_eq!(
+            Some("FR"),
+            Element::select_by_id(other_country.id, &conn)?
+                .unwrap()
+                .tag("country")
+                .as_str(),
+        );
+        Ok(())
+    }
+}
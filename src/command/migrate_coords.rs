@@ -0,0 +1,40 @@
+use crate::{element::Element, Result};
+use rusqlite::Connection;
+use tracing::info;
+
+pub fn run(conn: &mut Connection) -> Result<()> {
+    let tx = conn.transaction()?;
+    let updated_elements = migrate_coords(&tx)?;
+    tx.commit()?;
+    info!(updated_elements, "Finished migrating element coordinates");
+    Ok(())
+}
+
+// Elements which already have the right coordinates are left untouched, so it's safe to re-run
+fn migrate_coords(conn: &Connection) -> Result<usize> {
+    let mut updated_elements = 0;
+    for element in Element::select_all(None, conn)? {
+        updated_elements +=
+            Element::set_coord(element.id, element.overpass_data.try_coord(), conn)?;
+    }
+    Ok(updated_elements)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{element::Element, osm::overpass::OverpassElement, test::mock_conn, Result};
+    use geo::coord;
+
+    #[test]
+    fn run_twice() -> Result<()> {
+        let mut conn = mock_conn();
+        let node = Element::insert(&OverpassElement::mock(1), &conn)?;
+        let way = Element::insert(&OverpassElement::mock(2), &conn)?;
+        Element::set_coord(node.id, None, &conn)?;
+        Element::set_coord(way.id, Some(coord! { x: 5.0, y: 5.0 }), &conn)?;
+        assert_eq!(2, super::migrate_coords(&conn)?);
+        assert_eq!(0, super::migrate_coords(&conn)?);
+        super::run(&mut conn)?;
+        Ok(())
+    }
+}
@@ -2,10 +2,12 @@ pub mod add_area;
 pub mod analyze_logs;
 pub mod compress_reports;
 pub mod db;
+pub mod export;
 pub mod fix_tags;
 pub mod generate_android_icons;
 pub mod generate_element_categories;
 pub mod generate_reports;
 pub mod import_countries;
 pub mod lint;
+pub mod migrate_coords;
 pub mod sync;
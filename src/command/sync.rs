@@ -1,20 +1,31 @@
+use crate::area::stats::AreaStats;
 use crate::area::Area;
 use crate::discord;
+use crate::discord::ElementAction;
 use crate::element::find_areas;
 use crate::element::Element;
 use crate::event::Event;
 use crate::lint;
 use crate::osm::osm;
 use crate::osm::overpass::query_bitcoin_merchants;
-use crate::osm::overpass::OverpassElement;
+use crate::osm::overpass::ChangeType;
+use crate::osm::overpass::Response;
+use crate::osm::overpass::SyncMode;
+use crate::sync::deleted_notification::DeletedNotification;
+use crate::sync::lock;
+use crate::sync::status::SyncStatus;
 use crate::user::User;
 use crate::Error;
 use crate::Result;
 use rusqlite::Connection;
 use rusqlite::Transaction;
 use serde_json::Value;
+use std::collections::HashMap;
 use std::collections::HashSet;
+use std::fs::File;
+use std::io::BufReader;
 use std::ops::Add;
+use std::time::Instant;
 use std::time::SystemTime;
 use time::format_description::well_known::Rfc3339;
 use time::OffsetDateTime;
@@ -22,22 +33,59 @@ use tracing::error;
 use tracing::info;
 use tracing::warn;
 
-pub async fn run(db: Connection) -> Result<()> {
-    info!(db_path = ?db.path().unwrap(), "Starting sync");
+const LOCK_NAME: &str = "sync";
+
+// Full syncs take minutes, a lock this old was left behind by a crashed sync
+const LOCK_STALE_AFTER: time::Duration = time::Duration::hours(2);
+
+// Elements flapping in and out of Overpass shouldn't flood the channel with deletion alerts
+const DELETED_NOTIFICATION_COOLDOWN: time::Duration = time::Duration::hours(24);
+
+#[derive(Default, Debug, PartialEq)]
+pub struct SyncSummary {
+    pub created: usize,
+    pub updated: usize,
+    pub deleted: usize,
+}
+
+pub async fn run(mut db: Connection, incremental: bool) -> Result<()> {
+    let Some(lock_owner) = acquire_lock(&db)? else {
+        return Ok(());
+    };
+    let res = sync(&mut db, incremental, &lock_owner).await;
+    release_lock(&db, &lock_owner);
+    res
+}
+
+async fn sync(db: &mut Connection, incremental: bool, lock_owner: &str) -> Result<()> {
+    let started_at = Instant::now();
+    info!(db_path = ?db.path().unwrap(), incremental, "Starting sync");
+
+    let mode = if incremental {
+        select_mode(db, OffsetDateTime::now_utc())?
+    } else {
+        SyncMode::Full
+    };
+
+    info!(mode = mode.name(), "Selected sync mode");
 
     let query_elements_start = SystemTime::now();
-    let elements = query_bitcoin_merchants().await?;
+    let response = query_bitcoin_merchants(&mode).await?;
     let query_elements_duration = SystemTime::now()
         .duration_since(query_elements_start)
         .unwrap();
+    refresh_lock(db, lock_owner)?;
 
     let process_elements_start = SystemTime::now();
-    process_elements(elements, db).await?;
+    let summary = process_elements(response, &mode, started_at, db).await?;
     let process_elements_duration = SystemTime::now()
         .duration_since(process_elements_start)
         .unwrap();
 
     info!(
+        created = summary.created,
+        updated = summary.updated,
+        deleted = summary.deleted,
         query_elements_duration_seconds = query_elements_duration.as_secs_f64(),
         process_elements_duration_seconds = process_elements_duration.as_secs_f64(),
         "Finished sync",
@@ -46,7 +94,81 @@ pub async fn run(db: Connection) -> Result<()> {
     Ok(())
 }
 
-async fn process_elements(fresh_elements: Vec<OverpassElement>, mut db: Connection) -> Result<()> {
+fn select_mode(db: &Connection, now: OffsetDateTime) -> Result<SyncMode> {
+    let last_sync = SyncStatus::select_latest(db)?.map(|it| it.timestamp_osm_base);
+    let last_full_sync = SyncStatus::select_latest_by_mode(SyncMode::Full.name(), db)?
+        .map(|it| it.timestamp_osm_base);
+    Ok(SyncMode::select(last_sync, last_full_sync, now))
+}
+
+// Saved Overpass responses are treated as full data sets
+pub async fn run_from_file(mut db: Connection, path: &str) -> Result<()> {
+    let Some(lock_owner) = acquire_lock(&db)? else {
+        return Ok(());
+    };
+    let res = sync_from_file(&mut db, path, &lock_owner).await;
+    release_lock(&db, &lock_owner);
+    res
+}
+
+async fn sync_from_file(db: &mut Connection, path: &str, lock_owner: &str) -> Result<()> {
+    let started_at = Instant::now();
+    info!(db_path = ?db.path().unwrap(), path, "Starting sync from file");
+    let response: Response = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+    info!(
+        elements = response.elements.len(),
+        "Loaded elements from file"
+    );
+    refresh_lock(db, lock_owner)?;
+    let summary = process_elements(response, &SyncMode::Full, started_at, db).await?;
+    info!(
+        created = summary.created,
+        updated = summary.updated,
+        deleted = summary.deleted,
+        "Finished sync",
+    );
+    Ok(())
+}
+
+// Cron and manual syncs can be started at the same time, only one of them should run
+fn acquire_lock(db: &Connection) -> Result<Option<String>> {
+    let owner = lock::acquire(LOCK_NAME, &OffsetDateTime::now_utc(), LOCK_STALE_AFTER, db)?;
+    if owner.is_none() {
+        warn!("Another sync is in progress, exiting");
+    }
+    Ok(owner)
+}
+
+// Every sync step can take minutes, so the lock is refreshed between them. A sync which was too
+// slow and lost its lock to another one should stop, so they don't run at the same time
+fn refresh_lock(db: &Connection, owner: &str) -> Result<()> {
+    if !lock::refresh(LOCK_NAME, owner, &OffsetDateTime::now_utc(), db)? {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            "Sync lock was taken over by another sync",
+        ))?
+    }
+    Ok(())
+}
+
+// Sync errors are more interesting than release errors, so the latter are only logged. A lock
+// which wasn't released becomes stale and gets taken over by a later sync
+fn release_lock(db: &Connection, owner: &str) {
+    if let Err(e) = lock::release(LOCK_NAME, owner, db) {
+        error!(?e, "Failed to release sync lock");
+    }
+}
+
+async fn process_elements(
+    response: Response,
+    mode: &SyncMode,
+    started_at: Instant,
+    db: &mut Connection,
+) -> Result<SyncSummary> {
+    let mut summary = SyncSummary::default();
+    let mut notifications = vec![];
+    let fresh_elements = response.elements;
+    let fresh_elements_len = fresh_elements.len() as i64;
     let tx: Transaction = db.transaction()?;
 
     let cached_elements = Element::select_all(None, &tx)?;
@@ -65,8 +187,10 @@ async fn process_elements(fresh_elements: Vec<OverpassElement>, mut db: Connecti
         .collect();
 
     // First, let's check if any of the cached elements no longer accept bitcoins
+    // Diffs don't include removed elements, so it only makes sense for full syncs
     for cached_element in &cached_elements {
-        if !fresh_element_ids.contains(&cached_element.overpass_data.btcmap_id())
+        if mode == &SyncMode::Full
+            && !fresh_element_ids.contains(&cached_element.overpass_data.btcmap_id())
             && cached_element.deleted_at.is_none()
         {
             warn!(
@@ -110,10 +234,24 @@ async fn process_elements(fresh_elements: Vec<OverpassElement>, mut db: Connecti
                 user_name = fresh_element.user,
                 message,
             );
-            discord::send_message_to_channel(&message, discord::CHANNEL_OSM_CHANGES).await;
+            let now = OffsetDateTime::now_utc();
+            if should_notify_deleted(cached_element.id, &now, &tx)? {
+                notifications.push(Notification {
+                    action: ElementAction::Delete,
+                    element_name: name.into(),
+                    user_name: fresh_element.user.clone(),
+                    osm_url: format!("https://www.openstreetmap.org/{element_type}/{osm_id}"),
+                });
+            } else {
+                info!(
+                    cached_element.id,
+                    "Deletion was reported recently, skipping alert"
+                );
+            }
 
             info!(cached_element.id, "Marking element as deleted");
-            cached_element.set_deleted_at(Some(OffsetDateTime::now_utc()), &tx)?;
+            cached_element.set_deleted_at(Some(now), &tx)?;
+            summary.deleted += 1;
         }
     }
 
@@ -130,6 +268,11 @@ async fn process_elements(fresh_elements: Vec<OverpassElement>, mut db: Connecti
             .find(|it| it.overpass_data.btcmap_id() == btcmap_id)
         {
             Some(cached_element) => {
+                if cached_element.deleted_at.is_some() {
+                    info!(btcmap_id, "Bitcoin tags were re-added");
+                    cached_element.set_deleted_at(None, &tx)?;
+                }
+
                 if fresh_element != cached_element.overpass_data {
                     info!(
                         btcmap_id,
@@ -138,20 +281,45 @@ async fn process_elements(fresh_elements: Vec<OverpassElement>, mut db: Connecti
                         "Element JSON was updated",
                     );
 
+                    let change_type = cached_element.overpass_data.change_type(&fresh_element);
+
+                    if change_type == ChangeType::Metadata {
+                        info!(
+                            btcmap_id,
+                            "Only metadata was changed, skipping update event"
+                        );
+                        cached_element.set_overpass_data(&fresh_element, &tx)?;
+                        continue;
+                    }
+
+                    summary.updated += 1;
+
                     if let Some(user_id) = user_id {
                         insert_user_if_not_exists(user_id, &tx).await?;
                     }
 
-                    if fresh_element.changeset != cached_element.overpass_data.changeset {
-                        let event = Event::insert(
-                            user_id.unwrap().try_into().unwrap(),
-                            cached_element.id,
-                            "update",
-                            &tx,
-                        )?;
+                    let same_changeset =
+                        fresh_element.changeset == cached_element.overpass_data.changeset;
+                    if let (Some(user_id), false) = (user_id, same_changeset) {
+                        let event = Event::insert(user_id, cached_element.id, "update", &tx)?;
+                        let mut event_tags = HashMap::new();
+                        event_tags.insert("change_type".into(), change_type.name().into());
+                        event_tags.insert(
+                            "changed".into(),
+                            cached_element
+                                .overpass_data
+                                .changed_tags(&fresh_element)
+                                .into(),
+                        );
+                        let event = event.patch_tags(&event_tags, &tx)?;
                         on_new_event(&event, &tx).await?;
-                    } else {
+                    } else if same_changeset {
                         warn!("Changeset ID is identical, skipped user event generation");
+                    } else {
+                        warn!(
+                            btcmap_id,
+                            "Element has no uid, skipped user event generation"
+                        );
                     }
 
                     let message = format!("User {user_display_name} updated https://www.openstreetmap.org/{element_type}/{osm_id}");
@@ -162,7 +330,12 @@ async fn process_elements(fresh_elements: Vec<OverpassElement>, mut db: Connecti
                         user_name = user_display_name,
                         message,
                     );
-                    discord::send_message_to_channel(&message, discord::CHANNEL_OSM_CHANGES).await;
+                    notifications.push(Notification {
+                        action: ElementAction::Update,
+                        element_name: name.into(),
+                        user_name: user_display_name.clone(),
+                        osm_url: format!("https://www.openstreetmap.org/{element_type}/{osm_id}"),
+                    });
 
                     info!("Updating osm_json");
                     let mut updated_element =
@@ -186,11 +359,6 @@ async fn process_elements(fresh_elements: Vec<OverpassElement>, mut db: Connecti
                     lint::generate_element_issues(&updated_element, &tx)?;
                     find_areas::find_and_save(&updated_element, &areas, &tx)?;
                 }
-
-                if cached_element.deleted_at.is_some() {
-                    info!(btcmap_id, "Bitcoin tags were re-added");
-                    cached_element.set_deleted_at(None, &tx)?;
-                }
             }
             None => {
                 info!(btcmap_id, "Element does not exist, inserting");
@@ -200,14 +368,18 @@ async fn process_elements(fresh_elements: Vec<OverpassElement>, mut db: Connecti
                 }
 
                 let element = Element::insert(&fresh_element, &tx)?;
+                summary.created += 1;
 
-                let event = Event::insert(
-                    user_id.unwrap().try_into().unwrap(),
-                    element.id,
-                    "create",
-                    &tx,
-                )?;
-                on_new_event(&event, &tx).await?;
+                match user_id {
+                    Some(user_id) => {
+                        let event = Event::insert(user_id, element.id, "create", &tx)?;
+                        on_new_event(&event, &tx).await?;
+                    }
+                    None => warn!(
+                        btcmap_id,
+                        "Element has no uid, skipped user event generation"
+                    ),
+                }
 
                 let category = element.overpass_data.generate_category();
                 let android_icon = element.overpass_data.generate_android_icon();
@@ -229,13 +401,65 @@ async fn process_elements(fresh_elements: Vec<OverpassElement>, mut db: Connecti
                     user_name = user_display_name,
                     message,
                 );
-                discord::send_message_to_channel(&message, discord::CHANNEL_OSM_CHANGES).await;
+                notifications.push(Notification {
+                    action: ElementAction::Create,
+                    element_name: name.into(),
+                    user_name: user_display_name.clone(),
+                    osm_url: format!("https://www.openstreetmap.org/{element_type}/{osm_id}"),
+                });
             }
         }
     }
 
+    AreaStats::recompute(&OffsetDateTime::now_utc(), &tx)?;
+
+    // Covers the Overpass query as well, so it shows the whole time sync takes
+    let status = SyncStatus::insert(
+        mode.name(),
+        &OffsetDateTime::parse(&response.osm3s.timestamp_osm_base, &Rfc3339)?,
+        started_at.elapsed(),
+        fresh_elements_len,
+        &tx,
+    )?;
+    info!(
+        duration_secs = status.duration_secs,
+        elements = status.elements,
+        elements_per_second = status.elements_per_second,
+        "Saved sync status",
+    );
+
     tx.commit()?;
-    Ok(())
+
+    for notification in notifications {
+        discord::send_element_event_to_channel(
+            notification.action,
+            &notification.element_name,
+            &notification.user_name,
+            &notification.osm_url,
+            discord::CHANNEL_OSM_CHANGES,
+        )
+        .await;
+    }
+
+    Ok(summary)
+}
+
+// Alerts are sent once the transaction is committed, so there are none for rolled back changes
+struct Notification {
+    action: ElementAction,
+    element_name: String,
+    user_name: String,
+    osm_url: String,
+}
+
+fn should_notify_deleted(element_id: i64, now: &OffsetDateTime, conn: &Connection) -> Result<bool> {
+    if let Some(notification) = DeletedNotification::select_by_element_id(element_id, conn)? {
+        if *now - notification.notified_at < DELETED_NOTIFICATION_COOLDOWN {
+            return Ok(false);
+        }
+    }
+    DeletedNotification::upsert(element_id, now, conn)?;
+    Ok(true)
 }
 
 async fn on_new_event(event: &Event, conn: &Connection) -> Result<()> {
@@ -309,3 +533,309 @@ pub async fn insert_user_if_not_exists(user_id: i64, conn: &Connection) -> Resul
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::{process_elements, SyncSummary};
+    use crate::area::stats::AreaStats;
+    use crate::area::Area;
+    use crate::command::db;
+    use crate::element::Element;
+    use crate::event::Event;
+    use crate::osm::osm::OsmUser;
+    use crate::osm::overpass::{Osm3s, OverpassElement, Response, SyncMode};
+    use crate::sync::status::SyncStatus;
+    use crate::test::{mock_conn, mock_osm_tags};
+    use crate::user::User;
+    use crate::Result;
+    use rusqlite::Connection;
+    use serde_json::{json, Map, Value};
+    use std::fs;
+    use std::time::Instant;
+    use time::macros::datetime;
+    use time::Duration;
+
+    #[actix_web::test]
+    async fn process_elements_skips_metadata_changes() -> Result<()> {
+        let mut conn = mock_conn();
+        let element = Element::insert(&OverpassElement::mock(1), &conn)?;
+        let response = Response {
+            version: 0.6,
+            generator: "".into(),
+            osm3s: Osm3s {
+                timestamp_osm_base: "2024-05-02T12:00:00Z".into(),
+            },
+            elements: vec![OverpassElement {
+                version: Some(2),
+                changeset: Some(2),
+                ..element.overpass_data.clone()
+            }],
+        };
+        let summary =
+            process_elements(response, &SyncMode::Full, Instant::now(), &mut conn).await?;
+        assert_eq!(SyncSummary::default(), summary);
+        assert!(Event::select_all(None, &conn)?.is_empty());
+        let elements = Element::select_all(None, &conn)?;
+        assert_eq!(Some(2), elements[0].overpass_data.version);
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn run_from_file() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("btcmap-sync-{}.json", std::process::id()));
+        let response = Response {
+            version: 0.6,
+            generator: "".into(),
+            osm3s: Osm3s {
+                timestamp_osm_base: "2024-05-02T12:00:00Z".into(),
+            },
+            elements: vec![OverpassElement::mock(1), OverpassElement::mock(2)],
+        };
+        fs::write(&path, serde_json::to_string(&response)?)?;
+        let db_path = std::env::temp_dir().join(format!("btcmap-sync-{}.db", std::process::id()));
+        let mut conn = Connection::open(&db_path)?;
+        db::migrate(&mut conn)?;
+        let user = User::insert(1, &OsmUser::mock(), &conn)?;
+        user.set_tag("osm:missing", &Value::Bool(true), &conn)?;
+        drop(conn);
+        super::run_from_file(Connection::open(&db_path)?, path.to_str().unwrap()).await?;
+        let conn = Connection::open(&db_path)?;
+        assert_eq!(2, Element::select_all(None, &conn)?.len());
+        let events = Event::select_all(None, &conn)?;
+        assert_eq!(2, events.len());
+        assert!(events.iter().all(|it| it.r#type == "create"));
+        let status = SyncStatus::select_latest(&conn)?.unwrap();
+        assert!(status.duration_secs.unwrap() > 0.0);
+        assert_eq!(Some(2), status.elements);
+        assert!(status.elements_per_second.unwrap() > 0.0);
+        drop(conn);
+        fs::remove_file(&path)?;
+        fs::remove_file(&db_path)?;
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn process_elements_rolls_back_on_failure() -> Result<()> {
+        let mut conn = mock_conn();
+        let user = User::insert(1, &OsmUser::mock(), &conn)?;
+        user.set_tag("osm:missing", &Value::Bool(true), &conn)?;
+        // Fails after all the elements and events are written
+        let response = Response {
+            version: 0.6,
+            generator: "".into(),
+            osm3s: Osm3s {
+                timestamp_osm_base: "yesterday".into(),
+            },
+            elements: vec![OverpassElement::mock(1), OverpassElement::mock(2)],
+        };
+        assert!(
+            process_elements(response, &SyncMode::Full, Instant::now(), &mut conn)
+                .await
+                .is_err()
+        );
+        assert!(Element::select_all(None, &conn)?.is_empty());
+        assert!(Event::select_all(None, &conn)?.is_empty());
+        assert!(SyncStatus::select_latest(&conn)?.is_none());
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn process_elements_without_uid() -> Result<()> {
+        let mut conn = mock_conn();
+        let user = User::insert(1, &OsmUser::mock(), &conn)?;
+        user.set_tag("osm:missing", &Value::Bool(true), &conn)?;
+        let response = Response {
+            version: 0.6,
+            generator: "".into(),
+            osm3s: Osm3s {
+                timestamp_osm_base: "2024-05-02T12:00:00Z".into(),
+            },
+            elements: vec![
+                OverpassElement::mock(1),
+                OverpassElement {
+                    uid: None,
+                    ..OverpassElement::mock(2)
+                },
+            ],
+        };
+        let summary =
+            process_elements(response, &SyncMode::Full, Instant::now(), &mut conn).await?;
+        assert_eq!(2, summary.created);
+        assert_eq!(2, Element::select_all(None, &conn)?.len());
+        // The element without uid is saved, but there is no one to attribute its event to
+        assert_eq!(1, Event::select_all(None, &conn)?.len());
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn process_elements_recomputes_area_stats() -> Result<()> {
+        let mut conn = mock_conn();
+        let user = User::insert(1, &OsmUser::mock(), &conn)?;
+        user.set_tag("osm:missing", &Value::Bool(true), &conn)?;
+        let mut tags = Map::new();
+        tags.insert("url_alias".into(), "test".into());
+        tags.insert(
+            "geo_json".into(),
+            json!({
+                "type": "Polygon",
+                "coordinates": [[[-1, -1], [1, -1], [1, 1], [-1, 1], [-1, -1]]],
+            }),
+        );
+        let area = Area::insert(&tags, &conn)?;
+        let response = Response {
+            version: 0.6,
+            generator: "".into(),
+            osm3s: Osm3s {
+                timestamp_osm_base: "2024-05-02T12:00:00Z".into(),
+            },
+            elements: vec![
+                OverpassElement {
+                    tags: Some(mock_osm_tags(&["payment:lightning", "yes"])),
+                    ..OverpassElement::mock(1)
+                },
+                OverpassElement {
+                    lat: Some(5.0),
+                    lon: Some(5.0),
+                    ..OverpassElement::mock(2)
+                },
+            ],
+        };
+        process_elements(response, &SyncMode::Full, Instant::now(), &mut conn).await?;
+        let stats = AreaStats::select_by_area_id(area.id, &conn)?.unwrap();
+        assert_eq!(1, stats.elements);
+        assert_eq!(1, stats.lightning_elements);
+        assert_eq!(0, stats.onchain_elements);
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn process_elements_assigns_country() -> Result<()> {
+        let mut conn = mock_conn();
+        let user = User::insert(1, &OsmUser::mock(), &conn)?;
+        user.set_tag("osm:missing", &Value::Bool(true), &conn)?;
+        let mut tags = Map::new();
+        tags.insert("url_alias".into(), "de".into());
+        tags.insert(
+            "geo_json".into(),
+            json!({
+                "type": "Polygon",
+                "coordinates": [[[-1, -1], [1, -1], [1, 1], [-1, 1], [-1, -1]]],
+            }),
+        );
+        Area::insert(&tags, &conn)?;
+        let response = |lat| Response {
+            version: 0.6,
+            generator: "".into(),
+            osm3s: Osm3s {
+                timestamp_osm_base: "2024-05-02T12:00:00Z".into(),
+            },
+            elements: vec![OverpassElement {
+                lat: Some(lat),
+                changeset: Some(lat as i64),
+                ..OverpassElement::mock(1)
+            }],
+        };
+        process_elements(response(0.0), &SyncMode::Full, Instant::now(), &mut conn).await?;
+        let element = Element::select_all(None, &conn)?.remove(0);
+        assert_eq!(Some("DE"), element.tag("country").as_str());
+        // Moved out of the country
+        process_elements(response(5.0), &SyncMode::Full, Instant::now(), &mut conn).await?;
+        let element = Element::select_all(None, &conn)?.remove(0);
+        assert!(element.tag("country").is_null());
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn process_elements_records_changed_tags() -> Result<()> {
+        let mut conn = mock_conn();
+        let user = User::insert(1, &OsmUser::mock(), &conn)?;
+        user.set_tag("osm:missing", &Value::Bool(true), &conn)?;
+        let element = Element::insert(
+            &OverpassElement {
+                tags: Some(mock_osm_tags(&["name", "foo", "phone", "123"])),
+                ..OverpassElement::mock(1)
+            },
+            &conn,
+        )?;
+        let response = Response {
+            version: 0.6,
+            generator: "".into(),
+            osm3s: Osm3s {
+                timestamp_osm_base: "2024-05-02T12:00:00Z".into(),
+            },
+            elements: vec![OverpassElement {
+                tags: Some(mock_osm_tags(&["name", "bar", "opening_hours", "24/7"])),
+                changeset: Some(2),
+                ..element.overpass_data.clone()
+            }],
+        };
+        let summary =
+            process_elements(response, &SyncMode::Full, Instant::now(), &mut conn).await?;
+        assert_eq!(1, summary.updated);
+        let events = Event::select_all(None, &conn)?;
+        assert_eq!(1, events.len());
+        assert_eq!("update", events[0].r#type);
+        assert_eq!(&json!("tags"), events[0].tag("change_type"));
+        assert_eq!(
+            &json!(["name", "opening_hours", "phone"]),
+            events[0].tag("changed"),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn should_notify_deleted() -> Result<()> {
+        let conn = mock_conn();
+        let element = Element::insert(&OverpassElement::mock(1), &conn)?;
+        let now = datetime!(2024-05-01 00:00 UTC);
+        assert!(super::should_notify_deleted(element.id, &now, &conn)?);
+        assert!(!super::should_notify_deleted(
+            element.id,
+            &(now + Duration::hours(12)),
+            &conn
+        )?);
+        assert!(super::should_notify_deleted(
+            element.id,
+            &(now + Duration::hours(24)),
+            &conn
+        )?);
+        Ok(())
+    }
+
+    #[test]
+    fn select_mode() -> Result<()> {
+        let conn = mock_conn();
+        let now = datetime!(2024-05-02 12:00 UTC);
+        assert_eq!(SyncMode::Full, super::select_mode(&conn, now)?);
+        SyncStatus::insert(
+            "full",
+            &datetime!(2024-04-30 12:00 UTC),
+            std::time::Duration::ZERO,
+            0,
+            &conn,
+        )?;
+        SyncStatus::insert(
+            "incremental",
+            &datetime!(2024-05-02 11:50 UTC),
+            std::time::Duration::ZERO,
+            0,
+            &conn,
+        )?;
+        // The last sync is recent, but the last full sync is too old
+        assert_eq!(SyncMode::Full, super::select_mode(&conn, now)?);
+        SyncStatus::insert(
+            "full",
+            &datetime!(2024-05-02 11:55 UTC),
+            std::time::Duration::ZERO,
+            0,
+            &conn,
+        )?;
+        assert_eq!(
+            SyncMode::Incremental {
+                since: datetime!(2024-05-02 11:55 UTC)
+            },
+            super::select_mode(&conn, now)?,
+        );
+        Ok(())
+    }
+}
@@ -1,20 +1,64 @@
-use std::{collections::HashMap, env};
+use serde_json::{json, Map, Value};
+use std::env;
 use tracing::{error, info};
 
 pub static CHANNEL_OSM_CHANGES: &str = "DISCORD_WEBHOOK_URL";
 pub static CHANNEL_API: &str = "DISCORD_ADMIN_CHANNEL_WEBHOOK_URL";
 
+static USERNAME: &str = "DISCORD_USERNAME";
+static AVATAR_URL: &str = "DISCORD_AVATAR_URL";
+static DEFAULT_USERNAME: &str = "btcmap.org";
+
+pub enum ElementAction {
+    Create,
+    Update,
+    Delete,
+}
+
+impl ElementAction {
+    fn verb(&self) -> &'static str {
+        match self {
+            ElementAction::Create => "added",
+            ElementAction::Update => "updated",
+            ElementAction::Delete => "removed",
+        }
+    }
+
+    fn color(&self) -> u32 {
+        match self {
+            ElementAction::Create => 0x2ecc71,
+            ElementAction::Update => 0xf1c40f,
+            ElementAction::Delete => 0xe74c3c,
+        }
+    }
+}
+
 pub async fn send_message_to_channel(message: &str, channel: &str) {
     if let Ok(webhook_url) = env::var(channel) {
-        send_message(message, &webhook_url).await;
+        let mut args = payload(env::var(USERNAME).ok(), env::var(AVATAR_URL).ok());
+        args.insert("content".into(), message.into());
+        send(&args, &webhook_url).await;
     }
 }
 
-async fn send_message(message: &str, webhook_url: &str) {
-    let mut args = HashMap::new();
-    args.insert("username", "btcmap.org");
-    args.insert("content", message);
+pub async fn send_element_event_to_channel(
+    action: ElementAction,
+    element_name: &str,
+    user_name: &str,
+    osm_url: &str,
+    channel: &str,
+) {
+    if let Ok(webhook_url) = env::var(channel) {
+        let mut args = payload(env::var(USERNAME).ok(), env::var(AVATAR_URL).ok());
+        args.insert(
+            "embeds".into(),
+            json!([element_embed(action, element_name, user_name, osm_url)]),
+        );
+        send(&args, &webhook_url).await;
+    }
+}
 
+async fn send(args: &Map<String, Value>, webhook_url: &str) {
     info!("Sending discord message");
 
     let response = reqwest::Client::new()
@@ -32,3 +76,83 @@ async fn send_message(message: &str, webhook_url: &str) {
         }
     };
 }
+
+fn payload(username: Option<String>, avatar_url: Option<String>) -> Map<String, Value> {
+    let mut args = Map::new();
+    args.insert(
+        "username".into(),
+        username
+            .filter(|it| !it.is_empty())
+            .unwrap_or(DEFAULT_USERNAME.into())
+            .into(),
+    );
+    if let Some(avatar_url) = avatar_url.filter(|it| !it.is_empty()) {
+        args.insert("avatar_url".into(), avatar_url.into());
+    }
+    args
+}
+
+// Unnamed elements are titled with their OSM link
+fn element_embed(
+    action: ElementAction,
+    element_name: &str,
+    user_name: &str,
+    osm_url: &str,
+) -> Value {
+    let title = if element_name.is_empty() {
+        osm_url
+    } else {
+        element_name
+    };
+    json!({
+        "title": title,
+        "url": osm_url,
+        "color": action.color(),
+        "description": format!("User {user_name} {} {osm_url}", action.verb()),
+        "fields": [
+            { "name": "Action", "value": action.verb(), "inline": true },
+            { "name": "Editor", "value": user_name, "inline": true },
+        ],
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::ElementAction;
+    use serde_json::json;
+
+    #[test]
+    fn payload() {
+        let args = super::payload(None, None);
+        assert_eq!("btcmap.org", args["username"]);
+        assert!(!args.contains_key("avatar_url"));
+        let args = super::payload(
+            Some("staging.btcmap.org".into()),
+            Some("https://btcmap.org/icon.png".into()),
+        );
+        assert_eq!("staging.btcmap.org", args["username"]);
+        assert_eq!("https://btcmap.org/icon.png", args["avatar_url"]);
+    }
+
+    #[test]
+    fn element_embed() {
+        assert_eq!(
+            json!({
+                "title": "Satoshi's Coffee",
+                "url": "https://www.openstreetmap.org/node/1",
+                "color": 0x2ecc71,
+                "description": "User satoshi added https://www.openstreetmap.org/node/1",
+                "fields": [
+                    { "name": "Action", "value": "added", "inline": true },
+                    { "name": "Editor", "value": "satoshi", "inline": true },
+                ],
+            }),
+            super::element_embed(
+                ElementAction::Create,
+                "Satoshi's Coffee",
+                "satoshi",
+                "https://www.openstreetmap.org/node/1",
+            ),
+        );
+    }
+}
@@ -1,10 +1,11 @@
 use super::Element;
 use crate::{
-    auth::AuthService, discord, element::ElementRepo, osm::overpass::OverpassElement, Error,
+    auth::AuthService, discord, element::ElementRepo, osm::overpass::OverpassElement,
+    user::UserRepo, Error,
 };
 use actix_web::{
     patch, post,
-    web::{Data, Form, Json, Path},
+    web::{Data, Form, Json, Path, Query},
     HttpRequest,
 };
 use serde::{Deserialize, Serialize};
@@ -92,7 +93,9 @@ async fn patch(
     .ok_or(Error::HttpNotFound(format!(
         "There is no element with id = {id}"
     )))?;
-    let element = repo.patch_tags(element.id, &args.tags).await?;
+    let element = repo
+        .patch_tags(element.id, &args.tags, &token.owner, None)
+        .await?;
     let log_message = format!(
         "{} updated element https://api.btcmap.org/v2/elements/{}",
         token.owner,
@@ -148,11 +151,120 @@ async fn post_tags(
     Ok(element.into())
 }
 
+#[derive(Serialize, Deserialize)]
+struct UndeleteArgs {
+    // OSM user id the restore event is attributed to, the user has to be known to BTC Map
+    user_id: i64,
+}
+
+#[post("{id}/undelete")]
+async fn undelete(
+    req: HttpRequest,
+    id: Path<String>,
+    args: Json<UndeleteArgs>,
+    auth: Data<AuthService>,
+    repo: Data<ElementRepo>,
+    user_repo: Data<UserRepo>,
+) -> Result<Json<ElementView>, Error> {
+    let token = auth.check(&req).await?;
+    let (r#type, osm_id) = id
+        .split_once(":")
+        .ok_or(Error::HttpBadRequest("Invalid identifier".into()))?;
+    let osm_id = osm_id
+        .parse::<i64>()
+        .map_err(|_| Error::HttpBadRequest("Invalid identifier".into()))?;
+    let element = repo
+        .select_by_osm_type_and_id(r#type, osm_id)
+        .await?
+        .ok_or(Error::HttpNotFound(format!(
+            "There is no element with id = {id}"
+        )))?;
+    if element.deleted_at.is_none() {
+        Err(Error::HttpConflict(format!(
+            "Element with id = {id} isn't deleted"
+        )))?
+    }
+    let user = user_repo
+        .select_by_id(args.user_id)
+        .await?
+        .ok_or(Error::HttpNotFound(format!(
+            "There is no user with id = {}",
+            args.user_id,
+        )))?;
+    let element = repo.restore(element.id, user.id, &token.owner).await?;
+    let log_message = format!(
+        "{} restored element https://api.btcmap.org/v2/elements/{}",
+        token.owner, id,
+    );
+    warn!(log_message);
+    discord::send_message_to_channel(&log_message, discord::CHANNEL_API).await;
+    Ok(element.into())
+}
+
+#[derive(Serialize, Deserialize)]
+struct VerifyArgs {
+    // OSM user id of the surveyor, the user has to be known to BTC Map
+    user_id: i64,
+}
+
+#[post("{id}/verify")]
+async fn verify(
+    req: HttpRequest,
+    id: Path<String>,
+    args: Json<VerifyArgs>,
+    auth: Data<AuthService>,
+    repo: Data<ElementRepo>,
+    user_repo: Data<UserRepo>,
+) -> Result<Json<ElementView>, Error> {
+    let token = auth.check(&req).await?;
+    let (r#type, osm_id) = id
+        .split_once(":")
+        .ok_or(Error::HttpBadRequest("Invalid identifier".into()))?;
+    let osm_id = osm_id
+        .parse::<i64>()
+        .map_err(|_| Error::HttpBadRequest("Invalid identifier".into()))?;
+    let element = repo
+        .select_by_osm_type_and_id(r#type, osm_id)
+        .await?
+        .ok_or(Error::HttpNotFound(format!(
+            "There is no element with id = {id}"
+        )))?;
+    if element.deleted_at.is_some() {
+        Err(Error::HttpConflict(format!(
+            "Element with id = {id} is deleted"
+        )))?
+    }
+    let user = user_repo
+        .select_by_id(args.user_id)
+        .await?
+        .ok_or(Error::HttpNotFound(format!(
+            "There is no user with id = {}",
+            args.user_id,
+        )))?;
+    let element = repo.verify(element.id, user.id, &token.owner).await?;
+    let log_message = format!(
+        "{} verified element https://api.btcmap.org/v2/elements/{}",
+        token.owner, id,
+    );
+    warn!(log_message);
+    discord::send_message_to_channel(&log_message, discord::CHANNEL_API).await;
+    Ok(element.into())
+}
+
+#[derive(Deserialize)]
+struct PatchTagsQuery {
+    // Should match the updated_at the client saw, so concurrent edits don't overwrite each other
+    #[serde(default)]
+    #[serde(with = "time::serde::rfc3339::option")]
+    expected_updated_at: Option<OffsetDateTime>,
+}
+
 #[patch("{id}/tags")]
 async fn patch_tags(
     req: HttpRequest,
     id: Path<String>,
     args: Json<Map<String, Value>>,
+    query: Query<PatchTagsQuery>,
     auth: Data<AuthService>,
     repo: Data<ElementRepo>,
 ) -> Result<Json<ElementView>, Error> {
@@ -172,7 +284,9 @@ async fn patch_tags(
             "There is no element with id = {}",
             id,
         )))?;
-    let element = repo.patch_tags(element.id, &args).await?;
+    let element = repo
+        .patch_tags(element.id, &args, &token.owner, query.expected_updated_at)
+        .await?;
     let log_message = format!(
         "{} patched tags for element https://api.btcmap.org/v2/elements/{} {}",
         token.owner,
@@ -187,15 +301,21 @@ async fn patch_tags(
 #[cfg(test)]
 mod test {
     use crate::element::admin::{PatchArgs, PostTagsArgs};
-    use crate::element::ElementRepo;
+    use crate::element::{Element, ElementRepo};
+    use crate::event::Event;
+    use crate::osm::osm::OsmUser;
     use crate::osm::overpass::OverpassElement;
+    use crate::server::{json_config, MAX_JSON_PAYLOAD};
     use crate::test::mock_state;
+    use crate::user::User;
     use crate::Result;
     use actix_web::http::StatusCode;
     use actix_web::test::TestRequest;
     use actix_web::web::Data;
     use actix_web::{test, App};
     use serde_json::{json, Map, Value};
+    use time::macros::datetime;
+    use time::OffsetDateTime;
 
     #[test]
     async fn patch_unauthorized() -> Result<()> {
@@ -255,6 +375,160 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    async fn undelete() -> Result<()> {
+        let state = mock_state().await;
+        let token = state.auth.mock_token("test").await.secret;
+        let element = state
+            .pool
+            .get()
+            .await?
+            .interact(|conn| -> Result<Element> {
+                let user = User::insert(1, &OsmUser::mock(), conn)?;
+                User::insert(2, &OsmUser::mock(), conn)?;
+                let element = Element::insert(&OverpassElement::mock(1), conn)?;
+                Event::insert(user.id, element.id, "delete", conn)?;
+                element.set_deleted_at(Some(OffsetDateTime::now_utc()), conn)
+            })
+            .await??;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.auth))
+                .app_data(Data::new(ElementRepo::new(&state.pool)))
+                .app_data(Data::new(state.user_repo))
+                .service(super::undelete),
+        )
+        .await;
+        let req = TestRequest::post()
+            .uri(&format!("/{}/undelete", element.overpass_data.btcmap_id()))
+            .append_header(("Authorization", format!("Bearer {token}")))
+            .set_json(json!({ "user_id": 3 }))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        let req = TestRequest::post()
+            .uri(&format!("/{}/undelete", element.overpass_data.btcmap_id()))
+            .append_header(("Authorization", format!("Bearer {token}")))
+            .set_json(json!({ "user_id": 2 }))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let restored = state.element_repo.select_by_id(element.id).await?.unwrap();
+        assert!(restored.deleted_at.is_none());
+        let event = state
+            .pool
+            .get()
+            .await?
+            .interact(move |conn| Event::select_latest_by_element_id(element.id, conn))
+            .await??
+            .unwrap();
+        assert_eq!("restore", event.r#type);
+        assert_eq!(2, event.user_id);
+        assert_eq!(&json!("test"), event.tag("restored_by"));
+        let req = TestRequest::post()
+            .uri(&format!("/{}/undelete", element.overpass_data.btcmap_id()))
+            .append_header(("Authorization", format!("Bearer {token}")))
+            .set_json(json!({ "user_id": 2 }))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+        let req = TestRequest::post()
+            .uri("/node:2/undelete")
+            .append_header(("Authorization", format!("Bearer {token}")))
+            .set_json(json!({ "user_id": 2 }))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        Ok(())
+    }
+
+    #[test]
+    async fn undelete_without_events() -> Result<()> {
+        let state = mock_state().await;
+        let token = state.auth.mock_token("test").await.secret;
+        state.user_repo.insert(1, &OsmUser::mock()).await?;
+        let element = state
+            .pool
+            .get()
+            .await?
+            .interact(|conn| -> Result<Element> {
+                Element::insert(&OverpassElement::mock(1), conn)?
+                    .set_deleted_at(Some(OffsetDateTime::now_utc()), conn)
+            })
+            .await??;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.auth))
+                .app_data(Data::new(ElementRepo::new(&state.pool)))
+                .app_data(Data::new(state.user_repo))
+                .service(super::undelete),
+        )
+        .await;
+        let req = TestRequest::post()
+            .uri(&format!("/{}/undelete", element.overpass_data.btcmap_id()))
+            .append_header(("Authorization", format!("Bearer {token}")))
+            .set_json(json!({ "user_id": 1 }))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let event = state
+            .pool
+            .get()
+            .await?
+            .interact(move |conn| Event::select_latest_by_element_id(element.id, conn))
+            .await??
+            .unwrap();
+        assert_eq!("restore", event.r#type);
+        assert_eq!(1, event.user_id);
+        Ok(())
+    }
+
+    #[test]
+    async fn verify() -> Result<()> {
+        let state = mock_state().await;
+        let token = state.auth.mock_token("test").await.secret;
+        state.user_repo.insert(1, &OsmUser::mock()).await?;
+        let element = state.element_repo.insert(&OverpassElement::mock(1)).await?;
+        assert!(!element.up_to_date());
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.auth))
+                .app_data(Data::new(ElementRepo::new(&state.pool)))
+                .app_data(Data::new(state.user_repo))
+                .service(super::verify),
+        )
+        .await;
+        let req = TestRequest::post()
+            .uri(&format!("/{}/verify", element.overpass_data.btcmap_id()))
+            .append_header(("Authorization", format!("Bearer {token}")))
+            .set_json(json!({ "user_id": 1 }))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let verified = state.element_repo.select_by_id(element.id).await?.unwrap();
+        let today = OffsetDateTime::now_utc().date().to_string();
+        assert_eq!(&json!(today), verified.tag("check_date:currency:XBT"));
+        assert!(verified.up_to_date());
+        let event = state
+            .pool
+            .get()
+            .await?
+            .interact(move |conn| Event::select_latest_by_element_id(element.id, conn))
+            .await??
+            .unwrap();
+        assert_eq!("verify", event.r#type);
+        assert_eq!(1, event.user_id);
+        assert_eq!(&json!("test"), event.tag("verified_by"));
+        let req = TestRequest::post()
+            .uri(&format!("/{}/verify", element.overpass_data.btcmap_id()))
+            .append_header(("Authorization", format!("Bearer {token}")))
+            .set_json(json!({ "user_id": 2 }))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        Ok(())
+    }
+
     #[test]
     async fn post_tags() -> Result<()> {
         let state = mock_state().await;
@@ -301,4 +575,75 @@ mod test {
         assert_eq!(res.status(), StatusCode::OK);
         Ok(())
     }
+
+    #[test]
+    async fn patch_tags_stale() -> Result<()> {
+        let state = mock_state().await;
+        let token = state.auth.mock_token("test").await.secret;
+        let element = state.element_repo.insert(&OverpassElement::mock(1)).await?;
+        let element = state
+            .element_repo
+            .set_updated_at(element.id, &datetime!(2020-01-01 00:00 UTC))
+            .await?;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.auth))
+                .app_data(Data::new(ElementRepo::new(&state.pool)))
+                .service(super::patch_tags),
+        )
+        .await;
+        // Another admin edits the element after it was loaded
+        state
+            .element_repo
+            .set_updated_at(element.id, &datetime!(2021-01-01 00:00 UTC))
+            .await?;
+        let req = TestRequest::patch()
+            .uri(&format!(
+                "/{}/tags?expected_updated_at=2020-01-01T00:00:00Z",
+                element.overpass_data.btcmap_id(),
+            ))
+            .append_header(("Authorization", format!("Bearer {token}")))
+            .set_json(json!({ "foo": "bar" }))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+        let element = state.element_repo.select_by_id(element.id).await?.unwrap();
+        assert!(element.tags.get("foo").is_none());
+        let req = TestRequest::patch()
+            .uri(&format!(
+                "/{}/tags?expected_updated_at=2021-01-01T00:00:00Z",
+                element.overpass_data.btcmap_id(),
+            ))
+            .append_header(("Authorization", format!("Bearer {token}")))
+            .set_json(json!({ "foo": "bar" }))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        Ok(())
+    }
+
+    #[test]
+    async fn patch_tags_too_large() -> Result<()> {
+        let state = mock_state().await;
+        let token = state.auth.mock_token("test").await.secret;
+        let element = state.element_repo.insert(&OverpassElement::mock(1)).await?;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.auth))
+                .app_data(Data::new(state.element_repo))
+                .app_data(json_config(MAX_JSON_PAYLOAD))
+                .service(super::patch_tags),
+        )
+        .await;
+        let req = TestRequest::patch()
+            .uri(&format!("/{}/tags", element.overpass_data.btcmap_id()))
+            .append_header(("Authorization", format!("Bearer {token}")))
+            .set_json(json!({ "foo": "a".repeat(MAX_JSON_PAYLOAD) }))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        let res: Value = test::read_body_json(res).await;
+        assert_eq!(413, res["code"]);
+        Ok(())
+    }
 }
@@ -1,6 +1,8 @@
 use super::Element;
 use crate::{area::Area, Result};
+use geo::Area as _;
 use geo::Contains;
+use geo::Coord;
 use geo::LineString;
 use geo::MultiPolygon;
 use geo::Polygon;
@@ -52,6 +54,31 @@ pub fn find_and_save(element: &Element, areas: &Vec<Area>, conn: &Connection) ->
             areas = ?element_area_names,
     );
 
+    // Elements created or moved by sync should be found by the country filter right away
+    let country = element_areas.iter().find_map(|it| country_code(it));
+    match country {
+        Some(country) => {
+            if element.tag("country").as_str() != Some(&country) {
+                info!(country, "Updating country tag");
+                element.set_tag("country", &country.into(), conn)?;
+            }
+        }
+        // Countries which weren't imported yet can't be checked, so their codes are kept
+        None => {
+            let old_country = element.tag("country").as_str().unwrap_or_default();
+            if areas
+                .iter()
+                .any(|it| country_code(it).as_deref() == Some(old_country))
+            {
+                info!(
+                    old_country,
+                    "Element left its country, removing country tag"
+                );
+                element.remove_tag("country", conn)?;
+            }
+        }
+    }
+
     let element_areas: Vec<Value> = element_areas.iter().map(|it| {
         json!({"id": it.id, "url_alias": it.tags.get("url_alias").unwrap_or(&Value::Null).as_str().unwrap_or_default()})
     }).collect();
@@ -69,77 +96,112 @@ pub fn find_and_save(element: &Element, areas: &Vec<Area>, conn: &Connection) ->
     Ok(())
 }
 
-fn find_areas<'a>(element: &Element, areas: &'a Vec<Area>) -> Result<Vec<&'a Area>> {
-    let mut element_areas = vec![];
+// Country areas use ISO 3166-1 alpha-2 codes as url aliases
+pub fn country_code(area: &Area) -> Option<String> {
+    let url_alias = area.tags.get("url_alias")?.as_str()?;
+    if url_alias.len() == 2 && url_alias.chars().all(|it| it.is_ascii_alphabetic()) {
+        Some(url_alias.to_uppercase())
+    } else {
+        None
+    }
+}
 
-    for area in areas {
-        if area.tags.get("url_alias") == Some(&Value::String("earth".into())) {
-            continue;
-        }
+pub fn find_areas<'a>(element: &Element, areas: &'a Vec<Area>) -> Result<Vec<&'a Area>> {
+    find_areas_by_coord(&element.overpass_data.coord(), areas)
+}
 
-        let geo_json = area.tags.get("geo_json").unwrap_or(&Value::Null);
+pub fn find_areas_by_coord<'a>(coord: &Coord, areas: &'a Vec<Area>) -> Result<Vec<&'a Area>> {
+    let mut coord_areas = vec![];
 
-        if geo_json.is_null() {
+    for area in areas {
+        if area.tags.get("url_alias") == Some(&Value::String("earth".into())) {
             continue;
         }
 
-        if geo_json.is_object() {
-            let geo_json: Result<GeoJson, _> = serde_json::to_string(geo_json)?.parse();
+        for geometry in &geometries(area)? {
+            match &geometry.value {
+                geojson::Value::MultiPolygon(_) => {
+                    let multi_poly: MultiPolygon = (&geometry.value).try_into().unwrap();
 
-            let geo_json = match geo_json {
-                Ok(geo_json) => geo_json,
-                Err(e) => {
-                    error!(?e, "Failed to parse GeoJSON");
-                    continue;
+                    if multi_poly.contains(coord) {
+                        coord_areas.push(area);
+                    }
                 }
-            };
+                geojson::Value::Polygon(_) => {
+                    let poly: Polygon = (&geometry.value).try_into().unwrap();
 
-            let mut geometries: Vec<&Geometry> = vec![];
-
-            match &geo_json {
-                GeoJson::FeatureCollection(v) => {
-                    for feature in &v.features {
-                        if let Some(v) = &feature.geometry {
-                            geometries.push(v);
-                        }
+                    if poly.contains(coord) {
+                        coord_areas.push(area);
                     }
                 }
-                GeoJson::Feature(v) => {
-                    if let Some(v) = &v.geometry {
-                        geometries.push(v);
+                geojson::Value::LineString(_) => {
+                    let line_string: LineString = (&geometry.value).try_into().unwrap();
+
+                    if line_string.contains(coord) {
+                        coord_areas.push(area);
                     }
                 }
-                GeoJson::Geometry(v) => geometries.push(v),
-            };
+                _ => continue,
+            }
+        }
+    }
 
-            for geometry in &geometries {
-                match &geometry.value {
-                    geojson::Value::MultiPolygon(_) => {
-                        let multi_poly: MultiPolygon = (&geometry.value).try_into().unwrap();
+    Ok(coord_areas)
+}
 
-                        if multi_poly.contains(&element.overpass_data.coord()) {
-                            element_areas.push(area);
-                        }
-                    }
-                    geojson::Value::Polygon(_) => {
-                        let poly: Polygon = (&geometry.value).try_into().unwrap();
+// Smaller areas come first, so the most specific area is on top
+pub fn find_areas_containing<'a>(coord: &Coord, areas: &'a Vec<Area>) -> Result<Vec<&'a Area>> {
+    let mut coord_areas = find_areas_by_coord(coord, areas)?;
+    coord_areas.dedup_by_key(|it| it.id);
+    let mut sized_areas = vec![];
+
+    for area in coord_areas {
+        let size: f64 = geometries(area)?
+            .iter()
+            .filter_map(|it| geo::Geometry::<f64>::try_from(&it.value).ok())
+            .map(|it| it.unsigned_area())
+            .sum();
+        sized_areas.push((area, size));
+    }
 
-                        if poly.contains(&element.overpass_data.coord()) {
-                            element_areas.push(area);
-                        }
-                    }
-                    geojson::Value::LineString(_) => {
-                        let line_string: LineString = (&geometry.value).try_into().unwrap();
+    sized_areas.sort_by(|a, b| a.1.total_cmp(&b.1));
+    Ok(sized_areas.into_iter().map(|it| it.0).collect())
+}
 
-                        if line_string.contains(&element.overpass_data.coord()) {
-                            element_areas.push(area);
-                        }
-                    }
-                    _ => continue,
+fn geometries(area: &Area) -> Result<Vec<Geometry>> {
+    let geo_json = area.tags.get("geo_json").unwrap_or(&Value::Null);
+
+    if !geo_json.is_object() {
+        return Ok(vec![]);
+    }
+
+    let geo_json: Result<GeoJson, _> = serde_json::to_string(geo_json)?.parse();
+
+    let geo_json = match geo_json {
+        Ok(geo_json) => geo_json,
+        Err(e) => {
+            error!(?e, "Failed to parse GeoJSON");
+            return Ok(vec![]);
+        }
+    };
+
+    let mut geometries: Vec<Geometry> = vec![];
+
+    match geo_json {
+        GeoJson::FeatureCollection(v) => {
+            for feature in v.features {
+                if let Some(v) = feature.geometry {
+                    geometries.push(v);
                 }
             }
         }
-    }
+        GeoJson::Feature(v) => {
+            if let Some(v) = v.geometry {
+                geometries.push(v);
+            }
+        }
+        GeoJson::Geometry(v) => geometries.push(v),
+    };
 
-    Ok(element_areas)
+    Ok(geometries)
 }
@@ -0,0 +1,42 @@
+const BASE32: &[u8; 32] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+
+// Each character halves the cell 5 times, alternating between longitude and latitude, so
+// elements sharing a prefix are close to each other
+pub fn encode(lat: f64, lon: f64, precision: usize) -> String {
+    let mut lat_range = (-90.0, 90.0);
+    let mut lon_range = (-180.0, 180.0);
+    let mut res = String::with_capacity(precision);
+    let mut even_bit = true;
+    while res.len() < precision {
+        let mut index = 0;
+        for _ in 0..5 {
+            let (value, range) = if even_bit {
+                (lon, &mut lon_range)
+            } else {
+                (lat, &mut lat_range)
+            };
+            let mid = (range.0 + range.1) / 2.0;
+            index <<= 1;
+            if value >= mid {
+                index |= 1;
+                range.0 = mid;
+            } else {
+                range.1 = mid;
+            }
+            even_bit = !even_bit;
+        }
+        res.push(BASE32[index] as char);
+    }
+    res
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn encode() {
+        assert_eq!("u4pruydqqvj", super::encode(57.64911, 10.40744, 11));
+        assert_eq!("ezs42", super::encode(42.6, -5.6, 5));
+        assert_eq!("s0000", super::encode(0.0, 0.0, 5));
+        assert_eq!("", super::encode(0.0, 0.0, 0));
+    }
+}
@@ -1,7 +1,12 @@
 pub mod model;
 pub use model::Element;
 pub use model::ElementRepo;
+pub use model::Filter;
+pub use model::Sort;
 pub mod admin;
 pub mod find_areas;
+pub mod geohash;
+pub mod opening_hours;
+pub mod tag_change;
 pub mod v2;
 pub mod v3;
@@ -1,14 +1,140 @@
+use crate::element::tag_change::ElementTagChange;
+use crate::event::Event;
+use crate::osm::overpass::outdated_days;
+use crate::osm::overpass::VERIFICATION_TAGS;
 use crate::Result;
 use crate::{osm::overpass::OverpassElement, Error};
 use deadpool_sqlite::Pool;
-use rusqlite::{named_params, Connection, OptionalExtension, Row};
+use geo::Coord;
+use rusqlite::{named_params, Connection, OptionalExtension, Row, TransactionBehavior};
 use serde_json::{Map, Value};
 use std::{collections::HashMap, sync::Arc};
-use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use time::macros::format_description;
+use time::{format_description::well_known::Rfc3339, Date, Duration, OffsetDateTime};
 use tracing::debug;
 
 pub struct ElementRepo {
     pool: Arc<Pool>,
+    read_pool: Arc<Pool>,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub enum Sort {
+    #[default]
+    UpdatedAt,
+    UpdatedAtDesc,
+    Name,
+    NameDesc,
+}
+
+impl Sort {
+    fn order_by(&self) -> String {
+        // Row id keeps the order stable when sort keys are equal or missing
+        let name = format!("json_extract({COL_OVERPASS_DATA}, '$.tags.name') COLLATE NOCASE");
+        match self {
+            Sort::UpdatedAt => format!("{COL_UPDATED_AT}, {COL_ROWID}"),
+            Sort::UpdatedAtDesc => format!("{COL_UPDATED_AT} DESC, {COL_ROWID} DESC"),
+            Sort::Name => format!("{name}, {COL_ROWID}"),
+            Sort::NameDesc => format!("{name} DESC, {COL_ROWID} DESC"),
+        }
+    }
+}
+
+// Filters for element lists, each one that is set narrows the result down
+#[derive(Clone, Default, Debug)]
+pub struct Filter {
+    pub country: Option<String>,
+    pub updated_since: Option<OffsetDateTime>,
+    // min_lon, min_lat, max_lon, max_lat
+    pub bbox: Option<(f64, f64, f64, f64)>,
+    // Matched against the areas tag, so it relies on area membership being precomputed
+    pub area: Option<String>,
+    pub outdated: bool,
+    pub never_verified: bool,
+}
+
+impl Filter {
+    // Every param is always bound, filters which aren't set are turned off by NULL or false
+    fn sql() -> String {
+        format!(
+            r#"
+                (:country IS NULL OR json_extract({COL_TAGS}, '$.country') = :country)
+                AND {COL_UPDATED_AT} > :updated_since
+                AND (:min_lon IS NULL OR {COL_ROWID} IN (
+                    SELECT id
+                    FROM element_rtree
+                    WHERE max_lon >= :min_lon AND min_lon <= :max_lon
+                    AND max_lat >= :min_lat AND min_lat <= :max_lat
+                ))
+                AND (:area IS NULL OR EXISTS (
+                    SELECT 1
+                    FROM json_each({COL_TAGS}, '$.areas')
+                    WHERE json_extract(value, '$.url_alias') = :area
+                ))
+                AND (NOT :outdated OR (
+                    {COL_DELETED_AT} IS NULL AND {} <= :min_verification_date
+                ))
+                AND (NOT :never_verified OR ({COL_DELETED_AT} IS NULL AND {}))
+            "#,
+            verification_date_sql(),
+            never_verified_sql(),
+        )
+    }
+
+    fn bbox_params(&self) -> (Option<f64>, Option<f64>, Option<f64>, Option<f64>) {
+        match self.bbox {
+            Some((min_lon, min_lat, max_lon, max_lat)) => {
+                (Some(min_lon), Some(min_lat), Some(max_lon), Some(max_lat))
+            }
+            None => (None, None, None, None),
+        }
+    }
+
+    fn updated_since(&self) -> Result<String> {
+        Ok(match self.updated_since {
+            Some(updated_since) => updated_since.format(&Rfc3339)?,
+            None => "".into(),
+        })
+    }
+
+    // Verification dates are days, so an element is up to date if it was verified after this day
+    fn min_verification_date(&self) -> Result<String> {
+        let min_date = OffsetDateTime::now_utc().saturating_sub(Duration::days(outdated_days()));
+        Ok(min_date
+            .date()
+            .format(format_description!("[year]-[month]-[day]"))?)
+    }
+}
+
+// Same rules as Element::verification_date, an empty string means the element was never verified.
+// date(it) = it only holds for valid YYYY-MM-DD dates
+fn verification_date_sql() -> String {
+    let valid = |it: String| format!("CASE WHEN date({it}) = {it} THEN {it} END");
+    let osm = VERIFICATION_TAGS
+        .iter()
+        .map(|tag| {
+            valid(format!(
+                "json_extract({COL_OVERPASS_DATA}, '$.tags.\"{tag}\"')"
+            ))
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    let api = valid(format!(
+        "json_extract({COL_TAGS}, '$.\"{VERIFICATION_TAG}\"')"
+    ));
+    format!("max(coalesce({osm}, ''), coalesce({api}, ''))")
+}
+
+// Unlike outdated elements, these have no verification date at all, not even a broken one
+fn never_verified_sql() -> String {
+    let osm = VERIFICATION_TAGS
+        .iter()
+        .map(|tag| {
+            format!("coalesce(json_extract({COL_OVERPASS_DATA}, '$.tags.\"{tag}\"'), '') = ''")
+        })
+        .collect::<Vec<_>>()
+        .join(" AND ");
+    format!("{osm} AND json_extract({COL_TAGS}, '$.\"{VERIFICATION_TAG}\"') IS NULL")
 }
 
 #[derive(PartialEq, Debug)]
@@ -23,16 +149,24 @@ pub struct Element {
 
 impl ElementRepo {
     pub fn new(pool: &Arc<Pool>) -> Self {
-        Self { pool: pool.clone() }
+        Self {
+            pool: pool.clone(),
+            read_pool: pool.clone(),
+        }
     }
 
-    #[cfg(test)]
-    pub fn mock() -> Self {
+    pub fn with_read_pool(self, read_pool: &Arc<Pool>) -> Self {
         Self {
-            pool: Arc::new(crate::test::mock_pool()),
+            read_pool: read_pool.clone(),
+            ..self
         }
     }
 
+    #[cfg(test)]
+    pub fn mock() -> Self {
+        Self::new(&Arc::new(crate::test::mock_pool()))
+    }
+
     #[cfg(test)]
     pub async fn insert(&self, overpass_data: &OverpassElement) -> Result<Element> {
         let overpass_data = overpass_data.clone();
@@ -44,28 +178,126 @@ impl ElementRepo {
     }
 
     pub async fn select_all(&self, limit: Option<i64>) -> Result<Vec<Element>> {
-        self.pool
+        self.read_pool
             .get()
             .await?
             .interact(move |conn| Element::select_all(limit, conn))
             .await?
     }
 
+    pub async fn select_page(
+        &self,
+        updated_since: Option<OffsetDateTime>,
+        after_id: i64,
+        limit: i64,
+    ) -> Result<Vec<Element>> {
+        self.read_pool
+            .get()
+            .await?
+            .interact(move |conn| {
+                Element::select_page(updated_since.as_ref(), after_id, limit, conn)
+            })
+            .await?
+    }
+
     pub async fn select_updated_since(
         &self,
         updated_since: &OffsetDateTime,
         limit: Option<i64>,
     ) -> Result<Vec<Element>> {
         let updated_since = updated_since.clone();
-        self.pool
+        self.read_pool
             .get()
             .await?
             .interact(move |conn| Element::select_updated_since(&updated_since, limit, conn))
             .await?
     }
 
+    pub async fn select_count(&self, filter: &Filter) -> Result<i64> {
+        let filter = filter.clone();
+        self.read_pool
+            .get()
+            .await?
+            .interact(move |conn| Element::select_count(&filter, conn))
+            .await?
+    }
+
+    pub async fn select_max_updated_at(&self) -> Result<Option<OffsetDateTime>> {
+        self.read_pool
+            .get()
+            .await?
+            .interact(|conn| Element::select_max_updated_at(conn))
+            .await?
+    }
+
+    pub async fn select_sorted(
+        &self,
+        filter: &Filter,
+        sort: Sort,
+        limit: Option<i64>,
+    ) -> Result<Vec<Element>> {
+        let filter = filter.clone();
+        self.read_pool
+            .get()
+            .await?
+            .interact(move |conn| Element::select_sorted(&filter, sort, limit, conn))
+            .await?
+    }
+
+    pub async fn select_by_bbox(
+        &self,
+        min_lon: f64,
+        min_lat: f64,
+        max_lon: f64,
+        max_lat: f64,
+    ) -> Result<Vec<Element>> {
+        self.read_pool
+            .get()
+            .await?
+            .interact(move |conn| Element::select_by_bbox(min_lon, min_lat, max_lon, max_lat, conn))
+            .await?
+    }
+
+    pub async fn select_coords(&self) -> Result<Vec<(f64, f64)>> {
+        self.read_pool
+            .get()
+            .await?
+            .interact(|conn| Element::select_coords(conn))
+            .await?
+    }
+
+    pub async fn select_by_search_query(
+        &self,
+        search_query: &str,
+        limit: Option<i64>,
+    ) -> Result<Vec<Element>> {
+        let search_query = search_query.to_string();
+        self.read_pool
+            .get()
+            .await?
+            .interact(move |conn| Element::select_by_search_query(&search_query, limit, conn))
+            .await?
+    }
+
+    pub async fn select_by_area_id(
+        &self,
+        area_id: i64,
+        payment: Option<&str>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<Element>> {
+        let payment = payment.map(|it| it.to_string());
+        self.read_pool
+            .get()
+            .await?
+            .interact(move |conn| {
+                Element::select_by_area_id(area_id, payment.as_deref(), limit, offset, conn)
+            })
+            .await?
+    }
+
     pub async fn select_by_id(&self, id: i64) -> Result<Option<Element>> {
-        self.pool
+        self.read_pool
             .get()
             .await?
             .interact(move |conn| Element::select_by_id(id, conn))
@@ -78,19 +310,82 @@ impl ElementRepo {
         id: i64,
     ) -> Result<Option<Element>> {
         let r#type = r#type.to_string();
-        self.pool
+        self.read_pool
             .get()
             .await?
             .interact(move |conn| Element::select_by_osm_type_and_id(&r#type, id, conn))
             .await?
     }
 
-    pub async fn patch_tags(&self, id: i64, tags: &Map<String, Value>) -> Result<Element> {
+    // Keeps the order of ids, missing elements are None
+    pub async fn select_by_osm_type_and_ids(
+        &self,
+        ids: Vec<(String, i64)>,
+    ) -> Result<Vec<Option<Element>>> {
+        self.read_pool
+            .get()
+            .await?
+            .interact(move |conn| {
+                ids.iter()
+                    .map(|(r#type, id)| Element::select_by_osm_type_and_id(r#type, *id, conn))
+                    .collect()
+            })
+            .await?
+    }
+
+    pub async fn select_event_stats(
+        &self,
+        ids: Vec<i64>,
+    ) -> Result<HashMap<i64, (i64, OffsetDateTime)>> {
+        self.read_pool
+            .get()
+            .await?
+            .interact(move |conn| Event::select_stats_by_element_ids(&ids, conn))
+            .await?
+    }
+
+    pub async fn restore(&self, id: i64, user_id: i64, restored_by: &str) -> Result<Element> {
+        let restored_by = restored_by.to_string();
+        self.pool
+            .get()
+            .await?
+            .interact(move |conn| Element::restore(id, user_id, &restored_by, conn))
+            .await?
+    }
+
+    pub async fn verify(&self, id: i64, user_id: i64, verified_by: &str) -> Result<Element> {
+        let verified_by = verified_by.to_string();
+        let date = OffsetDateTime::now_utc().date();
+        self.pool
+            .get()
+            .await?
+            .interact(move |conn| Element::verify(id, user_id, &verified_by, date, conn))
+            .await?
+    }
+
+    pub async fn patch_tags(
+        &self,
+        id: i64,
+        tags: &Map<String, Value>,
+        changed_by: &str,
+        expected_updated_at: Option<OffsetDateTime>,
+    ) -> Result<Element> {
         let tags = tags.clone();
+        let changed_by = changed_by.to_string();
         self.pool
             .get()
             .await?
-            .interact(move |conn| Element::_patch_tags(id, &tags, conn))
+            .interact(move |conn| {
+                Element::patch_tags_with_history(id, &tags, &changed_by, expected_updated_at, conn)
+            })
+            .await?
+    }
+
+    pub async fn select_tag_history(&self, id: i64) -> Result<Vec<ElementTagChange>> {
+        self.read_pool
+            .get()
+            .await?
+            .interact(move |conn| ElementTagChange::select_by_element_id(id, conn))
             .await?
     }
 
@@ -129,22 +424,32 @@ const ALL_COLUMNS: &str = "rowid, overpass_data, tags, created_at, updated_at, d
 const COL_ROWID: &str = "rowid";
 const COL_OVERPASS_DATA: &str = "overpass_data";
 const COL_TAGS: &str = "tags";
-const _COL_CREATED_AT: &str = "created_at";
+const COL_LAT: &str = "lat";
+const COL_LON: &str = "lon";
+const COL_CREATED_AT: &str = "created_at";
 const COL_UPDATED_AT: &str = "updated_at";
 const COL_DELETED_AT: &str = "deleted_at";
 
+// Same tag surveyors use in OSM, so both sources share the meaning
+const VERIFICATION_TAG: &str = "check_date:currency:XBT";
+
 impl Element {
     pub fn insert(overpass_data: &OverpassElement, conn: &Connection) -> Result<Element> {
         let query = format!(
             r#"
-                INSERT INTO {TABLE} ({COL_OVERPASS_DATA}) 
-                VALUES (json(:overpass_data))
+                INSERT INTO {TABLE} ({COL_OVERPASS_DATA}, {COL_LAT}, {COL_LON}) 
+                VALUES (json(:overpass_data), :lat, :lon)
             "#
         );
         debug!(query);
+        let coord = overpass_data.try_coord();
         conn.execute(
             &query,
-            named_params! { ":overpass_data": serde_json::to_string(overpass_data)?},
+            named_params! {
+                ":overpass_data": serde_json::to_string(overpass_data)?,
+                ":lat": coord.map(|it| it.y),
+                ":lon": coord.map(|it| it.x),
+            },
         )?;
         Ok(Element::select_by_id(conn.last_insert_rowid(), &conn)?
             .ok_or(Error::Rusqlite(rusqlite::Error::QueryReturnedNoRows))?)
@@ -196,6 +501,248 @@ impl Element {
             .collect::<Result<Vec<_>, _>>()?)
     }
 
+    // Keyset pagination, every page is an index lookup no matter how deep into the table it is
+    pub fn select_page(
+        updated_since: Option<&OffsetDateTime>,
+        after_id: i64,
+        limit: i64,
+        conn: &Connection,
+    ) -> Result<Vec<Element>> {
+        let query = format!(
+            r#"
+                SELECT {ALL_COLUMNS}
+                FROM {TABLE}
+                WHERE {COL_ROWID} > :after_id
+                AND {COL_UPDATED_AT} > :updated_since
+                ORDER BY {COL_ROWID}
+                LIMIT :limit
+            "#
+        );
+        let updated_since = match updated_since {
+            Some(updated_since) => updated_since.format(&Rfc3339)?,
+            None => "".into(),
+        };
+        debug!(query);
+        Ok(conn
+            .prepare(&query)?
+            .query_map(
+                named_params! {
+                    ":updated_since": updated_since,
+                    ":after_id": after_id,
+                    ":limit": limit,
+                },
+                mapper(),
+            )?
+            .collect::<Result<Vec<_>, _>>()?)
+    }
+
+    pub fn select_sorted(
+        filter: &Filter,
+        sort: Sort,
+        limit: Option<i64>,
+        conn: &Connection,
+    ) -> Result<Vec<Element>> {
+        let query = format!(
+            r#"
+                SELECT {ALL_COLUMNS}
+                FROM {TABLE}
+                WHERE {}
+                ORDER BY {}
+                LIMIT :limit
+            "#,
+            Filter::sql(),
+            sort.order_by(),
+        );
+        debug!(query);
+        let (min_lon, min_lat, max_lon, max_lat) = filter.bbox_params();
+        Ok(conn
+            .prepare(&query)?
+            .query_map(
+                named_params! {
+                    ":country": filter.country,
+                    ":updated_since": filter.updated_since()?,
+                    ":min_lon": min_lon,
+                    ":min_lat": min_lat,
+                    ":max_lon": max_lon,
+                    ":max_lat": max_lat,
+                    ":area": filter.area,
+                    ":outdated": filter.outdated,
+                    ":never_verified": filter.never_verified,
+                    ":min_verification_date": filter.min_verification_date()?,
+                    ":limit": limit.unwrap_or(i64::MAX),
+                },
+                mapper(),
+            )?
+            .collect::<Result<Vec<_>, _>>()?)
+    }
+
+    // Newest change in the whole table, deleted rows included
+    pub fn select_max_updated_at(conn: &Connection) -> Result<Option<OffsetDateTime>> {
+        let query = format!(
+            r#"
+                SELECT max({COL_UPDATED_AT})
+                FROM {TABLE}
+            "#
+        );
+        debug!(query);
+        Ok(conn.query_row(&query, [], |row| row.get(0))?)
+    }
+
+    pub fn select_count(filter: &Filter, conn: &Connection) -> Result<i64> {
+        let query = format!(
+            r#"
+                SELECT count(*)
+                FROM {TABLE}
+                WHERE {}
+            "#,
+            Filter::sql(),
+        );
+        debug!(query);
+        let (min_lon, min_lat, max_lon, max_lat) = filter.bbox_params();
+        Ok(conn.query_row(
+            &query,
+            named_params! {
+                ":country": filter.country,
+                ":updated_since": filter.updated_since()?,
+                ":min_lon": min_lon,
+                ":min_lat": min_lat,
+                ":max_lon": max_lon,
+                ":max_lat": max_lat,
+                ":area": filter.area,
+                ":outdated": filter.outdated,
+                ":never_verified": filter.never_verified,
+                ":min_verification_date": filter.min_verification_date()?,
+            },
+            |row| row.get(0),
+        )?)
+    }
+
+    // Returns (lat, lon) of every element which isn't deleted, without loading the rest of the data
+    pub fn select_coords(conn: &Connection) -> Result<Vec<(f64, f64)>> {
+        let query = format!(
+            r#"
+                SELECT {COL_LAT}, {COL_LON}
+                FROM {TABLE}
+                WHERE {COL_DELETED_AT} IS NULL AND {COL_LAT} IS NOT NULL AND {COL_LON} IS NOT NULL
+            "#
+        );
+        debug!(query);
+        Ok(conn
+            .prepare(&query)?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?)
+    }
+
+    // Uses the element_rtree index, which is kept in sync with the lat and lon columns by triggers
+    pub fn select_by_bbox(
+        min_lon: f64,
+        min_lat: f64,
+        max_lon: f64,
+        max_lat: f64,
+        conn: &Connection,
+    ) -> Result<Vec<Element>> {
+        let query = format!(
+            r#"
+                SELECT {ALL_COLUMNS}
+                FROM {TABLE}
+                WHERE {COL_ROWID} IN (
+                    SELECT id
+                    FROM element_rtree
+                    WHERE max_lon >= :min_lon AND min_lon <= :max_lon
+                    AND max_lat >= :min_lat AND min_lat <= :max_lat
+                )
+                ORDER BY {COL_UPDATED_AT}, {COL_ROWID}
+            "#
+        );
+        debug!(query);
+        Ok(conn
+            .prepare(&query)?
+            .query_map(
+                named_params! {
+                    ":min_lon": min_lon,
+                    ":min_lat": min_lat,
+                    ":max_lon": max_lon,
+                    ":max_lat": max_lat,
+                },
+                mapper(),
+            )?
+            .collect::<Result<Vec<_>, _>>()?)
+    }
+
+    // Uses the element_fts index, which is kept in sync with name and key tags by triggers
+    // Its tokenizer folds case and strips diacritics, so "cafe" matches "Café" and vice versa
+    pub fn select_by_search_query(
+        search_query: &str,
+        limit: Option<i64>,
+        conn: &Connection,
+    ) -> Result<Vec<Element>> {
+        let query = format!(
+            r#"
+                SELECT
+                    el.{COL_ROWID},
+                    el.{COL_OVERPASS_DATA},
+                    el.{COL_TAGS},
+                    el.{COL_CREATED_AT},
+                    el.{COL_UPDATED_AT},
+                    el.{COL_DELETED_AT}
+                FROM element_fts
+                JOIN {TABLE} el ON el.{COL_ROWID} = element_fts.rowid
+                WHERE element_fts MATCH :match
+                ORDER BY element_fts.rank
+                LIMIT :limit
+            "#
+        );
+        debug!(query);
+        Ok(conn
+            .prepare(&query)?
+            .query_map(
+                named_params! {
+                    ":match": fts_match(search_query),
+                    ":limit": limit.unwrap_or(i64::MAX),
+                },
+                mapper(),
+            )?
+            .collect::<Result<Vec<_>, _>>()?)
+    }
+
+    // Relies on the element areas tag, payment filters by payment:{payment}=yes
+    pub fn select_by_area_id(
+        area_id: i64,
+        payment: Option<&str>,
+        limit: i64,
+        offset: i64,
+        conn: &Connection,
+    ) -> Result<Vec<Element>> {
+        let query = format!(
+            r#"
+                SELECT {ALL_COLUMNS}
+                FROM {TABLE}
+                WHERE {COL_DELETED_AT} IS NULL AND EXISTS (
+                    SELECT 1
+                    FROM json_each({COL_TAGS}, '$.areas')
+                    WHERE json_extract(value, '$.id') = :area_id
+                )
+                AND (:payment IS NULL OR json_extract({COL_OVERPASS_DATA}, :payment) = 'yes')
+                ORDER BY {COL_ROWID}
+                LIMIT :limit
+                OFFSET :offset
+            "#
+        );
+        debug!(query);
+        Ok(conn
+            .prepare(&query)?
+            .query_map(
+                named_params! {
+                    ":area_id": area_id,
+                    ":payment": payment.map(|it| format!("$.tags.\"payment:{it}\"")),
+                    ":limit": limit,
+                    ":offset": offset,
+                },
+                mapper(),
+            )?
+            .collect::<Result<Vec<_>, _>>()?)
+    }
+
     pub fn select_by_id(id: i64, conn: &Connection) -> Result<Option<Element>> {
         let query = format!(
             r#"
@@ -281,6 +828,38 @@ impl Element {
             .ok_or(Error::Rusqlite(rusqlite::Error::QueryReturnedNoRows))?)
     }
 
+    // Keeps a record of every changed tag, so it's possible to tell who changed what
+    // The write lock is taken upfront, so nobody can sneak in between the check and the update
+    pub fn patch_tags_with_history(
+        id: i64,
+        tags: &Map<String, Value>,
+        changed_by: &str,
+        expected_updated_at: Option<OffsetDateTime>,
+        conn: &mut Connection,
+    ) -> Result<Element> {
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let old = Element::select_by_id(id, &tx)?
+            .ok_or(Error::Rusqlite(rusqlite::Error::QueryReturnedNoRows))?;
+        if let Some(expected_updated_at) = expected_updated_at {
+            if old.updated_at != expected_updated_at {
+                Err(Error::HttpConflict(format!(
+                    "Element with id = {id} has been modified since {}",
+                    expected_updated_at.format(&Rfc3339)?,
+                )))?
+            }
+        }
+        let new = Element::_patch_tags(id, tags, &tx)?;
+        for tag in tags.keys() {
+            let old_value = old.tags.get(tag).unwrap_or(&Value::Null);
+            let new_value = new.tags.get(tag).unwrap_or(&Value::Null);
+            if old_value != new_value {
+                ElementTagChange::insert(id, tag, old_value, new_value, changed_by, &tx)?;
+            }
+        }
+        tx.commit()?;
+        Ok(new)
+    }
+
     pub fn set_overpass_data(
         &self,
         overpass_data: &OverpassElement,
@@ -289,16 +868,19 @@ impl Element {
         let query = format!(
             r#"
                 UPDATE {TABLE}
-                SET {COL_OVERPASS_DATA} = json(:overpass_data)
+                SET {COL_OVERPASS_DATA} = json(:overpass_data), {COL_LAT} = :lat, {COL_LON} = :lon
                 WHERE {COL_ROWID} = :id
             "#
         );
         debug!(query);
+        let coord = overpass_data.try_coord();
         conn.execute(
             &query,
             named_params! {
                 ":id": self.id,
                 ":overpass_data": serde_json::to_string(overpass_data)?,
+                ":lat": coord.map(|it| it.y),
+                ":lon": coord.map(|it| it.x),
             },
         )?;
         Ok(Element::select_by_id(self.id, &conn)?
@@ -339,6 +921,26 @@ impl Element {
             .ok_or(Error::Rusqlite(rusqlite::Error::QueryReturnedNoRows))?)
     }
 
+    // Returns the number of changed rows, which is zero if the stored coordinates are up to date
+    pub fn set_coord(id: i64, coord: Option<Coord>, conn: &Connection) -> Result<usize> {
+        let query = format!(
+            r#"
+                UPDATE {TABLE}
+                SET {COL_LAT} = :lat, {COL_LON} = :lon
+                WHERE {COL_ROWID} = :id AND ({COL_LAT} IS NOT :lat OR {COL_LON} IS NOT :lon)
+            "#
+        );
+        debug!(query);
+        Ok(conn.execute(
+            &query,
+            named_params! {
+                ":id": id,
+                ":lat": coord.map(|it| it.y),
+                ":lon": coord.map(|it| it.x),
+            },
+        )?)
+    }
+
     #[cfg(test)]
     pub fn set_updated_at(
         &self,
@@ -373,6 +975,44 @@ impl Element {
             .ok_or(Error::Rusqlite(rusqlite::Error::QueryReturnedNoRows))?)
     }
 
+    // Admins aren't OSM users, so the restore event goes to the OSM user they act on behalf of
+    pub fn restore(
+        id: i64,
+        user_id: i64,
+        restored_by: &str,
+        conn: &mut Connection,
+    ) -> Result<Element> {
+        let tx = conn.transaction()?;
+        let element = Element::select_by_id(id, &tx)?
+            .ok_or(Error::Rusqlite(rusqlite::Error::QueryReturnedNoRows))?
+            .set_deleted_at(None, &tx)?;
+        let event = Event::insert(user_id, id, "restore", &tx)?;
+        let mut tags = HashMap::new();
+        tags.insert("restored_by".into(), restored_by.into());
+        event.patch_tags(&tags, &tx)?;
+        tx.commit()?;
+        Ok(element)
+    }
+
+    // Surveyors verify elements without editing OSM, so the date is stored as a BTC Map tag
+    pub fn verify(
+        id: i64,
+        user_id: i64,
+        verified_by: &str,
+        date: Date,
+        conn: &mut Connection,
+    ) -> Result<Element> {
+        let tx = conn.transaction()?;
+        let date = date.format(format_description!("[year]-[month]-[day]"))?;
+        let element = Element::_set_tag(id, VERIFICATION_TAG, &date.into(), &tx)?;
+        let event = Event::insert(user_id, id, "verify", &tx)?;
+        let mut tags = HashMap::new();
+        tags.insert("verified_by".into(), verified_by.into());
+        event.patch_tags(&tags, &tx)?;
+        tx.commit()?;
+        Ok(element)
+    }
+
     pub fn set_deleted_at(
         &self,
         deleted_at: Option<OffsetDateTime>,
@@ -415,6 +1055,30 @@ impl Element {
     pub fn tag(&self, name: &str) -> &Value {
         self.tags.get(name).unwrap_or(&Value::Null)
     }
+
+    // The most recent of the OSM verification date and the one submitted through the API
+    pub fn verification_date(&self) -> Option<OffsetDateTime> {
+        let verified = self
+            .tag(VERIFICATION_TAG)
+            .as_str()
+            .and_then(|it| Date::parse(it, format_description!("[year]-[month]-[day]")).ok())
+            .map(|it| it.midnight().assume_utc());
+        self.overpass_data.verification_date().max(verified)
+    }
+
+    pub fn up_to_date(&self) -> bool {
+        let min_date = OffsetDateTime::now_utc().saturating_sub(Duration::days(outdated_days()));
+        self.verification_date().is_some_and(|it| it > min_date)
+    }
+}
+
+// Quotes every term so user input can't break FTS5 query syntax, each term is a prefix match
+fn fts_match(search_query: &str) -> String {
+    search_query
+        .split_whitespace()
+        .map(|it| format!("\"{}\"*", it.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
 }
 
 const fn mapper() -> fn(&Row) -> rusqlite::Result<Element> {
@@ -440,7 +1104,11 @@ mod test {
     use serde_json::json;
     use time::{macros::datetime, OffsetDateTime};
 
-    use crate::{osm::overpass::OverpassElement, test::mock_conn, Result};
+    use crate::{
+        osm::overpass::{Bounds, OverpassElement},
+        test::{mock_conn, mock_osm_tags},
+        Result,
+    };
 
     use super::Element;
 
@@ -470,6 +1138,50 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn select_by_bbox() -> Result<()> {
+        let conn = mock_conn();
+        let node = |id, lon, lat| OverpassElement {
+            lon: Some(lon),
+            lat: Some(lat),
+            ..OverpassElement::mock(id)
+        };
+        Element::insert(&node(1, 10.0, 10.0), &conn)?;
+        Element::insert(&node(2, 15.0, 12.0), &conn)?;
+        Element::insert(&node(3, 30.0, 10.0), &conn)?;
+        Element::insert(&node(4, -10.0, -10.0), &conn)?;
+        Element::insert(
+            &OverpassElement {
+                r#type: "way".into(),
+                lon: None,
+                lat: None,
+                bounds: Some(Bounds {
+                    minlon: 11.0,
+                    maxlon: 13.0,
+                    minlat: 11.0,
+                    maxlat: 13.0,
+                }),
+                ..OverpassElement::mock(5)
+            },
+            &conn,
+        )?;
+        // Moved out of the box, index should follow
+        Element::insert(&node(6, 12.0, 12.0), &conn)?
+            .set_overpass_data(&node(6, 50.0, 50.0), &conn)?;
+        let (min_lon, min_lat, max_lon, max_lat) = (5.0, 5.0, 20.0, 20.0);
+        let linear_scan: Vec<Element> = Element::select_all(None, &conn)?
+            .into_iter()
+            .filter(|it| {
+                let coord = it.overpass_data.coord();
+                coord.x >= min_lon && coord.x <= max_lon && coord.y >= min_lat && coord.y <= max_lat
+            })
+            .collect();
+        let rtree = Element::select_by_bbox(min_lon, min_lat, max_lon, max_lat, &conn)?;
+        assert_eq!(3, rtree.len());
+        assert_eq!(linear_scan, rtree);
+        Ok(())
+    }
+
     #[test]
     fn select_updated_since() -> Result<()> {
         let conn = mock_conn();
@@ -484,6 +1196,56 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn select_page() -> Result<()> {
+        let conn = mock_conn();
+        for id in 1..=3 {
+            Element::insert(&OverpassElement::mock(id), &conn)?;
+        }
+        Element::_set_updated_at(2, &datetime!(2020-01-01 00:00 UTC), &conn)?;
+        let ids = |page: Vec<Element>| page.iter().map(|it| it.id).collect::<Vec<_>>();
+        assert_eq!(vec![1, 2], ids(Element::select_page(None, 0, 2, &conn)?));
+        assert_eq!(vec![3], ids(Element::select_page(None, 2, 2, &conn)?));
+        assert!(Element::select_page(None, 3, 2, &conn)?.is_empty());
+        let updated_since = datetime!(2021-01-01 00:00 UTC);
+        assert_eq!(
+            vec![1, 3],
+            ids(Element::select_page(Some(&updated_since), 0, 2, &conn)?),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn select_by_search_query_ignores_case_and_diacritics() -> Result<()> {
+        let conn = mock_conn();
+        let cafe = Element::insert(
+            &OverpassElement {
+                tags: Some(mock_osm_tags(&["name", "Café Nöir", "amenity", "cafe"])),
+                ..OverpassElement::mock(1)
+            },
+            &conn,
+        )?;
+        Element::insert(
+            &OverpassElement {
+                tags: Some(mock_osm_tags(&["name", "Bakery", "shop", "bakery"])),
+                ..OverpassElement::mock(2)
+            },
+            &conn,
+        )?;
+        assert_eq!(
+            vec![cafe.id],
+            Element::select_by_search_query("Cafe noir", None, &conn)?
+                .into_iter()
+                .map(|it| it.id)
+                .collect::<Vec<_>>(),
+        );
+        assert_eq!(
+            1,
+            Element::select_by_search_query("CAFÉ", None, &conn)?.len()
+        );
+        Ok(())
+    }
+
     #[test]
     fn select_by_id() -> Result<()> {
         let conn = mock_conn();
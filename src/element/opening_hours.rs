@@ -0,0 +1,188 @@
+use ::opening_hours::RuleKind;
+use chrono::{NaiveDate, NaiveDateTime};
+use std::env;
+use std::sync::OnceLock;
+use time::macros::format_description;
+use time::{Date, Duration, OffsetDateTime, PrimitiveDateTime, UtcOffset};
+use time_tz::{timezones, OffsetDateTimeExt, Tz};
+
+// Parsing and evaluation are delegated to the opening-hours crate, which covers the whole OSM
+// opening_hours syntax, including public holidays, month ranges and exceptions
+pub struct OpeningHours(::opening_hours::OpeningHours);
+
+pub fn parse(value: &str) -> Option<OpeningHours> {
+    let value = value.trim();
+    if value.is_empty() {
+        return None;
+    }
+    ::opening_hours::OpeningHours::parse(value)
+        .ok()
+        .map(OpeningHours)
+}
+
+impl OpeningHours {
+    // States the crate can't resolve, such as rules with comments, count as closed
+    pub fn is_open(&self, at: PrimitiveDateTime) -> bool {
+        self.0.is_open(naive(at))
+    }
+
+    pub fn hours(&self, date: Date) -> String {
+        let day_start = naive(date.midnight());
+        let day_end = naive(date.midnight() + Duration::days(1));
+        let intervals = match self.0.iter_range(day_start, day_end) {
+            Ok(ranges) => ranges
+                .filter(|it| matches!(it.kind, RuleKind::Open))
+                .map(|it| {
+                    format!(
+                        "{}-{}",
+                        format_time(it.range.start, day_start),
+                        format_time(it.range.end, day_start),
+                    )
+                })
+                .collect::<Vec<_>>(),
+            Err(_) => vec![],
+        };
+        if intervals.is_empty() {
+            return "off".into();
+        }
+        intervals.join(",")
+    }
+}
+
+fn naive(at: PrimitiveDateTime) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(at.year(), u8::from(at.month()) as u32, at.day() as u32)
+        .and_then(|it| it.and_hms_opt(at.hour() as u32, at.minute() as u32, at.second() as u32))
+        .unwrap_or_default()
+}
+
+// Time since the start of the day, so the end of the last interval is 24:00
+fn format_time(at: NaiveDateTime, day_start: NaiveDateTime) -> String {
+    let minutes = (at - day_start).num_minutes();
+    format!("{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+#[derive(Clone, Copy)]
+pub enum Zone {
+    Named(&'static Tz),
+    Fixed(UtcOffset),
+}
+
+impl Zone {
+    // IANA names such as Europe/Berlin follow DST, offsets such as +02:00 are kept for old clients
+    pub fn parse(value: &str) -> Option<Zone> {
+        if let Some(tz) = timezones::get_by_name(value) {
+            return Some(Zone::Named(tz));
+        }
+        UtcOffset::parse(
+            value,
+            format_description!("[offset_hour sign:mandatory]:[offset_minute]"),
+        )
+        .ok()
+        .map(Zone::Fixed)
+    }
+
+    pub fn local(&self, at: OffsetDateTime) -> PrimitiveDateTime {
+        let local = match self {
+            Zone::Named(tz) => at.to_timezone(*tz),
+            Zone::Fixed(offset) => at.to_offset(*offset),
+        };
+        PrimitiveDateTime::new(local.date(), local.time())
+    }
+}
+
+// Set OPENING_HOURS_TZ to change the zone used for requests without tz, it defaults to UTC
+pub fn default_zone() -> Zone {
+    static DEFAULT_ZONE: OnceLock<Zone> = OnceLock::new();
+    *DEFAULT_ZONE.get_or_init(|| parse_default_zone(env::var("OPENING_HOURS_TZ").ok().as_deref()))
+}
+
+fn parse_default_zone(value: Option<&str>) -> Zone {
+    value
+        .and_then(Zone::parse)
+        .unwrap_or(Zone::Fixed(UtcOffset::UTC))
+}
+
+#[cfg(test)]
+mod test {
+    use super::Zone;
+    use time::macros::{date, datetime};
+
+    #[test]
+    fn parse() {
+        assert!(super::parse("24/7").is_some());
+        assert!(super::parse("Mo-Fr 09:00-17:00; Sa 10:00-12:00,13:00-15:00; Su off").is_some());
+        assert!(super::parse("09:00-17:00").is_some());
+        assert!(super::parse("Fr-Mo 22:00-02:00").is_some());
+        assert!(super::parse("Mo-Fr 09:00-17:00; PH off").is_some());
+        assert!(super::parse("").is_none());
+        assert!(super::parse("call us").is_none());
+    }
+
+    #[test]
+    fn is_open() {
+        let hours = super::parse("Mo-Fr 09:00-17:00").unwrap();
+        // 2024-01-01 is a Monday
+        assert!(hours.is_open(datetime!(2024-01-01 09:00)));
+        assert!(hours.is_open(datetime!(2024-01-05 16:59)));
+        assert!(!hours.is_open(datetime!(2024-01-01 08:59)));
+        assert!(!hours.is_open(datetime!(2024-01-01 17:00)));
+        assert!(!hours.is_open(datetime!(2024-01-06 12:00)));
+        let hours = super::parse("Mo-Sa 08:00-20:00; Sa 22:00-02:00").unwrap();
+        assert!(!hours.is_open(datetime!(2024-01-06 12:00)));
+        assert!(hours.is_open(datetime!(2024-01-06 23:00)));
+        assert!(hours.is_open(datetime!(2024-01-07 01:00)));
+        assert!(!hours.is_open(datetime!(2024-01-07 02:00)));
+        assert!(super::parse("24/7")
+            .unwrap()
+            .is_open(datetime!(2024-01-07 03:00)));
+    }
+
+    #[test]
+    fn hours() {
+        let hours = super::parse("Mo-Fr 09:00-17:00; Sa 10:00-12:00,13:00-15:00").unwrap();
+        assert_eq!("09:00-17:00", hours.hours(date!(2024 - 01 - 03)));
+        assert_eq!(
+            "10:00-12:00,13:00-15:00",
+            hours.hours(date!(2024 - 01 - 06))
+        );
+        assert_eq!("off", hours.hours(date!(2024 - 01 - 07)));
+        assert_eq!(
+            "00:00-24:00",
+            super::parse("24/7").unwrap().hours(date!(2024 - 01 - 07))
+        );
+    }
+
+    #[test]
+    fn zone() {
+        let at = datetime!(2024-07-01 12:00 UTC);
+        let berlin = Zone::parse("Europe/Berlin").unwrap();
+        assert_eq!(datetime!(2024-07-01 14:00), berlin.local(at));
+        assert_eq!(
+            datetime!(2024-01-01 13:00),
+            berlin.local(datetime!(2024-01-01 12:00 UTC))
+        );
+        assert_eq!(
+            datetime!(2024-07-01 09:30),
+            Zone::parse("-02:30").unwrap().local(at)
+        );
+        assert!(Zone::parse("Europe/Atlantis").is_none());
+        assert!(Zone::parse("2").is_none());
+    }
+
+    #[test]
+    fn parse_default_zone() {
+        let at = datetime!(2024-07-01 12:00 UTC);
+        assert_eq!(
+            datetime!(2024-07-01 12:00),
+            super::parse_default_zone(None).local(at)
+        );
+        assert_eq!(
+            datetime!(2024-07-01 14:00),
+            super::parse_default_zone(Some("Europe/Berlin")).local(at)
+        );
+        assert_eq!(
+            datetime!(2024-07-01 12:00),
+            super::parse_default_zone(Some("nowhere")).local(at)
+        );
+    }
+}
@@ -0,0 +1,156 @@
+use crate::{Error, Result};
+use rusqlite::{named_params, Connection, OptionalExtension, Row};
+use serde_json::Value;
+use time::OffsetDateTime;
+use tracing::debug;
+
+// Changes to BTC Map's own element tags, OSM edits are tracked by events. Tokens aren't linked to
+// OSM users, so these changes can't be stored as events
+#[derive(PartialEq, Debug)]
+pub struct ElementTagChange {
+    pub id: i64,
+    pub element_id: i64,
+    pub tag: String,
+    // Null if the tag didn't exist or was removed
+    pub old_value: Value,
+    pub new_value: Value,
+    pub changed_by: String,
+    pub created_at: OffsetDateTime,
+}
+
+const TABLE: &str = "element_tag_change";
+const ALL_COLUMNS: &str = "id, element_id, tag, old_value, new_value, changed_by, created_at";
+const COL_ID: &str = "id";
+const COL_ELEMENT_ID: &str = "element_id";
+const COL_TAG: &str = "tag";
+const COL_OLD_VALUE: &str = "old_value";
+const COL_NEW_VALUE: &str = "new_value";
+const COL_CHANGED_BY: &str = "changed_by";
+
+impl ElementTagChange {
+    pub fn insert(
+        element_id: i64,
+        tag: &str,
+        old_value: &Value,
+        new_value: &Value,
+        changed_by: &str,
+        conn: &Connection,
+    ) -> Result<ElementTagChange> {
+        let query = format!(
+            r#"
+                INSERT INTO {TABLE} (
+                    {COL_ELEMENT_ID},
+                    {COL_TAG},
+                    {COL_OLD_VALUE},
+                    {COL_NEW_VALUE},
+                    {COL_CHANGED_BY}
+                ) VALUES (
+                    :element_id,
+                    :tag,
+                    :old_value,
+                    :new_value,
+                    :changed_by
+                )
+            "#
+        );
+        debug!(query);
+        conn.execute(
+            &query,
+            named_params! {
+                ":element_id": element_id,
+                ":tag": tag,
+                ":old_value": to_sql(old_value)?,
+                ":new_value": to_sql(new_value)?,
+                ":changed_by": changed_by,
+            },
+        )?;
+        Ok(
+            ElementTagChange::select_by_id(conn.last_insert_rowid(), conn)?
+                .ok_or(Error::Rusqlite(rusqlite::Error::QueryReturnedNoRows))?,
+        )
+    }
+
+    pub fn select_by_id(id: i64, conn: &Connection) -> Result<Option<ElementTagChange>> {
+        let query = format!(
+            r#"
+                SELECT {ALL_COLUMNS}
+                FROM {TABLE}
+                WHERE {COL_ID} = :id
+            "#
+        );
+        debug!(query);
+        Ok(conn
+            .query_row(&query, named_params! { ":id": id }, Self::mapper())
+            .optional()?)
+    }
+
+    pub fn select_by_element_id(
+        element_id: i64,
+        conn: &Connection,
+    ) -> Result<Vec<ElementTagChange>> {
+        let query = format!(
+            r#"
+                SELECT {ALL_COLUMNS}
+                FROM {TABLE}
+                WHERE {COL_ELEMENT_ID} = :element_id
+                ORDER BY {COL_ID}
+            "#
+        );
+        debug!(query);
+        Ok(conn
+            .prepare(&query)?
+            .query_map(named_params! { ":element_id": element_id }, Self::mapper())?
+            .collect::<Result<Vec<_>, _>>()?)
+    }
+
+    const fn mapper() -> fn(&Row) -> rusqlite::Result<ElementTagChange> {
+        |row: &Row| -> rusqlite::Result<ElementTagChange> {
+            Ok(ElementTagChange {
+                id: row.get(0)?,
+                element_id: row.get(1)?,
+                tag: row.get(2)?,
+                old_value: from_sql(row.get(3)?),
+                new_value: from_sql(row.get(4)?),
+                changed_by: row.get(5)?,
+                created_at: row.get(6)?,
+            })
+        }
+    }
+}
+
+fn to_sql(value: &Value) -> Result<Option<String>> {
+    match value {
+        Value::Null => Ok(None),
+        value => Ok(Some(serde_json::to_string(value)?)),
+    }
+}
+
+fn from_sql(value: Option<String>) -> Value {
+    value
+        .and_then(|it| serde_json::from_str(&it).ok())
+        .unwrap_or(Value::Null)
+}
+
+#[cfg(test)]
+mod test {
+    use super::ElementTagChange;
+    use crate::element::Element;
+    use crate::osm::overpass::OverpassElement;
+    use crate::{test::mock_conn, Result};
+    use serde_json::{json, Value};
+
+    #[test]
+    fn insert() -> Result<()> {
+        let conn = mock_conn();
+        let element = Element::insert(&OverpassElement::mock(1), &conn)?;
+        let change =
+            ElementTagChange::insert(element.id, "foo", &Value::Null, &json!(1), "test", &conn)?;
+        assert_eq!(Value::Null, change.old_value);
+        assert_eq!(json!(1), change.new_value);
+        assert_eq!(
+            change,
+            ElementTagChange::select_by_id(change.id, &conn)?.unwrap()
+        );
+        Ok(())
+    }
+}
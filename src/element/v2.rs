@@ -1,117 +1,820 @@
+use crate::area::v2::parse_country_code;
+use crate::area::AreaRepo;
+use crate::element::geohash;
+use crate::element::opening_hours;
+use crate::element::opening_hours::Zone;
+use crate::element::tag_change::ElementTagChange;
 use crate::element::Element;
 use crate::element::ElementRepo;
+use crate::element::Filter;
+use crate::element::Sort;
+use crate::event::model::EventRepo;
+use crate::osm::osm;
 use crate::osm::overpass::OverpassElement;
+use crate::server::last_modified;
+use crate::server::limit::resolve_limit;
+use crate::server::limit::resolve_sync_limit;
+use crate::server::strict;
+use crate::server::timestamp;
+use crate::server::TOTAL_COUNT_HEADER;
 use crate::Error;
 use actix_web::get;
+use actix_web::http::header::ACCEPT_LANGUAGE;
+use actix_web::route;
+use actix_web::web::Bytes;
 use actix_web::web::Data;
 use actix_web::web::Json;
 use actix_web::web::Path;
 use actix_web::web::Query;
 use actix_web::web::Redirect;
+use actix_web::CustomizeResponder;
 use actix_web::Either;
+use actix_web::HttpRequest;
+use actix_web::HttpResponse;
+use actix_web::Responder;
+use futures_util::stream;
 use serde::Deserialize;
 use serde::Serialize;
 use serde_json::Value;
+use std::collections::BTreeMap;
 use std::collections::HashMap;
-use time::format_description::well_known::Rfc3339;
+use time::Duration;
 use time::OffsetDateTime;
+use time::PrimitiveDateTime;
 
 #[derive(Deserialize)]
 pub struct GetArgs {
     #[serde(default)]
-    #[serde(with = "time::serde::rfc3339::option")]
+    #[serde(with = "crate::server::updated_since::option")]
     updated_since: Option<OffsetDateTime>,
     limit: Option<i64>,
+    country: Option<String>,
+    // min_lon,min_lat,max_lon,max_lat
+    bbox: Option<String>,
+    // Area url_alias
+    area: Option<String>,
+    #[serde(default)]
+    outdated: bool,
+    #[serde(default)]
+    never_verified: bool,
+    sort: Option<String>,
+    #[serde(default)]
+    with_events: bool,
+    #[serde(default)]
+    with_hours: bool,
+    tz: Option<String>,
+    lang: Option<String>,
+}
+
+const GET_ARGS: [&str; 12] = [
+    "updated_since",
+    "limit",
+    "country",
+    "bbox",
+    "area",
+    "outdated",
+    "never_verified",
+    "sort",
+    "with_events",
+    "with_hours",
+    "tz",
+    "lang",
+];
+
+#[derive(Deserialize)]
+pub struct GetByIdArgs {
+    #[serde(default)]
+    with_events: bool,
+    #[serde(default)]
+    with_hours: bool,
+    tz: Option<String>,
+    lang: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
 pub struct GetItem {
     pub id: String,
     pub osm_json: OverpassElement,
+    // Copied from osm_json so clients don't have to dig into raw OSM data
+    pub osm_version: Option<i64>,
+    pub osm_timestamp: Option<String>,
+    pub osm_user: Option<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub osm_changeset_url: Option<String>,
+    #[serde(default)]
+    pub osm_url: String,
+    // Opens the element in the default OSM editor
+    #[serde(default)]
+    pub osm_edit_url: String,
+    // Free-text note for the app, empty if the element has none
+    #[serde(default)]
+    pub description: String,
+    // Never empty, the raw name is still available in osm_json
+    #[serde(default)]
+    pub display_name: String,
+    // Based on amenity=atm or the generated category, so clients don't have to detect ATMs
+    #[serde(default)]
+    pub is_atm: bool,
     pub tags: HashMap<String, Value>,
-    #[serde(with = "time::serde::rfc3339")]
+    #[serde(with = "crate::server::timestamp")]
     pub created_at: OffsetDateTime,
-    #[serde(with = "time::serde::rfc3339")]
+    #[serde(with = "crate::server::timestamp")]
     pub updated_at: OffsetDateTime,
     pub deleted_at: String,
+    // Only set when requested with with_events=true
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub events_count: Option<i64>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(with = "crate::server::timestamp::option")]
+    pub last_event_at: Option<OffsetDateTime>,
+    // Only set when requested with with_hours=true and opening_hours tag can be parsed
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub open_now: Option<bool>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hours_today: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct GetNdjsonArgs {
+    #[serde(default)]
+    #[serde(with = "crate::server::updated_since::option")]
+    updated_since: Option<OffsetDateTime>,
+}
+
+#[derive(Deserialize)]
+pub struct SearchArgs {
+    q: String,
+    limit: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct BatchArgs {
+    // Comma separated, such as node:1,way:2
+    ids: String,
+}
+
+const MAX_BATCH_IDS: usize = 500;
+
+#[derive(Deserialize)]
+pub struct HeatmapArgs {
+    precision: Option<usize>,
+}
+
+const DEFAULT_HEATMAP_PRECISION: usize = 5;
+// Cells are about 38 meters wide at this precision, finer cells don't make a useful heatmap
+const MAX_HEATMAP_PRECISION: usize = 8;
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct HeatmapItem {
+    pub geohash: String,
+    pub count: i64,
+}
+
+#[derive(Deserialize)]
+pub struct StatsArgs {
+    days: Option<i64>,
+}
+
+const DEFAULT_STATS_DAYS: i64 = 30;
+const MAX_STATS_DAYS: i64 = 3650;
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct StatsItem {
+    pub added: i64,
+    pub removed: i64,
+    pub net: i64,
+}
+
+#[derive(Deserialize)]
+pub struct GetDiffArgs {
+    osm_version: i64,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct TagChange {
+    pub old: String,
+    pub new: String,
+}
+
+// Describes how the tags changed from the requested OSM version to the stored one
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct GetDiffItem {
+    pub id: String,
+    pub osm_version: i64,
+    pub current_osm_version: Option<i64>,
+    pub added: BTreeMap<String, String>,
+    pub removed: BTreeMap<String, String>,
+    pub changed: BTreeMap<String, TagChange>,
+}
+
+// Tag edits made through the API, values are null when the tag didn't exist or was removed
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct GetTagHistoryItem {
+    pub tag: String,
+    pub old: Value,
+    pub new: Value,
+    pub changed_by: String,
+    #[serde(with = "crate::server::timestamp")]
+    pub created_at: OffsetDateTime,
+}
+
+impl Into<GetTagHistoryItem> for ElementTagChange {
+    fn into(self) -> GetTagHistoryItem {
+        GetTagHistoryItem {
+            tag: self.tag,
+            old: self.old_value,
+            new: self.new_value,
+            changed_by: self.changed_by,
+            created_at: self.created_at,
+        }
+    }
 }
 
 impl Into<GetItem> for Element {
     fn into(self) -> GetItem {
         GetItem {
             id: self.overpass_data.btcmap_id(),
+            osm_version: self.overpass_data.version,
+            osm_timestamp: self.overpass_data.timestamp.clone(),
+            osm_user: self.overpass_data.user.clone(),
+            osm_changeset_url: self
+                .overpass_data
+                .changeset
+                .map(|it| format!("https://www.openstreetmap.org/changeset/{it}")),
+            osm_url: format!(
+                "https://www.openstreetmap.org/{}/{}",
+                self.overpass_data.r#type, self.overpass_data.id,
+            ),
+            osm_edit_url: format!(
+                "https://www.openstreetmap.org/edit?{}={}",
+                self.overpass_data.r#type, self.overpass_data.id,
+            ),
+            description: description(&self.overpass_data).into(),
+            display_name: display_name(
+                &self.overpass_data,
+                self.tag("category").as_str().unwrap_or_default(),
+            ),
+            is_atm: self.overpass_data.tag("amenity") == "atm" || self.tag("category") == "atm",
             osm_json: self.overpass_data,
             tags: self.tags,
             created_at: self.created_at,
             updated_at: self.updated_at,
             deleted_at: self
                 .deleted_at
-                .map(|it| it.format(&Rfc3339).unwrap())
+                .map(|it| timestamp::format(&it))
                 .unwrap_or_default()
                 .into(),
+            events_count: None,
+            last_event_at: None,
+            open_now: None,
+            hours_today: None,
         }
     }
 }
 
+const DESCRIPTION_TAGS: [&str; 3] = ["description", "note", "description:en"];
+
+fn description(element: &OverpassElement) -> &str {
+    DESCRIPTION_TAGS
+        .iter()
+        .map(|it| element.tag(it))
+        .find(|it| !it.is_empty())
+        .unwrap_or_default()
+}
+
+const DISPLAY_NAME_TAGS: [&str; 3] = ["name", "brand", "operator"];
+
+// Unnamed elements are named after their category, so clients don't have to come up with a label
+fn display_name(element: &OverpassElement, category: &str) -> String {
+    if let Some(name) = DISPLAY_NAME_TAGS
+        .iter()
+        .map(|it| element.tag(it))
+        .find(|it| !it.is_empty())
+    {
+        return name.into();
+    }
+    match category {
+        "atm" => "ATM",
+        "cafe" => "Cafe",
+        "restaurant" => "Restaurant",
+        "bar" => "Bar",
+        "pub" => "Pub",
+        "hotel" => "Hotel",
+        _ => "Unnamed place",
+    }
+    .into()
+}
+
 impl Into<Json<GetItem>> for Element {
     fn into(self) -> Json<GetItem> {
         Json(self.into())
     }
 }
 
-#[get("")]
+#[route("", method = "GET", method = "HEAD")]
 pub async fn get(
+    req: HttpRequest,
     args: Query<GetArgs>,
     repo: Data<ElementRepo>,
-) -> Result<Either<Json<Vec<GetItem>>, Redirect>, Error> {
-    if args.limit.is_none() && args.updated_since.is_none() {
+) -> Result<Either<CustomizeResponder<Json<Vec<GetItem>>>, Redirect>, Error> {
+    strict::check(req.query_string(), &GET_ARGS)?;
+    let lang = lang(args.lang.as_deref(), &req);
+    let sort = parse_sort(args.sort.as_deref())?;
+    let limit = match args.updated_since {
+        Some(_) => resolve_sync_limit(args.limit),
+        None => Some(resolve_limit(args.limit)),
+    };
+    let filter = Filter {
+        country: match &args.country {
+            Some(country) => Some(parse_country_code(country)?),
+            None => None,
+        },
+        updated_since: args.updated_since,
+        bbox: match &args.bbox {
+            Some(bbox) => Some(parse_bbox(bbox)?),
+            None => None,
+        },
+        area: args.area.clone(),
+        outdated: args.outdated,
+        never_verified: args.never_verified,
+    };
+    let filtered = filter.country.is_some()
+        || filter.bbox.is_some()
+        || filter.area.is_some()
+        || filter.outdated
+        || filter.never_verified;
+
+    if !filtered && args.limit.is_none() && args.updated_since.is_none() && args.sort.is_none() {
         return Ok(Either::Right(
             Redirect::to("https://static.btcmap.org/api/v2/elements.json").permanent(),
         ));
     }
 
-    Ok(Either::Left(Json(match &args.updated_since {
-        Some(updated_since) => repo
-            .select_updated_since(&updated_since, args.limit)
+    let total = repo.select_count(&filter).await?;
+
+    let elements = repo.select_sorted(&filter, sort, limit).await?;
+    let hours_at = hours_at(
+        args.with_hours,
+        args.tz.as_deref(),
+        OffsetDateTime::now_utc(),
+    )?;
+    let items = into_items(elements, args.with_events, hours_at, lang.as_deref(), &repo).await?;
+
+    // Elements become outdated as time goes by, without any change to the table
+    let last_modified = match filter.outdated {
+        true => None,
+        false => repo.select_max_updated_at().await?,
+    };
+    Ok(Either::Left(
+        last_modified::customize(Json(items), last_modified)
+            .insert_header((TOTAL_COUNT_HEADER, total)),
+    ))
+}
+
+// Bulk consumers don't need the whole table at once, so it's read and sent one page at a time
+const NDJSON_PAGE_SIZE: i64 = 1000;
+
+// One element per line, so bulk consumers can process the response as it arrives
+#[get("elements.ndjson")]
+pub async fn get_ndjson(
+    args: Query<GetNdjsonArgs>,
+    repo: Data<ElementRepo>,
+) -> Result<HttpResponse, Error> {
+    let updated_since = args.updated_since;
+    let pages = stream::unfold(Some(0), move |after_id| {
+        let repo = repo.clone();
+        async move {
+            match ndjson_page(&repo, updated_since, after_id?).await {
+                Ok((chunk, next_after_id)) => Some((Ok(chunk), next_after_id)),
+                Err(e) => Some((Err(actix_web::Error::from(e)), None)),
+            }
+        }
+    });
+    Ok(HttpResponse::Ok()
+        .content_type("application/x-ndjson")
+        .streaming(pages))
+}
+
+// Returns the lines of a single page and the id to continue after, if there might be more
+async fn ndjson_page(
+    repo: &ElementRepo,
+    updated_since: Option<OffsetDateTime>,
+    after_id: i64,
+) -> Result<(Bytes, Option<i64>), Error> {
+    let elements = repo
+        .select_page(updated_since, after_id, NDJSON_PAGE_SIZE)
+        .await?;
+    let next_after_id = if elements.len() as i64 == NDJSON_PAGE_SIZE {
+        elements.last().map(|it| it.id)
+    } else {
+        None
+    };
+    let mut chunk = String::new();
+    for element in elements {
+        let item: GetItem = element.into();
+        chunk.push_str(&serde_json::to_string(&item)?);
+        chunk.push('\n');
+    }
+    Ok((Bytes::from(chunk), next_after_id))
+}
+
+#[get("search")]
+pub async fn search(
+    args: Query<SearchArgs>,
+    repo: Data<ElementRepo>,
+) -> Result<Json<Vec<GetItem>>, Error> {
+    if args.q.trim().is_empty() {
+        return Err(Error::HttpBadRequest("Search query can't be empty".into()));
+    }
+    Ok(Json(
+        repo.select_by_search_query(&args.q, Some(resolve_limit(args.limit)))
             .await?
             .into_iter()
             .map(|it| it.into())
             .collect(),
-        None => repo
-            .select_all(args.limit)
+    ))
+}
+
+// Returns elements in the requested order, missing elements are null
+#[get("batch")]
+pub async fn get_batch(
+    args: Query<BatchArgs>,
+    repo: Data<ElementRepo>,
+) -> Result<Json<Vec<Option<GetItem>>>, Error> {
+    let ids = args
+        .ids
+        .split(",")
+        .map(|it| parse_id(it.trim()).map(|(r#type, id)| (r#type.to_string(), id)))
+        .collect::<Result<Vec<_>, _>>()?;
+    if ids.len() > MAX_BATCH_IDS {
+        return Err(Error::HttpBadRequest(format!(
+            "Can't request more than {MAX_BATCH_IDS} ids"
+        )));
+    }
+    Ok(Json(
+        repo.select_by_osm_type_and_ids(ids)
             .await?
             .into_iter()
-            .map(|it| it.into())
+            .map(|it| it.map(|it| it.into()))
+            .collect(),
+    ))
+}
+
+// Element counts bucketed by geohash, precision is the geohash length
+#[get("heatmap")]
+pub async fn get_heatmap(
+    args: Query<HeatmapArgs>,
+    repo: Data<ElementRepo>,
+) -> Result<Json<Vec<HeatmapItem>>, Error> {
+    let precision = args.precision.unwrap_or(DEFAULT_HEATMAP_PRECISION);
+    if !(1..=MAX_HEATMAP_PRECISION).contains(&precision) {
+        return Err(Error::HttpBadRequest(format!(
+            "Precision should be between 1 and {MAX_HEATMAP_PRECISION}"
+        )));
+    }
+    let mut buckets: BTreeMap<String, i64> = BTreeMap::new();
+    for (lat, lon) in repo.select_coords().await? {
+        *buckets
+            .entry(geohash::encode(lat, lon, precision))
+            .or_default() += 1;
+    }
+    Ok(Json(
+        buckets
+            .into_iter()
+            .map(|(geohash, count)| HeatmapItem { geohash, count })
             .collect(),
-    })))
+    ))
+}
+
+// Based on create and delete events, so it's a count of changes rather than a diff of two snapshots
+#[get("stats")]
+pub async fn get_stats(
+    args: Query<StatsArgs>,
+    repo: Data<EventRepo>,
+) -> Result<Json<StatsItem>, Error> {
+    let days = args.days.unwrap_or(DEFAULT_STATS_DAYS);
+    if !(1..=MAX_STATS_DAYS).contains(&days) {
+        return Err(Error::HttpBadRequest(format!(
+            "Days should be between 1 and {MAX_STATS_DAYS}"
+        )));
+    }
+    let counts = repo
+        .select_count_by_type(&(OffsetDateTime::now_utc() - Duration::days(days)))
+        .await?;
+    let added = counts.get("create").copied().unwrap_or_default();
+    let removed = counts.get("delete").copied().unwrap_or_default();
+    Ok(Json(StatsItem {
+        added,
+        removed,
+        net: added - removed,
+    }))
 }
 
 #[get("{id}")]
 pub async fn get_by_osm_type_and_id(
+    req: HttpRequest,
     id: Path<String>,
+    args: Query<GetByIdArgs>,
     repo: Data<ElementRepo>,
 ) -> Result<Json<GetItem>, Error> {
-    let id_parts: Vec<&str> = id.split(":").collect();
-    let r#type = id_parts[0];
-    let id = id_parts[1]
-        .parse::<i64>()
-        .map_err(|_| Error::HttpBadRequest("Invalid ID".into()))?;
-    repo.select_by_osm_type_and_id(r#type, id)
+    let lang = lang(args.lang.as_deref(), &req);
+    let (r#type, osm_id) = parse_id(&id)?;
+    let element = repo
+        .select_by_osm_type_and_id(r#type, osm_id)
+        .await?
+        .ok_or(Error::HttpNotFound(format!(
+            "Element with id {id} doesn't exist"
+        )))?;
+    let hours_at = hours_at(
+        args.with_hours,
+        args.tz.as_deref(),
+        OffsetDateTime::now_utc(),
+    )?;
+    let mut items = into_items(
+        vec![element],
+        args.with_events,
+        hours_at,
+        lang.as_deref(),
+        &repo,
+    )
+    .await?;
+    Ok(Json(items.remove(0)))
+}
+
+#[get("{id}/diff")]
+pub async fn get_diff(
+    id: Path<String>,
+    args: Query<GetDiffArgs>,
+    repo: Data<ElementRepo>,
+) -> Result<Json<GetDiffItem>, Error> {
+    let (r#type, osm_id) = parse_id(&id)?;
+    let element = repo
+        .select_by_osm_type_and_id(r#type, osm_id)
+        .await?
+        .ok_or(Error::HttpNotFound(format!(
+            "Element with id {id} doesn't exist"
+        )))?;
+    let version = osm::get_element_version(r#type, osm_id, args.osm_version)
+        .await?
+        .ok_or(Error::HttpNotFound(format!(
+            "Version {} of element {id} doesn't exist on OSM",
+            args.osm_version,
+        )))?;
+    let empty = HashMap::new();
+    let old_tags = version.tags.as_ref().unwrap_or(&empty);
+    let new_tags = element.overpass_data.tags.as_ref().unwrap_or(&empty);
+    Ok(Json(GetDiffItem {
+        id: element.overpass_data.btcmap_id(),
+        osm_version: args.osm_version,
+        current_osm_version: element.overpass_data.version,
+        added: new_tags
+            .iter()
+            .filter(|(k, _)| !old_tags.contains_key(*k))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect(),
+        removed: old_tags
+            .iter()
+            .filter(|(k, _)| !new_tags.contains_key(*k))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect(),
+        changed: old_tags
+            .iter()
+            .filter_map(|(k, old)| match new_tags.get(k) {
+                Some(new) if new != old => Some((
+                    k.clone(),
+                    TagChange {
+                        old: old.clone(),
+                        new: new.clone(),
+                    },
+                )),
+                _ => None,
+            })
+            .collect(),
+    }))
+}
+
+#[get("{id}/tag-history")]
+pub async fn get_tag_history(
+    id: Path<String>,
+    repo: Data<ElementRepo>,
+) -> Result<Json<Vec<GetTagHistoryItem>>, Error> {
+    let (r#type, osm_id) = parse_id(&id)?;
+    let element = repo
+        .select_by_osm_type_and_id(r#type, osm_id)
+        .await?
+        .ok_or(Error::HttpNotFound(format!(
+            "Element with id {id} doesn't exist"
+        )))?;
+    Ok(Json(
+        repo.select_tag_history(element.id)
+            .await?
+            .into_iter()
+            .map(|it| it.into())
+            .collect(),
+    ))
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct GetAreasItem {
+    pub id: i64,
+    pub url_alias: String,
+    pub name: String,
+}
+
+// Area membership is precomputed during sync and stored in the areas tag
+#[get("{id}/areas")]
+pub async fn get_areas(
+    id: Path<String>,
+    repo: Data<ElementRepo>,
+    area_repo: Data<AreaRepo>,
+) -> Result<Json<Vec<GetAreasItem>>, Error> {
+    let (r#type, osm_id) = parse_id(&id)?;
+    let element = repo
+        .select_by_osm_type_and_id(r#type, osm_id)
         .await?
-        .map(|it| it.into())
         .ok_or(Error::HttpNotFound(format!(
             "Element with id {id} doesn't exist"
-        )))
+        )))?;
+    let area_ids: Vec<i64> = element
+        .tag("areas")
+        .as_array()
+        .map(|it| it.iter().filter_map(|it| it["id"].as_i64()).collect())
+        .unwrap_or_default();
+    let mut areas = area_repo.select_by_ids(area_ids.clone()).await?;
+    areas.sort_by_key(|it| area_ids.iter().position(|id| *id == it.id));
+    let items = areas
+        .into_iter()
+        // Areas deleted after the last sync are still referenced by the tag
+        .filter(|it| it.deleted_at.is_none())
+        .map(|area| {
+            let tag = |name| {
+                area.tags
+                    .get(name)
+                    .and_then(|it| it.as_str())
+                    .unwrap_or_default()
+                    .to_string()
+            };
+            GetAreasItem {
+                id: area.id,
+                url_alias: tag("url_alias"),
+                name: tag("name"),
+            }
+        })
+        .collect();
+    Ok(Json(items))
+}
+
+// Event stats need an extra query and opening hours need parsing, so both are opt-in
+async fn into_items(
+    elements: Vec<Element>,
+    with_events: bool,
+    hours_at: Option<PrimitiveDateTime>,
+    lang: Option<&str>,
+    repo: &ElementRepo,
+) -> Result<Vec<GetItem>, Error> {
+    let stats = if with_events {
+        repo.select_event_stats(elements.iter().map(|it| it.id).collect())
+            .await?
+    } else {
+        HashMap::new()
+    };
+    Ok(elements
+        .into_iter()
+        .map(|it| {
+            let stats = stats.get(&it.id).cloned();
+            let hours =
+                hours_at.and_then(|_| opening_hours::parse(it.overpass_data.tag("opening_hours")));
+            let localized_name = lang
+                .map(|lang| it.overpass_data.tag(&format!("name:{lang}")).to_string())
+                .filter(|it| !it.is_empty());
+            let mut item: GetItem = it.into();
+            if let Some(name) = localized_name {
+                item.display_name = name;
+            }
+            if with_events {
+                item.events_count = Some(stats.map(|it| it.0).unwrap_or(0));
+                item.last_event_at = stats.map(|it| it.1);
+            }
+            if let (Some(at), Some(hours)) = (hours_at, hours) {
+                item.open_now = Some(hours.is_open(at));
+                item.hours_today = Some(hours.hours(at.date()));
+            }
+            item
+        })
+        .collect())
+}
+
+// Localized names are picked from name:{lang} tags, lang param takes priority over the first
+// Accept-Language entry. Unknown or malformed languages are ignored
+fn lang(lang: Option<&str>, req: &HttpRequest) -> Option<String> {
+    let lang = match lang {
+        Some(lang) => lang.to_string(),
+        None => req
+            .headers()
+            .get(ACCEPT_LANGUAGE)
+            .and_then(|it| it.to_str().ok())?
+            .split(',')
+            .next()?
+            .split(';')
+            .next()?
+            .split('-')
+            .next()?
+            .trim()
+            .to_string(),
+    };
+    let lang = lang.to_lowercase();
+    if (2..=3).contains(&lang.len()) && lang.chars().all(|it| it.is_ascii_lowercase()) {
+        Some(lang)
+    } else {
+        None
+    }
+}
+
+fn parse_bbox(bbox: &str) -> Result<(f64, f64, f64, f64), Error> {
+    let invalid = || {
+        Error::HttpBadRequest(format!(
+            "Invalid bbox: {bbox}, expected min_lon,min_lat,max_lon,max_lat"
+        ))
+    };
+    let coords = bbox
+        .split(',')
+        .map(|it| it.trim().parse::<f64>().map_err(|_| invalid()))
+        .collect::<Result<Vec<_>, _>>()?;
+    match coords[..] {
+        [min_lon, min_lat, max_lon, max_lat] if min_lon <= max_lon && min_lat <= max_lat => {
+            Ok((min_lon, min_lat, max_lon, max_lat))
+        }
+        _ => Err(invalid()),
+    }
+}
+
+fn parse_sort(sort: Option<&str>) -> Result<Sort, Error> {
+    match sort {
+        None | Some("updated_at") => Ok(Sort::UpdatedAt),
+        Some("-updated_at") => Ok(Sort::UpdatedAtDesc),
+        Some("name") => Ok(Sort::Name),
+        Some("-name") => Ok(Sort::NameDesc),
+        Some(sort) => Err(Error::HttpBadRequest(format!(
+            "Invalid sort: {sort}, expected one of updated_at, -updated_at, name, -name"
+        ))),
+    }
+}
+
+// Opening hours are evaluated at local time. tz is an IANA name such as Europe/Berlin or a UTC
+// offset such as +02:00, requests without tz use the server default
+fn hours_at(
+    with_hours: bool,
+    tz: Option<&str>,
+    now: OffsetDateTime,
+) -> Result<Option<PrimitiveDateTime>, Error> {
+    if !with_hours {
+        return Ok(None);
+    }
+    let zone = match tz {
+        Some(tz) => Zone::parse(tz).ok_or(Error::HttpBadRequest(format!(
+            "Invalid tz: {tz}, expected time zone such as Europe/Berlin or UTC offset such as +02:00"
+        )))?,
+        None => opening_hours::default_zone(),
+    };
+    Ok(Some(zone.local(now)))
+}
+
+fn parse_id(id: &str) -> Result<(&str, i64), Error> {
+    let (r#type, osm_id) = id
+        .split_once(":")
+        .ok_or(Error::HttpBadRequest("Invalid ID".into()))?;
+    let osm_id = osm_id
+        .parse::<i64>()
+        .map_err(|_| Error::HttpBadRequest("Invalid ID".into()))?;
+    Ok((r#type, osm_id))
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::area::Area;
+    use crate::element::find_areas;
+    use crate::error::{self, ApiError};
+    use crate::event::Event;
+    use crate::osm::osm::OsmElement;
+    use crate::osm::osm::OsmUser;
+    use crate::test::mock_osm_tags;
     use crate::test::mock_state;
+    use crate::user::User;
     use crate::Result;
+    use actix_web::http::header::LAST_MODIFIED;
     use actix_web::test::TestRequest;
-    use actix_web::web::scope;
+    use actix_web::web::{scope, QueryConfig};
     use actix_web::{test, App};
+    use http::StatusCode;
+    use serde_json::json;
+    use time::format_description::well_known::Rfc3339;
+    use time::macros::date;
     use time::macros::datetime;
 
     #[test]
@@ -147,35 +850,52 @@ mod test {
     }
 
     #[test]
-    async fn get_with_limit() -> Result<()> {
+    async fn get_timestamps() -> Result<()> {
         let state = mock_state().await;
-        state.element_repo.insert(&OverpassElement::mock(1)).await?;
-        state.element_repo.insert(&OverpassElement::mock(2)).await?;
-        state.element_repo.insert(&OverpassElement::mock(3)).await?;
+        let element = state.element_repo.insert(&OverpassElement::mock(1)).await?;
+        let id = element.id;
+        state
+            .pool
+            .get()
+            .await?
+            .interact(move |conn| {
+                element.set_deleted_at(Some(datetime!(2024-01-02 00:00 UTC)), conn)
+            })
+            .await??;
+        // Set after deleted_at, since changing deleted_at also bumps updated_at
+        state
+            .element_repo
+            .set_updated_at(id, &datetime!(2024-01-01 12:30:00.123456789 +02:00))
+            .await?;
         let app = test::init_service(
             App::new()
                 .app_data(Data::new(state.element_repo))
                 .service(scope("/").service(super::get)),
         )
         .await;
-        let req = TestRequest::get().uri("/?limit=2").to_request();
-        let res: Vec<GetItem> = test::call_and_read_body_json(&app, req).await;
-        assert_eq!(res.len(), 2);
+        let req = TestRequest::get().uri("/?limit=10").to_request();
+        let res: Value = test::call_and_read_body_json(&app, req).await;
+        for field in ["created_at", "updated_at", "deleted_at"] {
+            let value = res[0][field].as_str().unwrap();
+            assert!(OffsetDateTime::parse(value, &Rfc3339).is_ok());
+        }
+        assert_eq!("2024-01-01T10:30:00.123Z", res[0]["updated_at"]);
+        assert_eq!("2024-01-02T00:00:00.000Z", res[0]["deleted_at"]);
         Ok(())
     }
 
     #[test]
-    async fn get_updated_since() -> Result<()> {
+    async fn get_last_modified() -> Result<()> {
         let state = mock_state().await;
         let element_1 = state.element_repo.insert(&OverpassElement::mock(1)).await?;
+        let element_2 = state.element_repo.insert(&OverpassElement::mock(2)).await?;
         state
             .element_repo
-            .set_updated_at(element_1.id, &datetime!(2022-01-05 00:00 UTC))
+            .set_updated_at(element_1.id, &datetime!(2024-01-01 00:00:00.5 UTC))
             .await?;
-        let element_2 = state.element_repo.insert(&OverpassElement::mock(2)).await?;
         state
             .element_repo
-            .set_updated_at(element_2.id, &datetime!(2022-02-05 00:00 UTC))
+            .set_updated_at(element_2.id, &datetime!(2024-01-02 00:00:00.5 UTC))
             .await?;
         let app = test::init_service(
             App::new()
@@ -183,29 +903,1091 @@ mod test {
                 .service(scope("/").service(super::get)),
         )
         .await;
+        // Based on the whole table, not just on the returned page, and rounded up
+        let req = TestRequest::get().uri("/?limit=1").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(
+            "Tue, 02 Jan 2024 00:00:01 GMT",
+            res.headers().get(LAST_MODIFIED).unwrap()
+        );
+        let req = TestRequest::get().uri("/?outdated=true").to_request();
+        let res = test::call_service(&app, req).await;
+        assert!(res.headers().get(LAST_MODIFIED).is_none());
+        Ok(())
+    }
+
+    #[test]
+    async fn get_with_limit() -> Result<()> {
+        let state = mock_state().await;
+        state.element_repo.insert(&OverpassElement::mock(1)).await?;
+        state.element_repo.insert(&OverpassElement::mock(2)).await?;
+        state.element_repo.insert(&OverpassElement::mock(3)).await?;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.element_repo))
+                .service(scope("/").service(super::get)),
+        )
+        .await;
+        let req = TestRequest::get().uri("/?limit=2").to_request();
+        let res: Vec<GetItem> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(res.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    async fn get_unknown_param() -> Result<()> {
+        let state = mock_state().await;
+        let app = test::init_service(
+            App::new()
+                .app_data(QueryConfig::default().error_handler(error::query_error_handler))
+                .app_data(Data::new(state.element_repo))
+                .service(scope("/").service(super::get)),
+        )
+        .await;
+        let req = TestRequest::get().uri("/?limit=10&limti=10").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
         let req = TestRequest::get()
-            .uri("/?updated_since=2022-01-10T00:00:00Z&limit=100")
+            .uri("/?limit=10&limti=10&strict=true")
             .to_request();
-        let res: Vec<GetItem> = test::call_and_read_body_json(&app, req).await;
-        assert_eq!(res.len(), 1);
+        let res: ApiError = test::try_call_and_read_body_json(&app, req).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST.as_u16(), res.http_code);
+        assert!(res.message.contains("Unknown query parameter: limti"));
         Ok(())
     }
 
     #[test]
-    async fn get_by_osm_type_and_id() -> Result<()> {
+    async fn get_total_count() -> Result<()> {
         let state = mock_state().await;
-        let element = state.element_repo.insert(&OverpassElement::mock(1)).await?;
+        for id in 1..=3 {
+            state
+                .element_repo
+                .insert(&OverpassElement::mock(id))
+                .await?;
+        }
         let app = test::init_service(
             App::new()
                 .app_data(Data::new(state.element_repo))
-                .service(super::get_by_osm_type_and_id),
+                .service(scope("/").service(super::get)),
         )
         .await;
+        let req = TestRequest::get().uri("/?limit=2").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!("3", res.headers().get(TOTAL_COUNT_HEADER).unwrap());
+        let res: Vec<GetItem> = test::read_body_json(res).await;
+        assert_eq!(2, res.len());
         let req = TestRequest::get()
-            .uri(&format!("/{}", element.overpass_data.btcmap_id()))
+            .uri("/?updated_since=2100-01-01T00:00:00Z&limit=2")
             .to_request();
-        let res: GetItem = test::call_and_read_body_json(&app, req).await;
-        assert_eq!(res, element.into());
+        let res = test::call_service(&app, req).await;
+        assert_eq!("0", res.headers().get(TOTAL_COUNT_HEADER).unwrap());
+        Ok(())
+    }
+
+    #[test]
+    async fn get_concurrently() -> Result<()> {
+        let state = mock_state().await;
+        state.element_repo.insert(&OverpassElement::mock(1)).await?;
+        state.element_repo.insert(&OverpassElement::mock(2)).await?;
+        state.element_repo.insert(&OverpassElement::mock(3)).await?;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.element_repo))
+                .service(scope("/").service(super::get)),
+        )
+        .await;
+        let get = || async {
+            let req = TestRequest::get().uri("/?limit=100").to_request();
+            let res: Vec<GetItem> = test::call_and_read_body_json(&app, req).await;
+            res.len()
+        };
+        let res = tokio::join!(get(), get(), get(), get(), get(), get(), get(), get());
+        assert_eq!((3, 3, 3, 3, 3, 3, 3, 3), res);
+        Ok(())
+    }
+
+    #[test]
+    async fn get_by_country() -> Result<()> {
+        let state = mock_state().await;
+        let element = state.element_repo.insert(&OverpassElement::mock(1)).await?;
+        let element = state
+            .element_repo
+            .set_tag(element.id, "country", &"DE".into())
+            .await?;
+        let other = state.element_repo.insert(&OverpassElement::mock(2)).await?;
+        state
+            .element_repo
+            .set_tag(other.id, "country", &"FR".into())
+            .await?;
+        state.element_repo.insert(&OverpassElement::mock(3)).await?;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.element_repo))
+                .service(scope("/").service(super::get)),
+        )
+        .await;
+        let req = TestRequest::get().uri("/?country=de").to_request();
+        let res: Vec<GetItem> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0], element.into());
+        let req = TestRequest::get().uri("/?country=deu").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(400, res.status().as_u16());
+        Ok(())
+    }
+
+    #[test]
+    async fn get_updated_since() -> Result<()> {
+        let state = mock_state().await;
+        let element_1 = state.element_repo.insert(&OverpassElement::mock(1)).await?;
+        state
+            .element_repo
+            .set_updated_at(element_1.id, &datetime!(2022-01-05 00:00 UTC))
+            .await?;
+        let element_2 = state.element_repo.insert(&OverpassElement::mock(2)).await?;
+        state
+            .element_repo
+            .set_updated_at(element_2.id, &datetime!(2022-02-05 00:00 UTC))
+            .await?;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.element_repo))
+                .service(scope("/").service(super::get)),
+        )
+        .await;
+        let req = TestRequest::get()
+            .uri("/?updated_since=2022-01-10T00:00:00Z&limit=100")
+            .to_request();
+        let res: Vec<GetItem> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(res.len(), 1);
+        let req = TestRequest::get()
+            .uri("/?updated_since=2022-01-10&limit=100")
+            .to_request();
+        let res_date: Vec<GetItem> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(res, res_date);
+        let req = TestRequest::get()
+            .uri("/?updated_since=2022-01&limit=100")
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(400, res.status().as_u16());
+        Ok(())
+    }
+
+    #[test]
+    async fn description() {
+        let element = |tags: &[&str]| OverpassElement {
+            tags: Some(mock_osm_tags(tags)),
+            ..OverpassElement::mock(1)
+        };
+        assert_eq!(
+            "a",
+            super::description(&element(&[
+                "description:en",
+                "c",
+                "note",
+                "b",
+                "description",
+                "a",
+            ])),
+        );
+        assert_eq!(
+            "b",
+            super::description(&element(&["description:en", "c", "note", "b"])),
+        );
+        assert_eq!("c", super::description(&element(&["description:en", "c"])));
+        assert_eq!(
+            "b",
+            super::description(&element(&["description", "", "note", "b"])),
+        );
+        assert_eq!("", super::description(&OverpassElement::mock(1)));
+    }
+
+    #[test]
+    async fn display_name() {
+        let element = |tags: &[&str]| OverpassElement {
+            tags: Some(mock_osm_tags(tags)),
+            ..OverpassElement::mock(1)
+        };
+        assert_eq!(
+            "a",
+            super::display_name(
+                &element(&["operator", "c", "brand", "b", "name", "a"]),
+                "cafe",
+            ),
+        );
+        assert_eq!(
+            "b",
+            super::display_name(&element(&["operator", "c", "brand", "b"]), "cafe"),
+        );
+        assert_eq!(
+            "c",
+            super::display_name(&element(&["name", "", "operator", "c"]), "cafe"),
+        );
+        assert_eq!("ATM", super::display_name(&OverpassElement::mock(1), "atm"),);
+        assert_eq!(
+            "Unnamed place",
+            super::display_name(&OverpassElement::mock(1), ""),
+        );
+    }
+
+    #[test]
+    async fn get_is_atm() -> Result<()> {
+        let state = mock_state().await;
+        state
+            .element_repo
+            .insert(&OverpassElement {
+                tags: Some(mock_osm_tags(&["amenity", "atm"])),
+                ..OverpassElement::mock(1)
+            })
+            .await?;
+        state
+            .element_repo
+            .insert(&OverpassElement {
+                tags: Some(mock_osm_tags(&["shop", "books"])),
+                ..OverpassElement::mock(2)
+            })
+            .await?;
+        let element = state.element_repo.insert(&OverpassElement::mock(3)).await?;
+        state
+            .element_repo
+            .set_tag(element.id, "category", &"atm".into())
+            .await?;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.element_repo))
+                .service(super::get_by_osm_type_and_id),
+        )
+        .await;
+        let req = TestRequest::get().uri("/node:1").to_request();
+        let res: GetItem = test::call_and_read_body_json(&app, req).await;
+        assert!(res.is_atm);
+        let req = TestRequest::get().uri("/node:2").to_request();
+        let res: GetItem = test::call_and_read_body_json(&app, req).await;
+        assert!(!res.is_atm);
+        let req = TestRequest::get().uri("/node:3").to_request();
+        let res: GetItem = test::call_and_read_body_json(&app, req).await;
+        assert!(res.is_atm);
+        Ok(())
+    }
+
+    #[test]
+    async fn get_with_hours() -> Result<()> {
+        let state = mock_state().await;
+        state
+            .element_repo
+            .insert(&OverpassElement {
+                tags: Some(mock_osm_tags(&["opening_hours", "24/7"])),
+                ..OverpassElement::mock(1)
+            })
+            .await?;
+        state
+            .element_repo
+            .insert(&OverpassElement {
+                tags: Some(mock_osm_tags(&["opening_hours", "call us"])),
+                ..OverpassElement::mock(2)
+            })
+            .await?;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.element_repo))
+                .service(super::get_by_osm_type_and_id),
+        )
+        .await;
+        let req = TestRequest::get().uri("/node:1").to_request();
+        let res: GetItem = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(None, res.open_now);
+        assert_eq!(None, res.hours_today);
+        let req = TestRequest::get()
+            .uri("/node:1?with_hours=true&tz=Europe/Berlin")
+            .to_request();
+        let res: GetItem = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(Some("00:00-24:00".into()), res.hours_today);
+        assert_eq!(Some(true), res.open_now);
+        let req = TestRequest::get()
+            .uri("/node:2?with_hours=true")
+            .to_request();
+        let res: GetItem = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(None, res.open_now);
+        assert_eq!(None, res.hours_today);
+        let req = TestRequest::get()
+            .uri("/node:1?with_hours=true&tz=Europe/Atlantis")
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(400, res.status().as_u16());
+        Ok(())
+    }
+
+    #[test]
+    async fn hours_at() -> Result<()> {
+        let now = datetime!(2024-07-01 22:30 UTC);
+        assert_eq!(None, super::hours_at(false, None, now)?);
+        assert_eq!(
+            Some(datetime!(2024-07-02 00:30)),
+            super::hours_at(true, Some("Europe/Berlin"), now)?,
+        );
+        assert_eq!(
+            Some(datetime!(2024-07-02 00:30)),
+            super::hours_at(true, Some("+02:00"), now)?,
+        );
+        assert!(super::hours_at(true, Some("Berlin"), now).is_err());
+        Ok(())
+    }
+
+    #[test]
+    async fn search() -> Result<()> {
+        let state = mock_state().await;
+        state
+            .element_repo
+            .insert(&OverpassElement {
+                tags: Some(mock_osm_tags(&[
+                    "name",
+                    "Luigi's",
+                    "amenity",
+                    "restaurant",
+                    "cuisine",
+                    "pizza;italian",
+                ])),
+                ..OverpassElement::mock(1)
+            })
+            .await?;
+        state
+            .element_repo
+            .insert(&OverpassElement {
+                tags: Some(mock_osm_tags(&["name", "Bob's Shoes", "shop", "shoes"])),
+                ..OverpassElement::mock(2)
+            })
+            .await?;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.element_repo))
+                .service(super::search),
+        )
+        .await;
+        let req = TestRequest::get().uri("/search?q=pizza").to_request();
+        let res: Vec<GetItem> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(1, res.len());
+        assert_eq!("node:1", res[0].id);
+        let req = TestRequest::get().uri("/search?q=shoe").to_request();
+        let res: Vec<GetItem> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(1, res.len());
+        assert_eq!("node:2", res[0].id);
+        let req = TestRequest::get().uri("/search?q=%22").to_request();
+        let res: Vec<GetItem> = test::call_and_read_body_json(&app, req).await;
+        assert!(res.is_empty());
+        let req = TestRequest::get().uri("/search?q=%20").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(400, res.status().as_u16());
+        Ok(())
+    }
+
+    #[test]
+    async fn get_by_osm_type_and_id() -> Result<()> {
+        let state = mock_state().await;
+        let element = state.element_repo.insert(&OverpassElement::mock(1)).await?;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.element_repo))
+                .service(super::get_by_osm_type_and_id),
+        )
+        .await;
+        let req = TestRequest::get()
+            .uri(&format!("/{}", element.overpass_data.btcmap_id()))
+            .to_request();
+        let res: GetItem = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(res, element.into());
+        Ok(())
+    }
+
+    #[test]
+    async fn get_by_osm_type_and_id_localized() -> Result<()> {
+        let state = mock_state().await;
+        state
+            .element_repo
+            .insert(&OverpassElement {
+                tags: Some(mock_osm_tags(&["name", "Bakery", "name:de", "Bäckerei"])),
+                ..OverpassElement::mock(1)
+            })
+            .await?;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.element_repo))
+                .service(super::get_by_osm_type_and_id),
+        )
+        .await;
+        let req = TestRequest::get().uri("/node:1?lang=de").to_request();
+        let res: GetItem = test::call_and_read_body_json(&app, req).await;
+        assert_eq!("Bäckerei", res.display_name);
+        assert_eq!("Bakery", res.osm_json.tag("name"));
+        let req = TestRequest::get()
+            .uri("/node:1")
+            .insert_header(("Accept-Language", "de-DE,de;q=0.9,en;q=0.8"))
+            .to_request();
+        let res: GetItem = test::call_and_read_body_json(&app, req).await;
+        assert_eq!("Bäckerei", res.display_name);
+        let req = TestRequest::get().uri("/node:1?lang=es").to_request();
+        let res: GetItem = test::call_and_read_body_json(&app, req).await;
+        assert_eq!("Bakery", res.display_name);
+        Ok(())
+    }
+
+    #[test]
+    async fn get_heatmap() -> Result<()> {
+        let state = mock_state().await;
+        for (id, lat, lon) in [
+            (1, 51.5007, -0.1246),
+            (2, 51.5014, -0.1419),
+            (3, 40.7, -74.0),
+        ] {
+            state
+                .element_repo
+                .insert(&OverpassElement {
+                    lat: Some(lat),
+                    lon: Some(lon),
+                    ..OverpassElement::mock(id)
+                })
+                .await?;
+        }
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.element_repo))
+                .service(super::get_heatmap),
+        )
+        .await;
+        let req = TestRequest::get().uri("/heatmap?precision=3").to_request();
+        let res: Vec<HeatmapItem> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(2, res.len());
+        assert_eq!(
+            Some(2),
+            res.iter().find(|it| it.geohash == "gcp").map(|it| it.count)
+        );
+        let req = TestRequest::get().uri("/heatmap?precision=7").to_request();
+        let res: Vec<HeatmapItem> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(3, res.len());
+        let req = TestRequest::get().uri("/heatmap?precision=9").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(400, res.status().as_u16());
+        Ok(())
+    }
+
+    #[test]
+    async fn get_stats() -> Result<()> {
+        let state = mock_state().await;
+        state
+            .pool
+            .get()
+            .await?
+            .interact(|conn| -> Result<()> {
+                let user = User::insert(1, &OsmUser::mock(), conn)?;
+                let element = Element::insert(&OverpassElement::mock(1), conn)?;
+                for r#type in ["create", "create", "create", "update", "delete"] {
+                    Event::insert(user.id, element.id, r#type, conn)?;
+                }
+                // Outside of the default 30 day window
+                Event::insert(user.id, element.id, "delete", conn)?
+                    .set_created_at(&(OffsetDateTime::now_utc() - Duration::days(40)), conn)?;
+                Ok(())
+            })
+            .await??;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.event_repo))
+                .service(super::get_stats),
+        )
+        .await;
+        let req = TestRequest::get().uri("/stats").to_request();
+        let res: StatsItem = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            StatsItem {
+                added: 3,
+                removed: 1,
+                net: 2,
+            },
+            res,
+        );
+        let req = TestRequest::get().uri("/stats?days=60").to_request();
+        let res: StatsItem = test::call_and_read_body_json(&app, req).await;
+        assert_eq!((3, 2, 1), (res.added, res.removed, res.net));
+        let req = TestRequest::get().uri("/stats?days=0").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(400, res.status().as_u16());
+        Ok(())
+    }
+
+    #[test]
+    async fn get_outdated() -> Result<()> {
+        let state = mock_state().await;
+        let today = OffsetDateTime::now_utc().date();
+        for (id, check_date, country) in [
+            (1, today, "US"),
+            (2, date!(2020 - 01 - 01), "US"),
+            (3, date!(2020 - 01 - 01), "DE"),
+        ] {
+            let element = state
+                .element_repo
+                .insert(&OverpassElement {
+                    tags: Some(mock_osm_tags(&["check_date", &check_date.to_string()])),
+                    ..OverpassElement::mock(id)
+                })
+                .await?;
+            state
+                .element_repo
+                .set_tag(element.id, "country", &country.into())
+                .await?;
+        }
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.element_repo))
+                .service(scope("/").service(super::get)),
+        )
+        .await;
+        let req = TestRequest::get().uri("/?outdated=true").to_request();
+        let res: Vec<GetItem> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            vec!["node:2", "node:3"],
+            res.iter().map(|it| it.id.as_str()).collect::<Vec<_>>(),
+        );
+        let req = TestRequest::get()
+            .uri("/?outdated=true&country=us")
+            .to_request();
+        let res: Vec<GetItem> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(1, res.len());
+        assert_eq!("node:2", res[0].id);
+        let req = TestRequest::get()
+            .uri("/?outdated=true&limit=1")
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!("2", res.headers().get(TOTAL_COUNT_HEADER).unwrap());
+        let res: Vec<GetItem> = test::read_body_json(res).await;
+        assert_eq!(1, res.len());
+        Ok(())
+    }
+
+    #[test]
+    async fn get_outdated_in_bbox_and_area() -> Result<()> {
+        let state = mock_state().await;
+        let today = OffsetDateTime::now_utc().date().to_string();
+        for (id, check_date, coord, area) in [
+            (1, "2020-01-01", 10.0, Some("city")),
+            (2, "2020-01-01", 50.0, None),
+            (3, today.as_str(), 10.0, Some("city")),
+            (4, "soon", 50.0, None),
+        ] {
+            let element = state
+                .element_repo
+                .insert(&OverpassElement {
+                    lat: Some(coord),
+                    lon: Some(coord),
+                    tags: Some(mock_osm_tags(&["check_date", check_date])),
+                    ..OverpassElement::mock(id)
+                })
+                .await?;
+            if let Some(area) = area {
+                state
+                    .element_repo
+                    .set_tag(
+                        element.id,
+                        "areas",
+                        &json!([{ "id": 1, "url_alias": area }]),
+                    )
+                    .await?;
+            }
+        }
+        // Broken OSM dates fall back to the date submitted through the API
+        state
+            .element_repo
+            .set_tag(4, "check_date:currency:XBT", &today.clone().into())
+            .await?;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.element_repo))
+                .service(scope("/").service(super::get)),
+        )
+        .await;
+        let ids = |res: Vec<GetItem>| res.into_iter().map(|it| it.id).collect::<Vec<_>>();
+        let req = TestRequest::get().uri("/?outdated=true").to_request();
+        let res: Vec<GetItem> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(vec!["node:1", "node:2"], ids(res));
+        let req = TestRequest::get()
+            .uri("/?outdated=true&bbox=9,9,11,11")
+            .to_request();
+        let res: Vec<GetItem> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(vec!["node:1"], ids(res));
+        let req = TestRequest::get()
+            .uri("/?outdated=true&area=city")
+            .to_request();
+        let res: Vec<GetItem> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(vec!["node:1"], ids(res));
+        let req = TestRequest::get().uri("/?bbox=9,9,11,11").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!("2", res.headers().get(TOTAL_COUNT_HEADER).unwrap());
+        let req = TestRequest::get().uri("/?bbox=9,9,11").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        Ok(())
+    }
+
+    #[test]
+    async fn get_sorted() -> Result<()> {
+        let state = mock_state().await;
+        for (id, updated_at) in [
+            (1, datetime!(2023-01-02 00:00 UTC)),
+            (2, datetime!(2023-01-03 00:00 UTC)),
+            (3, datetime!(2023-01-01 00:00 UTC)),
+        ] {
+            let element = state
+                .element_repo
+                .insert(&OverpassElement::mock(id))
+                .await?;
+            state
+                .element_repo
+                .set_updated_at(element.id, &updated_at)
+                .await?;
+        }
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.element_repo))
+                .service(scope("/").service(super::get)),
+        )
+        .await;
+        let req = TestRequest::get().uri("/?sort=updated_at").to_request();
+        let res: Vec<GetItem> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            vec!["node:3", "node:1", "node:2"],
+            res.iter().map(|it| it.id.as_str()).collect::<Vec<_>>(),
+        );
+        let req = TestRequest::get().uri("/?sort=-updated_at").to_request();
+        let res: Vec<GetItem> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            vec!["node:2", "node:1", "node:3"],
+            res.iter().map(|it| it.id.as_str()).collect::<Vec<_>>(),
+        );
+        let req = TestRequest::get().uri("/?sort=foo").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(400, res.status().as_u16());
+        Ok(())
+    }
+
+    #[test]
+    async fn get_never_verified() -> Result<()> {
+        let state = mock_state().await;
+        state
+            .element_repo
+            .insert(&OverpassElement {
+                tags: Some(mock_osm_tags(&["survey:date", "2020-01-01"])),
+                ..OverpassElement::mock(1)
+            })
+            .await?;
+        state.element_repo.insert(&OverpassElement::mock(2)).await?;
+        let element = state.element_repo.insert(&OverpassElement::mock(3)).await?;
+        state
+            .element_repo
+            .set_tag(element.id, "check_date:currency:XBT", &"2020-01-01".into())
+            .await?;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.element_repo))
+                .service(scope("/").service(super::get)),
+        )
+        .await;
+        let req = TestRequest::get().uri("/?never_verified=true").to_request();
+        let res: Vec<GetItem> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            vec!["node:2"],
+            res.iter().map(|it| it.id.as_str()).collect::<Vec<_>>(),
+        );
+        Ok(())
+    }
+
+    #[test]
+    async fn get_ndjson() -> Result<()> {
+        let state = mock_state().await;
+        for id in 1..=3 {
+            state
+                .element_repo
+                .insert(&OverpassElement::mock(id))
+                .await?;
+        }
+        state
+            .element_repo
+            .set_updated_at(1, &datetime!(2020-01-01 00:00 UTC))
+            .await?;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.element_repo))
+                .service(super::get_ndjson),
+        )
+        .await;
+        let req = TestRequest::get().uri("/elements.ndjson").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(
+            "application/x-ndjson",
+            res.headers().get("content-type").unwrap()
+        );
+        let body = test::read_body(res).await;
+        let items: Vec<GetItem> = std::str::from_utf8(&body)
+            .unwrap()
+            .lines()
+            .map(|it| serde_json::from_str(it).unwrap())
+            .collect();
+        assert_eq!(
+            vec!["node:1", "node:2", "node:3"],
+            items.iter().map(|it| it.id.as_str()).collect::<Vec<_>>(),
+        );
+        let req = TestRequest::get()
+            .uri("/elements.ndjson?updated_since=2021-01-01")
+            .to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        assert_eq!(2, std::str::from_utf8(&body).unwrap().lines().count());
+        Ok(())
+    }
+
+    #[test]
+    async fn get_with_events() -> Result<()> {
+        let state = mock_state().await;
+        let last_event = state
+            .pool
+            .get()
+            .await?
+            .interact(|conn| -> Result<Event> {
+                let user = User::insert(1, &OsmUser::mock(), conn)?;
+                let element = Element::insert(&OverpassElement::mock(1), conn)?;
+                Element::insert(&OverpassElement::mock(2), conn)?;
+                Event::insert(user.id, element.id, "create", conn)?;
+                Event::insert(user.id, element.id, "update", conn)
+            })
+            .await??;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.element_repo))
+                .service(scope("/elements").service(super::get))
+                .service(super::get_by_osm_type_and_id),
+        )
+        .await;
+        let req = TestRequest::get().uri("/node:1").to_request();
+        let res: GetItem = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(None, res.events_count);
+        assert_eq!(None, res.last_event_at);
+        let req = TestRequest::get()
+            .uri("/node:1?with_events=true")
+            .to_request();
+        let res: GetItem = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(Some(2), res.events_count);
+        assert_eq!(Some(last_event.created_at), res.last_event_at);
+        let req = TestRequest::get()
+            .uri("/elements?limit=10&with_events=true")
+            .to_request();
+        let res: Vec<GetItem> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(2, res.len());
+        assert_eq!(Some(2), res[0].events_count);
+        assert_eq!(Some(0), res[1].events_count);
+        assert_eq!(None, res[1].last_event_at);
+        Ok(())
+    }
+
+    #[test]
+    async fn get_by_osm_type_and_id_with_osm_metadata() -> Result<()> {
+        let state = mock_state().await;
+        let element = state
+            .element_repo
+            .insert(&OverpassElement {
+                version: Some(3),
+                timestamp: Some("2024-05-01T10:00:00Z".into()),
+                user: Some("satoshi".into()),
+                ..OverpassElement::mock(1)
+            })
+            .await?;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.element_repo))
+                .service(super::get_by_osm_type_and_id),
+        )
+        .await;
+        let req = TestRequest::get()
+            .uri(&format!("/{}", element.overpass_data.btcmap_id()))
+            .to_request();
+        let res: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(3, res["osm_version"]);
+        assert_eq!("2024-05-01T10:00:00Z", res["osm_timestamp"]);
+        assert_eq!("satoshi", res["osm_user"]);
+        assert_eq!(3, res["osm_json"]["version"]);
+        Ok(())
+    }
+
+    #[test]
+    async fn get_by_osm_type_and_id_with_osm_urls() -> Result<()> {
+        let state = mock_state().await;
+        state
+            .element_repo
+            .insert(&OverpassElement {
+                r#type: "way".into(),
+                ..OverpassElement::mock(42)
+            })
+            .await?;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.element_repo))
+                .service(super::get_by_osm_type_and_id),
+        )
+        .await;
+        let req = TestRequest::get().uri("/way:42").to_request();
+        let res: GetItem = test::call_and_read_body_json(&app, req).await;
+        assert_eq!("https://www.openstreetmap.org/way/42", res.osm_url);
+        assert_eq!(
+            "https://www.openstreetmap.org/edit?way=42",
+            res.osm_edit_url
+        );
+        Ok(())
+    }
+
+    #[test]
+    async fn get_by_osm_type_and_id_with_changeset_url() -> Result<()> {
+        let state = mock_state().await;
+        state
+            .element_repo
+            .insert(&OverpassElement {
+                changeset: Some(123),
+                ..OverpassElement::mock(1)
+            })
+            .await?;
+        state
+            .element_repo
+            .insert(&OverpassElement {
+                changeset: None,
+                ..OverpassElement::mock(2)
+            })
+            .await?;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.element_repo))
+                .service(super::get_by_osm_type_and_id),
+        )
+        .await;
+        let req = TestRequest::get().uri("/node:1").to_request();
+        let res: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            "https://www.openstreetmap.org/changeset/123",
+            res["osm_changeset_url"],
+        );
+        let req = TestRequest::get().uri("/node:2").to_request();
+        let res: Value = test::call_and_read_body_json(&app, req).await;
+        assert!(res.get("osm_changeset_url").is_none());
+        Ok(())
+    }
+
+    #[test]
+    async fn get_by_osm_type_and_id_not_found() -> Result<()> {
+        let state = mock_state().await;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.element_repo))
+                .service(super::get_by_osm_type_and_id),
+        )
+        .await;
+        let req = TestRequest::get().uri("/node:1").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(404, res.status().as_u16());
+        let res: Value = test::read_body_json(res).await;
+        assert_eq!("not_found", res["error"]);
+        assert_eq!("Element with id node:1 doesn't exist", res["message"]);
+        let req = TestRequest::get().uri("/node").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(400, res.status().as_u16());
+        Ok(())
+    }
+
+    #[test]
+    async fn get_diff() -> Result<()> {
+        let state = mock_state().await;
+        state
+            .element_repo
+            .insert(&OverpassElement {
+                tags: Some(mock_osm_tags(&[
+                    "name",
+                    "bar",
+                    "opening_hours",
+                    "24/7",
+                    "currency:XBT",
+                    "yes",
+                ])),
+                version: Some(3),
+                ..OverpassElement::mock(1001)
+            })
+            .await?;
+        let version: OsmElement = serde_json::from_str(
+            r#"
+            {
+                "type": "node",
+                "id": 1001,
+                "lat": 0.0,
+                "lon": 0.0,
+                "version": 1,
+                "user": "satoshi",
+                "uid": 1,
+                "tags": {
+                    "name": "foo",
+                    "phone": "123",
+                    "currency:XBT": "yes"
+                }
+            }
+            "#,
+        )?;
+        osm::cache_element_version(("node".into(), 1001, 1), version);
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.element_repo))
+                .service(super::get_diff),
+        )
+        .await;
+        let req = TestRequest::get()
+            .uri("/node:1001/diff?osm_version=1")
+            .to_request();
+        let res: GetDiffItem = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            GetDiffItem {
+                id: "node:1001".into(),
+                osm_version: 1,
+                current_osm_version: Some(3),
+                added: BTreeMap::from([("opening_hours".into(), "24/7".into())]),
+                removed: BTreeMap::from([("phone".into(), "123".into())]),
+                changed: BTreeMap::from([(
+                    "name".into(),
+                    TagChange {
+                        old: "foo".into(),
+                        new: "bar".into(),
+                    },
+                )]),
+            },
+            res,
+        );
+        Ok(())
+    }
+
+    #[test]
+    async fn get_areas() -> Result<()> {
+        let state = mock_state().await;
+        // Element is at 0,0, the city is within the country
+        for (url_alias, min, max) in [
+            ("country", -10.0, 10.0),
+            ("city", -1.0, 1.0),
+            ("elsewhere", 20.0, 30.0),
+        ] {
+            let mut tags = serde_json::Map::new();
+            tags.insert("url_alias".into(), url_alias.into());
+            tags.insert("name".into(), url_alias.to_uppercase().into());
+            tags.insert(
+                "geo_json".into(),
+                json!({
+                    "type": "Polygon",
+                    "coordinates": [[[min, min], [max, min], [max, max], [min, max], [min, min]]],
+                }),
+            );
+            state.area_repo.insert(&tags).await?;
+        }
+        let element = state.element_repo.insert(&OverpassElement::mock(1)).await?;
+        state
+            .pool
+            .get()
+            .await?
+            .interact(move |conn| {
+                let areas = Area::select_all(None, conn)?;
+                find_areas::find_and_save(&element, &areas, conn)
+            })
+            .await??;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.element_repo))
+                .app_data(Data::new(state.area_repo))
+                .service(super::get_areas),
+        )
+        .await;
+        let req = TestRequest::get().uri("/node:1/areas").to_request();
+        let res: Vec<GetAreasItem> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            vec![("country", "COUNTRY"), ("city", "CITY")],
+            res.iter()
+                .map(|it| (it.url_alias.as_str(), it.name.as_str()))
+                .collect::<Vec<_>>(),
+        );
+        let req = TestRequest::get().uri("/node:2/areas").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(404, res.status().as_u16());
+        Ok(())
+    }
+
+    #[test]
+    async fn get_tag_history() -> Result<()> {
+        let state = mock_state().await;
+        let element = state.element_repo.insert(&OverpassElement::mock(1)).await?;
+        let mut tags = serde_json::Map::new();
+        tags.insert("icon:android".into(), "cafe".into());
+        state
+            .element_repo
+            .patch_tags(element.id, &tags, "foo", None)
+            .await?;
+        tags.insert("icon:android".into(), "restaurant".into());
+        state
+            .element_repo
+            .patch_tags(element.id, &tags, "bar", None)
+            .await?;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.element_repo))
+                .service(super::get_tag_history),
+        )
+        .await;
+        let req = TestRequest::get().uri("/node:1/tag-history").to_request();
+        let res: Vec<GetTagHistoryItem> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(2, res.len());
+        assert_eq!(
+            (Value::Null, Value::from("cafe"), "foo"),
+            (
+                res[0].old.clone(),
+                res[0].new.clone(),
+                res[0].changed_by.as_str()
+            ),
+        );
+        assert_eq!(
+            (Value::from("cafe"), Value::from("restaurant"), "bar"),
+            (
+                res[1].old.clone(),
+                res[1].new.clone(),
+                res[1].changed_by.as_str()
+            ),
+        );
+        let req = TestRequest::get().uri("/node:2/tag-history").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(404, res.status().as_u16());
+        Ok(())
+    }
+
+    #[test]
+    async fn get_batch() -> Result<()> {
+        let state = mock_state().await;
+        state.element_repo.insert(&OverpassElement::mock(1)).await?;
+        state.element_repo.insert(&OverpassElement::mock(2)).await?;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.element_repo))
+                .service(super::get_batch),
+        )
+        .await;
+        let req = TestRequest::get()
+            .uri("/batch?ids=node:2,node:3,node:1")
+            .to_request();
+        let res: Vec<Option<GetItem>> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(3, res.len());
+        assert_eq!("node:2", res[0].as_ref().unwrap().id);
+        assert!(res[1].is_none());
+        assert_eq!("node:1", res[2].as_ref().unwrap().id);
+        let req = TestRequest::get().uri("/batch?ids=node:1,foo").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(400, res.status().as_u16());
+        let ids = vec!["node:1"; super::MAX_BATCH_IDS + 1].join(",");
+        let req = TestRequest::get()
+            .uri(&format!("/batch?ids={ids}"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(400, res.status().as_u16());
         Ok(())
     }
 }
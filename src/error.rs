@@ -1,5 +1,7 @@
 use actix_web::{
-    error::QueryPayloadError, http::StatusCode, HttpRequest, HttpResponse, ResponseError,
+    error::{JsonPayloadError, QueryPayloadError},
+    http::StatusCode,
+    HttpRequest, HttpResponse, ResponseError,
 };
 use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
@@ -12,6 +14,7 @@ pub enum Error {
     Reqwest(reqwest::Error),
     SerdeJson(serde_json::Error),
     TimeFormat(time::error::Format),
+    TimeParse(time::error::Parse),
     OsmApi(String),
     OverpassApi(String),
     DeadpoolPool(deadpool_sqlite::PoolError),
@@ -22,6 +25,7 @@ pub enum Error {
     HttpUnauthorized(String),
     HttpNotFound(String),
     HttpConflict(String),
+    HttpUnprocessableEntity(String),
 }
 
 impl Display for Error {
@@ -33,6 +37,7 @@ impl Display for Error {
             Error::Reqwest(err) => err.fmt(f),
             Error::SerdeJson(err) => err.fmt(f),
             Error::TimeFormat(err) => err.fmt(f),
+            Error::TimeParse(err) => err.fmt(f),
             Error::OsmApi(err) => err.fmt(f),
             Error::OverpassApi(err) => err.fmt(f),
             Error::DeadpoolPool(err) => err.fmt(f),
@@ -43,6 +48,7 @@ impl Display for Error {
             Error::HttpNotFound(err) => write!(f, "{}", err),
             Error::HttpConflict(err) => write!(f, "{}", err),
             Error::HttpUnauthorized(err) => write!(f, "{}", err),
+            Error::HttpUnprocessableEntity(err) => write!(f, "{}", err),
         }
     }
 }
@@ -83,6 +89,12 @@ impl From<time::error::Format> for Error {
     }
 }
 
+impl From<time::error::Parse> for Error {
+    fn from(error: time::error::Parse) -> Self {
+        Error::TimeParse(error)
+    }
+}
+
 impl From<deadpool_sqlite::PoolError> for Error {
     fn from(error: deadpool_sqlite::PoolError) -> Self {
         Error::DeadpoolPool(error)
@@ -111,18 +123,73 @@ pub fn query_error_handler(err: QueryPayloadError, _req: &HttpRequest) -> actix_
     Error::HttpBadRequest(format!("Invalid arguments: {err}")).into()
 }
 
-#[derive(Serialize, Deserialize)]
+pub fn json_error_handler(err: JsonPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    match err {
+        JsonPayloadError::OverflowKnownLength { .. } | JsonPayloadError::Overflow { .. } => {
+            ApiError::new(StatusCode::PAYLOAD_TOO_LARGE, &err.to_string()).into()
+        }
+        _ => Error::HttpBadRequest(format!("Invalid JSON: {err}")).into(),
+    }
+}
+
+// Unknown routes should have the same error shape as the rest of the API
+pub async fn not_found_handler(req: HttpRequest) -> Result<HttpResponse, Error> {
+    Err(Error::HttpNotFound(format!(
+        "There is no route for {} {}",
+        req.method(),
+        req.path(),
+    )))
+}
+
+#[derive(Serialize, Deserialize, Debug)]
 pub struct ApiError {
-    pub http_code: u16,
+    pub code: u16,
+    pub error: String,
     pub message: String,
+    // Same as code, kept for older clients
+    pub http_code: u16,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, message: &str) -> ApiError {
+        let error = match status {
+            StatusCode::BAD_REQUEST => "bad_request",
+            StatusCode::UNAUTHORIZED => "unauthorized",
+            StatusCode::NOT_FOUND => "not_found",
+            StatusCode::CONFLICT => "conflict",
+            StatusCode::PAYLOAD_TOO_LARGE => "payload_too_large",
+            StatusCode::UNPROCESSABLE_ENTITY => "unprocessable_entity",
+            StatusCode::TOO_MANY_REQUESTS => "too_many_requests",
+            _ => "internal_error",
+        };
+        ApiError {
+            code: status.as_u16(),
+            error: error.into(),
+            message: message.into(),
+            http_code: status.as_u16(),
+        }
+    }
+}
+
+impl Display for ApiError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.error, self.message)
+    }
+}
+
+impl ResponseError for ApiError {
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(self)
+    }
+
+    fn status_code(&self) -> StatusCode {
+        StatusCode::from_u16(self.code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
 }
 
 impl ResponseError for Error {
     fn error_response(&self) -> HttpResponse {
-        HttpResponse::build(self.status_code()).json(ApiError {
-            http_code: self.status_code().as_u16(),
-            message: self.to_string(),
-        })
+        ApiError::new(self.status_code(), &self.to_string()).error_response()
     }
 
     fn status_code(&self) -> StatusCode {
@@ -131,7 +198,59 @@ impl ResponseError for Error {
             Error::HttpUnauthorized(_) => StatusCode::UNAUTHORIZED,
             Error::HttpNotFound(_) => StatusCode::NOT_FOUND,
             Error::HttpConflict(_) => StatusCode::CONFLICT,
+            Error::HttpUnprocessableEntity(_) => StatusCode::UNPROCESSABLE_ENTITY,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{ApiError, Error};
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+    use actix_web::{get, test, web, App};
+    use serde_json::Value;
+
+    #[get("/")]
+    async fn not_found() -> Result<&'static str, Error> {
+        Err(Error::HttpNotFound("Nothing here".into()))
+    }
+
+    #[test]
+    async fn not_found_body() {
+        let app = test::init_service(App::new().service(not_found)).await;
+        let req = TestRequest::get().uri("/").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+        let res: Value = test::read_body_json(res).await;
+        assert_eq!(404, res["code"]);
+        assert_eq!("not_found", res["error"]);
+        assert_eq!("Nothing here", res["message"]);
+    }
+
+    #[test]
+    async fn not_found_handler() {
+        let app = test::init_service(
+            App::new()
+                .service(not_found)
+                .default_service(web::to(super::not_found_handler)),
+        )
+        .await;
+        let req = TestRequest::get().uri("/bogus").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+        let res: ApiError = test::read_body_json(res).await;
+        assert_eq!(404, res.code);
+        assert_eq!("not_found", res.error);
+        assert_eq!("There is no route for GET /bogus", res.message);
+    }
+
+    #[test]
+    async fn new() {
+        let error = ApiError::new(StatusCode::UNAUTHORIZED, "Invalid token");
+        assert_eq!(401, error.code);
+        assert_eq!("unauthorized", error.error);
+        assert_eq!(error.code, error.http_code);
+    }
+}
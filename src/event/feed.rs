@@ -0,0 +1,160 @@
+use super::Event;
+use crate::event::model::EventRepo;
+use crate::Error;
+use actix_web::get;
+use actix_web::web::Data;
+use actix_web::web::Query;
+use actix_web::HttpResponse;
+use quick_xml::escape::escape;
+use serde::Deserialize;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+const FEED_ENTRIES: i64 = 100;
+const EVENT_TYPES: [&str; 5] = ["create", "update", "delete", "restore", "verify"];
+
+#[derive(Deserialize)]
+pub struct GetArgs {
+    r#type: Option<String>,
+}
+
+#[get("events.atom")]
+pub async fn get_events(
+    args: Query<GetArgs>,
+    repo: Data<EventRepo>,
+) -> Result<HttpResponse, Error> {
+    if let Some(r#type) = &args.r#type {
+        if !EVENT_TYPES.contains(&r#type.as_str()) {
+            return Err(Error::HttpBadRequest(format!(
+                "Unknown event type: {}, supported types: {}",
+                r#type,
+                EVENT_TYPES.join(", "),
+            )));
+        }
+    }
+    let events = repo
+        .select_latest(args.r#type.clone(), FEED_ENTRIES)
+        .await?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/atom+xml; charset=utf-8")
+        .body(events_to_atom(&events)?))
+}
+
+fn events_to_atom(events: &[Event]) -> Result<String, Error> {
+    let updated = events
+        .first()
+        .map(|it| it.created_at)
+        .unwrap_or(OffsetDateTime::now_utc())
+        .format(&Rfc3339)?;
+    let mut entries = String::new();
+    for event in events {
+        let element_id = format!("{}:{}", event.element_osm_type, event.element_osm_id);
+        let url = format!(
+            "https://www.openstreetmap.org/{}/{}",
+            event.element_osm_type, event.element_osm_id,
+        );
+        let title = match event.r#type.as_str() {
+            "create" => format!("Added {element_id}"),
+            "update" => format!("Updated {element_id}"),
+            "delete" => format!("Removed {element_id}"),
+            "restore" => format!("Restored {element_id}"),
+            "verify" => format!("Verified {element_id}"),
+            _ => format!("Changed {element_id}"),
+        };
+        entries.push_str(&format!(
+            r#"
+    <entry>
+        <id>https://api.btcmap.org/v2/events/{id}</id>
+        <title>{title}</title>
+        <link href="{url}"/>
+        <updated>{updated}</updated>
+    </entry>"#,
+            id = event.id,
+            title = escape(&title),
+            url = escape(&url),
+            updated = event.created_at.format(&Rfc3339)?,
+        ));
+    }
+    Ok(format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+    <id>https://api.btcmap.org/feeds/events.atom</id>
+    <title>BTC Map Events</title>
+    <link href="https://btcmap.org"/>
+    <author>
+        <name>BTC Map</name>
+    </author>
+    <updated>{updated}</updated>{entries}
+</feed>
+"#
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::osm::osm::OsmUser;
+    use crate::osm::overpass::OverpassElement;
+    use crate::test::mock_state;
+    use crate::Result;
+    use actix_web::test::TestRequest;
+    use actix_web::web::Data;
+    use actix_web::{test, App};
+    use quick_xml::events::Event;
+    use quick_xml::Reader;
+
+    fn count_entries(xml: &str) -> usize {
+        let mut reader = Reader::from_str(xml);
+        let mut entries = 0;
+        loop {
+            match reader.read_event().unwrap() {
+                Event::Start(it) if it.name().as_ref() == b"entry" => entries += 1,
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+        entries
+    }
+
+    #[test]
+    async fn get_events() -> Result<()> {
+        let state = mock_state().await;
+        state.user_repo.insert(1, &OsmUser::mock()).await?;
+        state.element_repo.insert(&OverpassElement::mock(1)).await?;
+        state.event_repo.insert(1, 1, "create").await?;
+        state.event_repo.insert(1, 1, "update").await?;
+        state.event_repo.insert(1, 1, "delete").await?;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.event_repo))
+                .service(super::get_events),
+        )
+        .await;
+        let req = TestRequest::get().uri("/events.atom").to_request();
+        let res = test::call_and_read_body(&app, req).await;
+        let xml = std::str::from_utf8(&res).unwrap();
+        assert_eq!(3, count_entries(xml));
+        // Required by RFC 4287 when entries have no author of their own
+        assert!(xml.contains("<author>\n        <name>BTC Map</name>\n    </author>"));
+        let req = TestRequest::get()
+            .uri("/events.atom?type=create")
+            .to_request();
+        let res = test::call_and_read_body(&app, req).await;
+        assert_eq!(1, count_entries(std::str::from_utf8(&res).unwrap()));
+        Ok(())
+    }
+
+    #[test]
+    async fn get_events_unknown_type() -> Result<()> {
+        let state = mock_state().await;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.event_repo))
+                .service(super::get_events),
+        )
+        .await;
+        let req = TestRequest::get().uri("/events.atom?type=foo").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(400, res.status().as_u16());
+        Ok(())
+    }
+}
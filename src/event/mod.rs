@@ -1,3 +1,4 @@
+pub mod feed;
 pub mod model;
 pub mod v2;
 pub use model::Event;
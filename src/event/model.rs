@@ -14,6 +14,7 @@ use tracing::debug;
 
 pub struct EventRepo {
     pool: Arc<Pool>,
+    read_pool: Arc<Pool>,
 }
 
 #[derive(PartialEq, Debug)]
@@ -32,7 +33,17 @@ pub struct Event {
 
 impl EventRepo {
     pub fn new(pool: &Arc<Pool>) -> Self {
-        Self { pool: pool.clone() }
+        Self {
+            pool: pool.clone(),
+            read_pool: pool.clone(),
+        }
+    }
+
+    pub fn with_read_pool(self, read_pool: &Arc<Pool>) -> Self {
+        Self {
+            read_pool: read_pool.clone(),
+            ..self
+        }
     }
 
     #[cfg(test)]
@@ -47,7 +58,7 @@ impl EventRepo {
 
     #[cfg(test)]
     pub async fn _select_all(&self, limit: Option<i64>) -> Result<Vec<Event>> {
-        self.pool
+        self.read_pool
             .get()
             .await?
             .interact(move |conn| Event::select_all(limit, conn))
@@ -60,15 +71,74 @@ impl EventRepo {
         limit: Option<i64>,
     ) -> Result<Vec<Event>> {
         let updated_since = updated_since.clone();
-        self.pool
+        self.read_pool
             .get()
             .await?
             .interact(move |conn| Event::select_updated_since(&updated_since, limit, conn))
             .await?
     }
 
+    pub async fn select_count(&self, updated_since: Option<OffsetDateTime>) -> Result<i64> {
+        self.read_pool
+            .get()
+            .await?
+            .interact(move |conn| Event::select_count(updated_since.as_ref(), conn))
+            .await?
+    }
+
+    pub async fn select_max_updated_at(&self) -> Result<Option<OffsetDateTime>> {
+        self.read_pool
+            .get()
+            .await?
+            .interact(|conn| Event::select_max_updated_at(conn))
+            .await?
+    }
+
+    pub async fn select_count_by_type(
+        &self,
+        created_since: &OffsetDateTime,
+    ) -> Result<HashMap<String, i64>> {
+        let created_since = created_since.clone();
+        self.read_pool
+            .get()
+            .await?
+            .interact(move |conn| Event::select_count_by_type(&created_since, conn))
+            .await?
+    }
+
+    pub async fn select_latest(&self, r#type: Option<String>, limit: i64) -> Result<Vec<Event>> {
+        self.read_pool
+            .get()
+            .await?
+            .interact(move |conn| Event::select_latest(r#type.as_deref(), limit, conn))
+            .await?
+    }
+
+    pub async fn select_by_area_id(&self, area_id: i64, limit: i64) -> Result<Vec<Event>> {
+        self.read_pool
+            .get()
+            .await?
+            .interact(move |conn| Event::select_by_area_id(area_id, limit, conn))
+            .await?
+    }
+
+    pub async fn select_by_area_id_and_period(
+        &self,
+        area_id: i64,
+        from: &OffsetDateTime,
+        to: &OffsetDateTime,
+    ) -> Result<Vec<Event>> {
+        let from = from.clone();
+        let to = to.clone();
+        self.read_pool
+            .get()
+            .await?
+            .interact(move |conn| Event::select_by_area_id_and_period(area_id, &from, &to, conn))
+            .await?
+    }
+
     pub async fn select_by_id(&self, id: i64) -> Result<Option<Event>> {
-        self.pool
+        self.read_pool
             .get()
             .await?
             .interact(move |conn| Event::select_by_id(id, conn))
@@ -135,7 +205,6 @@ impl Event {
             .ok_or(Error::Rusqlite(rusqlite::Error::QueryReturnedNoRows))?)
     }
 
-    #[cfg(test)]
     pub fn select_all(limit: Option<i64>, conn: &Connection) -> Result<Vec<Event>> {
         let query = format!(
             r#"
@@ -204,6 +273,162 @@ impl Event {
             .collect::<Result<Vec<_>, _>>()?)
     }
 
+    // Newest change in the whole table, deleted rows included
+    pub fn select_max_updated_at(conn: &Connection) -> Result<Option<OffsetDateTime>> {
+        let query = format!(
+            r#"
+                SELECT max({COL_UPDATED_AT})
+                FROM {TABLE}
+            "#
+        );
+        debug!(query);
+        Ok(conn.query_row(&query, [], |row| row.get(0))?)
+    }
+
+    pub fn select_count(updated_since: Option<&OffsetDateTime>, conn: &Connection) -> Result<i64> {
+        let query = format!(
+            r#"
+                SELECT count(*)
+                FROM {TABLE}
+                WHERE {COL_UPDATED_AT} > :updated_since
+            "#
+        );
+        let updated_since = match updated_since {
+            Some(updated_since) => updated_since.format(&Rfc3339)?,
+            None => "".into(),
+        };
+        debug!(query);
+        Ok(conn.query_row(
+            &query,
+            named_params! { ":updated_since": updated_since },
+            |row| row.get(0),
+        )?)
+    }
+
+    pub fn select_latest(
+        r#type: Option<&str>,
+        limit: i64,
+        conn: &Connection,
+    ) -> Result<Vec<Event>> {
+        let query = format!(
+            r#"
+                SELECT
+                    ev.{COL_ROWID},
+                    ev.{COL_USER_ID},
+                    ev.{COL_ELEMENT_ID},
+                    json_extract(el.overpass_data, '$.type'),
+                    json_extract(el.overpass_data, '$.id'),
+                    ev.{COL_TYPE},
+                    ev.{COL_TAGS},
+                    ev.{COL_CREATED_AT},
+                    ev.{COL_UPDATED_AT},
+                    ev.{COL_DELETED_AT}
+                FROM {TABLE} ev
+                LEFT JOIN element el on el.rowid = ev.{COL_ELEMENT_ID}
+                WHERE ev.{COL_DELETED_AT} IS NULL AND (:type IS NULL OR ev.{COL_TYPE} = :type)
+                ORDER BY ev.{COL_CREATED_AT} DESC, ev.{COL_ROWID} DESC
+                LIMIT :limit
+            "#
+        );
+        debug!(query);
+        Ok(conn
+            .prepare(&query)?
+            .query_map(
+                named_params! {
+                    ":type": r#type,
+                    ":limit": limit,
+                },
+                mapper(),
+            )?
+            .collect::<Result<Vec<_>, _>>()?)
+    }
+
+    // Relies on the element areas tag, which is kept in sync with element coordinates
+    pub fn select_by_area_id(area_id: i64, limit: i64, conn: &Connection) -> Result<Vec<Event>> {
+        let query = format!(
+            r#"
+                SELECT
+                    ev.{COL_ROWID},
+                    ev.{COL_USER_ID},
+                    ev.{COL_ELEMENT_ID},
+                    json_extract(el.overpass_data, '$.type'),
+                    json_extract(el.overpass_data, '$.id'),
+                    ev.{COL_TYPE},
+                    ev.{COL_TAGS},
+                    ev.{COL_CREATED_AT},
+                    ev.{COL_UPDATED_AT},
+                    ev.{COL_DELETED_AT}
+                FROM {TABLE} ev
+                JOIN element el on el.rowid = ev.{COL_ELEMENT_ID}
+                WHERE ev.{COL_DELETED_AT} IS NULL AND EXISTS (
+                    SELECT 1
+                    FROM json_each(el.tags, '$.areas')
+                    WHERE json_extract(value, '$.id') = :area_id
+                )
+                ORDER BY ev.{COL_CREATED_AT} DESC, ev.{COL_ROWID} DESC
+                LIMIT :limit
+            "#
+        );
+        debug!(query);
+        Ok(conn
+            .prepare(&query)?
+            .query_map(
+                named_params! {
+                    ":area_id": area_id,
+                    ":limit": limit,
+                },
+                mapper(),
+            )?
+            .collect::<Result<Vec<_>, _>>()?)
+    }
+
+    // The period includes from and excludes to
+    pub fn select_by_area_id_and_period(
+        area_id: i64,
+        from: &OffsetDateTime,
+        to: &OffsetDateTime,
+        conn: &Connection,
+    ) -> Result<Vec<Event>> {
+        let query = format!(
+            r#"
+                SELECT
+                    ev.{COL_ROWID},
+                    ev.{COL_USER_ID},
+                    ev.{COL_ELEMENT_ID},
+                    json_extract(el.overpass_data, '$.type'),
+                    json_extract(el.overpass_data, '$.id'),
+                    ev.{COL_TYPE},
+                    ev.{COL_TAGS},
+                    ev.{COL_CREATED_AT},
+                    ev.{COL_UPDATED_AT},
+                    ev.{COL_DELETED_AT}
+                FROM {TABLE} ev
+                JOIN element el on el.rowid = ev.{COL_ELEMENT_ID}
+                WHERE ev.{COL_DELETED_AT} IS NULL
+                AND ev.{COL_CREATED_AT} >= :from
+                AND ev.{COL_CREATED_AT} < :to
+                AND EXISTS (
+                    SELECT 1
+                    FROM json_each(el.tags, '$.areas')
+                    WHERE json_extract(value, '$.id') = :area_id
+                )
+                ORDER BY ev.{COL_CREATED_AT}, ev.{COL_ROWID}
+            "#
+        );
+        debug!(query);
+        Ok(conn
+            .prepare(&query)?
+            .query_map(
+                named_params! {
+                    ":area_id": area_id,
+                    ":from": from.format(&Rfc3339)?,
+                    ":to": to.format(&Rfc3339)?,
+                },
+                mapper(),
+            )?
+            .collect::<Result<Vec<_>, _>>()?)
+    }
+
     pub fn select_by_id(id: i64, conn: &Connection) -> Result<Option<Event>> {
         let query = format!(
             r#"
@@ -229,7 +454,88 @@ impl Event {
             .optional()?)
     }
 
-    #[cfg(test)]
+    pub fn select_latest_by_element_id(
+        element_id: i64,
+        conn: &Connection,
+    ) -> Result<Option<Event>> {
+        let query = format!(
+            r#"
+                SELECT
+                    ev.{COL_ROWID},
+                    ev.{COL_USER_ID},
+                    ev.{COL_ELEMENT_ID},
+                    json_extract(el.overpass_data, '$.type'),
+                    json_extract(el.overpass_data, '$.id'),
+                    ev.{COL_TYPE},
+                    ev.{COL_TAGS},
+                    ev.{COL_CREATED_AT},
+                    ev.{COL_UPDATED_AT},
+                    ev.{COL_DELETED_AT}
+                FROM {TABLE} ev
+                LEFT JOIN element el on el.rowid = ev.{COL_ELEMENT_ID}
+                WHERE ev.{COL_ELEMENT_ID} = :element_id
+                ORDER BY ev.{COL_ROWID} DESC
+                LIMIT 1
+            "#
+        );
+        debug!(query);
+        Ok(conn
+            .query_row(
+                &query,
+                named_params! { ":element_id": element_id },
+                mapper(),
+            )
+            .optional()?)
+    }
+
+    // Returns event count and last event time, elements without events are left out
+    pub fn select_stats_by_element_ids(
+        element_ids: &[i64],
+        conn: &Connection,
+    ) -> Result<HashMap<i64, (i64, OffsetDateTime)>> {
+        let query = format!(
+            r#"
+                SELECT {COL_ELEMENT_ID}, count(*), max({COL_CREATED_AT})
+                FROM {TABLE}
+                WHERE {COL_ELEMENT_ID} IN (SELECT value FROM json_each(:element_ids))
+                AND {COL_DELETED_AT} IS NULL
+                GROUP BY {COL_ELEMENT_ID}
+            "#
+        );
+        debug!(query);
+        Ok(conn
+            .prepare(&query)?
+            .query_map(
+                named_params! { ":element_ids": serde_json::to_string(element_ids)? },
+                |row| Ok((row.get(0)?, (row.get(1)?, row.get(2)?))),
+            )?
+            .collect::<Result<HashMap<_, _>, _>>()?)
+    }
+
+    // Number of events of each type created after a given date
+    pub fn select_count_by_type(
+        created_since: &OffsetDateTime,
+        conn: &Connection,
+    ) -> Result<HashMap<String, i64>> {
+        let query = format!(
+            r#"
+                SELECT {COL_TYPE}, count(*)
+                FROM {TABLE}
+                WHERE {COL_CREATED_AT} > :created_since
+                AND {COL_DELETED_AT} IS NULL
+                GROUP BY {COL_TYPE}
+            "#
+        );
+        debug!(query);
+        Ok(conn
+            .prepare(&query)?
+            .query_map(
+                named_params! { ":created_since": created_since.format(&Rfc3339)? },
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )?
+            .collect::<Result<HashMap<_, _>, _>>()?)
+    }
+
     pub fn patch_tags(&self, tags: &HashMap<String, Value>, conn: &Connection) -> Result<Event> {
         Event::_patch_tags(self.id, tags, conn)
     }
@@ -284,6 +590,27 @@ impl Event {
             .ok_or(Error::Rusqlite(rusqlite::Error::QueryReturnedNoRows))?)
     }
 
+    #[cfg(test)]
+    pub fn set_created_at(&self, created_at: &OffsetDateTime, conn: &Connection) -> Result<Event> {
+        let query = format!(
+            r#"
+                UPDATE {TABLE}
+                SET {COL_CREATED_AT} = :created_at
+                WHERE {COL_ROWID} = :id
+            "#
+        );
+        debug!(query);
+        conn.execute(
+            &query,
+            named_params! {
+                ":id": self.id,
+                ":created_at": created_at.format(&Rfc3339)?,
+            },
+        )?;
+        Ok(Event::select_by_id(self.id, &conn)?
+            .ok_or(Error::Rusqlite(rusqlite::Error::QueryReturnedNoRows))?)
+    }
+
     #[cfg(test)]
     pub fn tag(&self, name: &str) -> &Value {
         self.tags.get(name).unwrap_or(&Value::Null)
@@ -1,29 +1,39 @@
 use super::Event;
 use crate::event::model::EventRepo;
+use crate::server::last_modified;
+use crate::server::limit::resolve_sync_limit;
+use crate::server::strict;
+use crate::server::timestamp;
+use crate::server::TOTAL_COUNT_HEADER;
 use crate::Error;
 use actix_web::get;
+use actix_web::route;
 use actix_web::web::Data;
 use actix_web::web::Json;
 use actix_web::web::Path;
 use actix_web::web::Query;
 use actix_web::web::Redirect;
+use actix_web::CustomizeResponder;
 use actix_web::Either;
+use actix_web::HttpRequest;
+use actix_web::Responder;
 use serde::Deserialize;
 use serde::Serialize;
 use serde_json::Value;
 use std::collections::HashMap;
-use time::format_description::well_known::Rfc3339;
 use time::Duration;
 use time::OffsetDateTime;
 
 #[derive(Deserialize)]
 pub struct GetArgs {
     #[serde(default)]
-    #[serde(with = "time::serde::rfc3339::option")]
+    #[serde(with = "crate::server::updated_since::option")]
     updated_since: Option<OffsetDateTime>,
     limit: Option<i64>,
 }
 
+const GET_ARGS: [&str; 2] = ["updated_since", "limit"];
+
 #[derive(Serialize, Deserialize)]
 pub struct GetItem {
     pub id: i64,
@@ -31,9 +41,9 @@ pub struct GetItem {
     pub element_id: String,
     pub r#type: String,
     pub tags: HashMap<String, Value>,
-    #[serde(with = "time::serde::rfc3339")]
+    #[serde(with = "crate::server::timestamp")]
     pub created_at: OffsetDateTime,
-    #[serde(with = "time::serde::rfc3339")]
+    #[serde(with = "crate::server::timestamp")]
     pub updated_at: OffsetDateTime,
     pub deleted_at: String,
 }
@@ -50,7 +60,7 @@ impl Into<GetItem> for Event {
             updated_at: self.updated_at,
             deleted_at: self
                 .deleted_at
-                .map(|it| it.format(&Rfc3339).unwrap())
+                .map(|it| timestamp::format(&it))
                 .unwrap_or_default()
                 .into(),
         }
@@ -63,36 +73,43 @@ impl Into<Json<GetItem>> for Event {
     }
 }
 
-#[get("")]
+#[route("", method = "GET", method = "HEAD")]
 async fn get(
+    req: HttpRequest,
     args: Query<GetArgs>,
     repo: Data<EventRepo>,
-) -> Result<Either<Json<Vec<GetItem>>, Redirect>, Error> {
+) -> Result<Either<CustomizeResponder<Json<Vec<GetItem>>>, Redirect>, Error> {
+    strict::check(req.query_string(), &GET_ARGS)?;
     if args.limit.is_none() && args.updated_since.is_none() {
         return Ok(Either::Right(
             Redirect::to("https://static.btcmap.org/api/v2/events.json").permanent(),
         ));
     }
 
-    Ok(Either::Left(Json(match &args.updated_since {
-        Some(updated_since) => repo
-            .select_updated_since(updated_since, args.limit)
-            .await?
-            .into_iter()
-            .map(|it| it.into())
-            .collect(),
-        None => repo
-            .select_updated_since(
-                &OffsetDateTime::now_utc()
-                    .checked_sub(Duration::days(30))
-                    .unwrap(),
-                args.limit,
-            )
-            .await?
-            .into_iter()
-            .map(|it| it.into())
-            .collect(),
-    })))
+    let updated_since = args.updated_since.unwrap_or(
+        OffsetDateTime::now_utc()
+            .checked_sub(Duration::days(30))
+            .unwrap(),
+    );
+
+    let total = repo.select_count(Some(updated_since)).await?;
+
+    let items: Vec<GetItem> = repo
+        .select_updated_since(&updated_since, resolve_sync_limit(args.limit))
+        .await?
+        .into_iter()
+        .map(|it| it.into())
+        .collect();
+
+    // The default window moves with time, so it can lose events without any change to the table
+    let last_modified = match args.updated_since {
+        Some(_) => repo.select_max_updated_at().await?,
+        None => None,
+    };
+    Ok(Either::Left(
+        last_modified::customize(Json(items), last_modified)
+            .insert_header((TOTAL_COUNT_HEADER, total)),
+    ))
 }
 
 #[get("{id}")]
@@ -1,4 +1,4 @@
-use crate::{element::Element, Result};
+use crate::{element::Element, osm::overpass::outdated_days, Result};
 use rusqlite::Connection;
 use serde::{Deserialize, Serialize};
 use std::{thread::sleep, time::Duration};
@@ -13,6 +13,7 @@ pub struct Report {
 
 #[derive(Serialize)]
 pub struct ReportIssue {
+    pub element_id: String,
     pub r#type: String,
     pub severity: i64,
     pub description: String,
@@ -75,6 +76,7 @@ fn _generate_report(elements: Vec<Element>) -> Result<Report> {
         let mut element_issues: Vec<ReportIssue> = element_issues
             .into_iter()
             .map(|it| ReportIssue {
+                element_id: element.overpass_data.btcmap_id(),
                 r#type: it.r#type,
                 severity: it.severity,
                 description: it.description,
@@ -98,6 +100,9 @@ fn get_issues(element: &Element) -> Vec<Issue> {
     let mut res: Vec<Issue> = vec![];
     res.append(&mut get_date_format_issues(element));
     res.append(&mut get_misspelled_tag_issues(element));
+    if let Some(issue) = get_payment_currency_mismatch_issue(element) {
+        res.push(issue);
+    };
     if let Some(issue) = get_missing_icon_issue(element) {
         res.push(issue);
     };
@@ -117,31 +122,28 @@ fn get_issues(element: &Element) -> Vec<Issue> {
 fn get_date_format_issues(element: &Element) -> Vec<Issue> {
     let mut res: Vec<Issue> = vec![];
     let date_format = format_description!("[year]-[month]-[day]");
-    let survey_date = element.overpass_data.tag("survey:date");
-    if survey_date.len() > 0 && Date::parse(survey_date, &date_format).is_err() {
-        res.push(Issue {
-            r#type: "date_format".into(),
-            severity: 600,
-            description: "survey:date is not formatted properly".into(),
-        });
-    }
-    let check_date = element.overpass_data.tag("check_date");
-    if check_date.len() > 0 && Date::parse(check_date, &date_format).is_err() {
-        res.push(Issue {
-            r#type: "date_format".into(),
-            severity: 600,
-            description: "check_date is not formatted properly".into(),
-        });
-    }
-    let check_date_currency_xbt = element.overpass_data.tag("check_date:currency:XBT");
-    if check_date_currency_xbt.len() > 0
-        && Date::parse(check_date_currency_xbt, &date_format).is_err()
-    {
-        res.push(Issue {
-            r#type: "date_format".into(),
-            severity: 600,
-            description: "check_date:currency:XBT is not formatted properly".into(),
-        });
+    let today = OffsetDateTime::now_utc().date();
+    for tag in ["survey:date", "check_date", "check_date:currency:XBT"] {
+        let value = element.overpass_data.tag(tag);
+        if value.len() == 0 {
+            continue;
+        }
+        match Date::parse(value, &date_format) {
+            Ok(date) => {
+                if date > today {
+                    res.push(Issue {
+                        r#type: "date_in_future".into(),
+                        severity: 600,
+                        description: format!("{tag} is in the future: {value}"),
+                    });
+                }
+            }
+            Err(_) => res.push(Issue {
+                r#type: "date_format".into(),
+                severity: 600,
+                description: format!("{tag} is not formatted properly: {value}"),
+            }),
+        }
     }
     res
 }
@@ -175,6 +177,30 @@ fn get_misspelled_tag_issues(element: &Element) -> Vec<Issue> {
     res
 }
 
+// Newer payment:* tags and the older currency:XBT tag should agree on accepting bitcoin
+fn get_payment_currency_mismatch_issue(element: &Element) -> Option<Issue> {
+    let payment_tags: Vec<&str> = [
+        "payment:bitcoin",
+        "payment:onchain",
+        "payment:lightning",
+        "payment:lightning_contactless",
+    ]
+    .into_iter()
+    .filter(|it| element.overpass_data.tag(it) == "yes")
+    .collect();
+    if payment_tags.is_empty() || element.overpass_data.tag("currency:XBT") == "yes" {
+        return None;
+    }
+    Some(Issue {
+        r#type: "payment_currency_mismatch".into(),
+        severity: 500,
+        description: format!(
+            "{}=yes but currency:XBT is not yes",
+            payment_tags.join("=yes, "),
+        ),
+    })
+}
+
 fn get_missing_icon_issue(element: &Element) -> Option<Issue> {
     if element.tag("icon:android").as_str().unwrap_or_default() == ""
         || element.tag("icon:android").as_str().unwrap_or_default() == "question_mark"
@@ -214,12 +240,11 @@ fn get_out_of_date_issue(element: &Element) -> Option<Issue> {
 }
 
 fn get_soon_out_of_date_issue(element: &Element) -> Option<Issue> {
-    if element.overpass_data.verification_date().is_some()
+    if element.overpass_data.up_to_date()
         && element
             .overpass_data
             .days_since_verified()
-            .map(|it| it > 365 - 90 && it < 365)
-            .is_some_and(|it| it)
+            .is_some_and(|it| it > outdated_days() - 90)
     {
         return Some(Issue {
             r#type: "out_of_date_soon".into(),
@@ -230,3 +255,91 @@ fn get_soon_out_of_date_issue(element: &Element) -> Option<Issue> {
 
     None
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{osm::overpass::OverpassElement, test::mock_osm_tags};
+    use std::collections::HashMap;
+
+    #[test]
+    fn date_format_issues() {
+        let tomorrow = OffsetDateTime::now_utc()
+            .date()
+            .next_day()
+            .unwrap()
+            .to_string();
+        let element = Element {
+            id: 1,
+            overpass_data: OverpassElement {
+                tags: Some(mock_osm_tags(&[
+                    "check_date",
+                    &tomorrow,
+                    "survey:date",
+                    "garbage",
+                    "check_date:currency:XBT",
+                    "2023-01-01",
+                ])),
+                ..OverpassElement::mock(1)
+            },
+            tags: HashMap::new(),
+            created_at: OffsetDateTime::now_utc(),
+            updated_at: OffsetDateTime::now_utc(),
+            deleted_at: None,
+        };
+        let issues = get_date_format_issues(&element);
+        assert_eq!(2, issues.len());
+        assert!(issues.iter().any(|it| it.r#type == "date_in_future"
+            && it.description == format!("check_date is in the future: {tomorrow}")));
+        assert!(issues.iter().any(|it| it.r#type == "date_format"
+            && it.description == "survey:date is not formatted properly: garbage"));
+    }
+
+    #[test]
+    fn payment_currency_mismatch_issue() {
+        let element = |tags: &[&str]| Element {
+            id: 1,
+            overpass_data: OverpassElement {
+                tags: Some(mock_osm_tags(tags)),
+                ..OverpassElement::mock(1)
+            },
+            tags: HashMap::new(),
+            created_at: OffsetDateTime::now_utc(),
+            updated_at: OffsetDateTime::now_utc(),
+            deleted_at: None,
+        };
+        let issue = get_payment_currency_mismatch_issue(&element(&[
+            "payment:lightning",
+            "yes",
+            "payment:onchain",
+            "no",
+        ]))
+        .unwrap();
+        assert_eq!("payment_currency_mismatch", issue.r#type);
+        assert_eq!(
+            "payment:lightning=yes but currency:XBT is not yes",
+            issue.description
+        );
+        let issue = get_payment_currency_mismatch_issue(&element(&[
+            "payment:onchain",
+            "yes",
+            "payment:lightning",
+            "yes",
+            "currency:XBT",
+            "no",
+        ]))
+        .unwrap();
+        assert_eq!(
+            "payment:onchain=yes, payment:lightning=yes but currency:XBT is not yes",
+            issue.description,
+        );
+        assert!(get_payment_currency_mismatch_issue(&element(&[
+            "payment:lightning",
+            "yes",
+            "currency:XBT",
+            "yes",
+        ]))
+        .is_none());
+        assert!(get_payment_currency_mismatch_issue(&element(&["currency:XBT", "yes"])).is_none());
+    }
+}
@@ -0,0 +1,60 @@
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::Layer;
+use tracing_subscriber::prelude::__tracing_subscriber_SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+
+// RUST_LOG accepts per-module directives such as "info,btcmap_api::sync=debug", modules without
+// a directive of their own log at info
+pub fn init() {
+    tracing_subscriber::registry()
+        .with(env_filter(std::env::var("RUST_LOG").ok().as_deref()))
+        .with(Layer::new().json())
+        .init();
+}
+
+fn env_filter(directives: Option<&str>) -> EnvFilter {
+    let directives = directives.unwrap_or_default().trim();
+    // The default directive is only used when nothing parses, so a RUST_LOG with target
+    // directives alone would turn off every other module
+    let has_level = directives
+        .split(',')
+        .any(|it| it.trim().parse::<LevelFilter>().is_ok());
+    let directives = match (directives.is_empty(), has_level) {
+        (true, _) => LevelFilter::INFO.to_string(),
+        (false, true) => directives.to_string(),
+        (false, false) => format!("{},{directives}", LevelFilter::INFO),
+    };
+    EnvFilter::builder().parse_lossy(directives)
+}
+
+#[cfg(test)]
+mod test {
+    use tracing_subscriber::filter::LevelFilter;
+
+    #[test]
+    fn env_filter() {
+        let filter = super::env_filter(None);
+        assert_eq!("info", filter.to_string().to_lowercase());
+        assert_eq!(Some(LevelFilter::INFO), filter.max_level_hint());
+        let filter = super::env_filter(Some("btcmap_api::sync=debug,actix_web=warn"));
+        let directives = filter.to_string().to_lowercase();
+        let mut directives: Vec<&str> = directives.split(',').collect();
+        directives.sort();
+        assert_eq!(
+            vec!["actix_web=warn", "btcmap_api::sync=debug", "info"],
+            directives,
+        );
+        assert_eq!(Some(LevelFilter::DEBUG), filter.max_level_hint());
+        let filter = super::env_filter(Some("btcmap_api::sync=debug"));
+        let directives = filter.to_string().to_lowercase();
+        let mut directives: Vec<&str> = directives.split(',').collect();
+        directives.sort();
+        assert_eq!(vec!["btcmap_api::sync=debug", "info"], directives);
+        let filter = super::env_filter(Some("warn,btcmap_api::sync=debug"));
+        let directives = filter.to_string().to_lowercase();
+        let mut directives: Vec<&str> = directives.split(',').collect();
+        directives.sort();
+        assert_eq!(vec!["btcmap_api::sync=debug", "warn"], directives);
+    }
+}
@@ -16,6 +16,7 @@ mod discord;
 mod element;
 mod error;
 mod event;
+mod openapi;
 mod osm;
 mod report;
 #[cfg(test)]
@@ -26,27 +27,17 @@ use rusqlite::Connection;
 use std::env;
 use std::process::ExitCode;
 use tracing::error;
-use tracing_subscriber::fmt::Layer;
-use tracing_subscriber::prelude::__tracing_subscriber_SubscriberExt;
-use tracing_subscriber::util::SubscriberInitExt;
-use tracing_subscriber::EnvFilter;
 mod area;
 mod boost;
 mod lint;
+mod log;
 mod sync;
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
 #[actix_web::main]
 async fn main() -> ExitCode {
-    if env::var("RUST_LOG").is_err() {
-        env::set_var("RUST_LOG", "info");
-    }
-
-    tracing_subscriber::registry()
-        .with(EnvFilter::from_default_env())
-        .with(Layer::new().json())
-        .init();
+    log::init();
 
     let mut db = match command::db::open_connection() {
         Ok(v) => v,
@@ -73,19 +64,56 @@ async fn main() -> ExitCode {
                 return ExitCode::FAILURE;
             }
 
+            if args.get(2).is_some_and(|it| it == "--with-sync") {
+                sync::schedule::spawn();
+            }
+
             if let Err(e) = server::run().await {
                 error!(?e, "Failed to start a server");
                 return ExitCode::FAILURE;
             }
         }
+        "db" => match args.get(2).map(|it| it.as_str()) {
+            Some("vacuum") => {
+                if let Err(e) = db::vacuum(&db) {
+                    error!(?e, "Failed to vacuum database");
+                    return ExitCode::FAILURE;
+                }
+            }
+            Some("migrate-coords") => {
+                if let Err(e) = command::migrate_coords::run(&mut db) {
+                    error!(?e, "Failed to migrate element coordinates");
+                    return ExitCode::FAILURE;
+                }
+            }
+            _ => {
+                error!("Unknown db action, supported actions: vacuum, migrate-coords");
+                return ExitCode::FAILURE;
+            }
+        },
+        "export" => {
+            if let Err(e) = command::export::run(args.get(2..).unwrap_or_default(), &db) {
+                error!(?e, "Failed to export snapshot");
+                return ExitCode::FAILURE;
+            }
+        }
         "sync" => {
-            if let Err(e) = command::sync::run(db).await {
+            let res = match args.get(2).map(|it| it.as_str()) {
+                Some("--from-file") => {
+                    let path = args.get(3).map(|it| it.as_str()).unwrap_or_default();
+                    command::sync::run_from_file(db, path).await
+                }
+                Some("--incremental") => command::sync::run(db, true).await,
+                _ => command::sync::run(db, false).await,
+            };
+            if let Err(e) = res {
                 error!(?e, "Failed to sync elements");
                 return ExitCode::FAILURE;
             }
         }
         "sync-users" => {
-            if let Err(e) = sync::users::run(db).await {
+            let refresh_all = args.get(2).is_some_and(|it| it == "--refresh-all");
+            if let Err(e) = sync::users::run(db, refresh_all).await {
                 error!(?e, "Failed to sync users");
                 return ExitCode::FAILURE;
             }
@@ -140,13 +168,15 @@ async fn main() -> ExitCode {
             }
         }
         "fix-tags" => {
-            if let Err(e) = fix_tags::run(&db).await {
+            if let Err(e) =
+                fix_tags::run(args.get(2).is_some_and(|it| it == "--dry-run"), &db).await
+            {
                 error!(?e, "Failed to fix tags");
                 return ExitCode::FAILURE;
             }
         }
         "compress-reports" => {
-            if let Err(e) = compress_reports::run(&db) {
+            if let Err(e) = compress_reports::run(&mut db) {
                 error!(?e, "Failed to compress reports");
                 return ExitCode::FAILURE;
             }
@@ -0,0 +1,146 @@
+use actix_web::{get, http::StatusCode, HttpResponse, Responder};
+
+// Hand-maintained, make sure to update it when changing any of the GetItem structs
+const SPEC: &str = include_str!("openapi.json");
+
+#[get("openapi.json")]
+async fn get() -> impl Responder {
+    HttpResponse::build(StatusCode::OK)
+        .content_type("application/json")
+        .body(SPEC)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::area::v2::GetItem as AreaItem;
+    use crate::element::v2::GetItem as ElementItem;
+    use crate::event::v2::GetItem as EventItem;
+    use crate::osm::osm::OsmUser;
+    use crate::osm::overpass::OverpassElement;
+    use crate::report::v2::GetItem as ReportItem;
+    use crate::sync::v2::GetStatusItem as SyncStatusItem;
+    use crate::user::v2::GetItem as UserItem;
+    use crate::Result;
+    use actix_web::test::TestRequest;
+    use actix_web::{test, App};
+    use serde::Serialize;
+    use serde_json::{Map, Value};
+    use std::collections::HashMap;
+    use time::OffsetDateTime;
+
+    #[test]
+    async fn get() -> Result<()> {
+        let app = test::init_service(App::new().service(super::get)).await;
+        let req = TestRequest::get().uri("/openapi.json").to_request();
+        let res: Value = test::call_and_read_body_json(&app, req).await;
+        assert!(res["paths"]["/v2/elements"].is_object());
+        Ok(())
+    }
+
+    fn assert_schema_matches(schema: &str, item: impl Serialize) -> Result<()> {
+        let spec: Value = serde_json::from_str(super::SPEC)?;
+        let mut expected: Vec<String> = spec["components"]["schemas"][schema]["properties"]
+            .as_object()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect();
+        expected.sort();
+        let mut actual: Vec<String> = serde_json::to_value(item)?
+            .as_object()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect();
+        actual.sort();
+        assert_eq!(expected, actual, "{schema} schema is out of sync");
+        Ok(())
+    }
+
+    #[test]
+    async fn schemas_match_get_items() -> Result<()> {
+        let now = OffsetDateTime::now_utc();
+        assert_schema_matches(
+            "Element",
+            ElementItem {
+                id: "".into(),
+                osm_json: OverpassElement::mock(1),
+                osm_version: None,
+                osm_timestamp: None,
+                osm_user: None,
+                osm_changeset_url: Some("".into()),
+                osm_url: "".into(),
+                osm_edit_url: "".into(),
+                description: "".into(),
+                display_name: "".into(),
+                is_atm: false,
+                tags: HashMap::new(),
+                created_at: now,
+                updated_at: now,
+                deleted_at: "".into(),
+                events_count: Some(1),
+                last_event_at: Some(now),
+                open_now: Some(true),
+                hours_today: Some("".into()),
+            },
+        )?;
+        assert_schema_matches(
+            "Event",
+            EventItem {
+                id: 1,
+                user_id: 1,
+                element_id: "".into(),
+                r#type: "".into(),
+                tags: HashMap::new(),
+                created_at: now,
+                updated_at: now,
+                deleted_at: "".into(),
+            },
+        )?;
+        assert_schema_matches(
+            "User",
+            UserItem {
+                id: 1,
+                osm_json: OsmUser::mock(),
+                tags: Map::new(),
+                created_at: now,
+                updated_at: now,
+                deleted_at: "".into(),
+            },
+        )?;
+        assert_schema_matches(
+            "Area",
+            AreaItem {
+                id: "".into(),
+                tags: Map::new(),
+                created_at: now,
+                updated_at: now,
+                deleted_at: "".into(),
+            },
+        )?;
+        assert_schema_matches(
+            "Report",
+            ReportItem {
+                id: 1,
+                area_id: "".into(),
+                date: "".into(),
+                tags: Map::new(),
+                created_at: now,
+                updated_at: now,
+                deleted_at: "".into(),
+            },
+        )?;
+        assert_schema_matches(
+            "SyncStatus",
+            SyncStatusItem {
+                mode: "".into(),
+                timestamp_osm_base: now,
+                created_at: now,
+                duration_secs: Some(1.0),
+                elements: Some(1),
+                elements_per_second: Some(1.0),
+            },
+        )?;
+        Ok(())
+    }
+}
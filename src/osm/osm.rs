@@ -1,18 +1,39 @@
 use std::collections::HashMap;
+use std::env;
+use std::future::Future;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
 
+use reqwest::header::RETRY_AFTER;
 use reqwest::{Response, StatusCode};
 use serde::{Deserialize, Serialize};
 use time::OffsetDateTime;
-use tracing::info;
+use tracing::{info, warn};
 
 use crate::{Error, Result};
 
+const DEFAULT_API_URL: &str = "https://api.openstreetmap.org";
+
+// Set OSM_API_URL to query a local mock or the dev instance instead of the main OSM API
+pub fn api_url() -> &'static str {
+    static API_URL: OnceLock<String> = OnceLock::new();
+    API_URL.get_or_init(|| parse_api_url(env::var("OSM_API_URL").ok().as_deref()))
+}
+
+fn parse_api_url(value: Option<&str>) -> String {
+    value
+        .map(|it| it.trim().trim_end_matches('/'))
+        .filter(|it| !it.is_empty())
+        .unwrap_or(DEFAULT_API_URL)
+        .to_string()
+}
+
 #[derive(Deserialize)]
 struct OsmElementResponse {
     elements: Vec<OsmElement>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Clone, Debug)]
 pub struct OsmElement {
     pub r#type: String,
     pub id: i64,
@@ -33,7 +54,8 @@ impl OsmElement {
 
 pub async fn get_element(element_type: &str, element_id: i64) -> Result<Option<OsmElement>> {
     let url = format!(
-        "https://api.openstreetmap.org/api/0.6/{element_type}s.json?{element_type}s={element_id}"
+        "{}/api/0.6/{element_type}s.json?{element_type}s={element_id}",
+        api_url(),
     );
     info!(url, "Querying OSM");
     let res = reqwest::get(&url).await?;
@@ -41,6 +63,60 @@ pub async fn get_element(element_type: &str, element_id: i64) -> Result<Option<O
     _get_element(res).await
 }
 
+// Past versions never change, the cache only exists to avoid hammering OSM with repeated requests
+const ELEMENT_VERSION_CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+
+type ElementVersionKey = (String, i64, i64);
+
+static ELEMENT_VERSION_CACHE: OnceLock<Mutex<HashMap<ElementVersionKey, (Instant, OsmElement)>>> =
+    OnceLock::new();
+
+pub async fn get_element_version(
+    element_type: &str,
+    element_id: i64,
+    version: i64,
+) -> Result<Option<OsmElement>> {
+    let key = (element_type.to_string(), element_id, version);
+    if let Some(element) = cached_element_version(&key) {
+        info!(element_type, element_id, version, "Using cached version");
+        return Ok(Some(element));
+    }
+    let url = format!(
+        "{}/api/0.6/{element_type}/{element_id}/{version}.json",
+        api_url(),
+    );
+    info!(url, "Querying OSM");
+    let res = reqwest::get(&url).await?;
+    info!(request_url = url, response_status = ?res.status(), "Got response from OSM");
+    let element = _get_element(res).await?;
+    if let Some(element) = &element {
+        cache_element_version(key, element.clone());
+    }
+    Ok(element)
+}
+
+fn cached_element_version(key: &ElementVersionKey) -> Option<OsmElement> {
+    let cache = ELEMENT_VERSION_CACHE
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap();
+    match cache.get(key) {
+        Some((cached_at, element)) if cached_at.elapsed() < ELEMENT_VERSION_CACHE_TTL => {
+            Some(element.clone())
+        }
+        _ => None,
+    }
+}
+
+pub fn cache_element_version(key: ElementVersionKey, element: OsmElement) {
+    let mut cache = ELEMENT_VERSION_CACHE
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap();
+    cache.retain(|_, (cached_at, _)| cached_at.elapsed() < ELEMENT_VERSION_CACHE_TTL);
+    cache.insert(key, (Instant::now(), element));
+}
+
 async fn _get_element(res: Response) -> Result<Option<OsmElement>> {
     if res.status().is_success() {
         let mut res: OsmElementResponse = res.json().await?;
@@ -135,13 +211,70 @@ impl OsmUser {
 }
 
 pub async fn get_user(id: i64) -> Result<Option<OsmUser>> {
-    let url = format!("https://api.openstreetmap.org/api/0.6/user/{id}.json");
+    let url = format!("{}/api/0.6/user/{id}.json", api_url());
     info!(url, "Querying OSM");
-    let res = reqwest::get(&url).await?;
+    let res = with_retries(|| reqwest::get(&url), RETRY_BACKOFF).await?;
     info!(request_url = url, response_status = ?res.status(), "Got response from OSM");
     _get_user(res).await
 }
 
+const MAX_ATTEMPTS: u32 = 5;
+const RETRY_BACKOFF: Duration = Duration::from_secs(2);
+// Servers can ask us to come back in hours, a sync shouldn't hang that long on a single request
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+// Total time a request can spend waiting between its attempts
+const RETRY_BUDGET: Duration = Duration::from_secs(120);
+
+// Rate limits and server errors are usually temporary, so it's worth waiting instead of losing data
+async fn with_retries<F, Fut>(mut send: F, backoff: Duration) -> reqwest::Result<Response>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = reqwest::Result<Response>>,
+{
+    let mut attempt = 1;
+    let mut waited = Duration::ZERO;
+    loop {
+        let res = send().await;
+        let retryable = match &res {
+            Ok(res) => {
+                res.status() == StatusCode::TOO_MANY_REQUESTS || res.status().is_server_error()
+            }
+            Err(_) => true,
+        };
+        if !retryable || attempt >= MAX_ATTEMPTS {
+            return res;
+        }
+        let delay = res
+            .as_ref()
+            .ok()
+            .and_then(retry_after)
+            .unwrap_or(backoff * 2u32.pow(attempt - 1))
+            .min(MAX_RETRY_DELAY);
+        if waited + delay > RETRY_BUDGET {
+            warn!(attempt, "OSM API request failed, retry budget exhausted");
+            return res;
+        }
+        waited += delay;
+        warn!(
+            attempt,
+            delay_seconds = delay.as_secs_f64(),
+            "OSM API request failed, retrying"
+        );
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+fn retry_after(res: &Response) -> Option<Duration> {
+    res.headers()
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+}
+
 async fn _get_user(res: Response) -> Result<Option<OsmUser>> {
     if res.status().is_success() {
         let res: OsmUserResponse = res.json().await?;
@@ -161,9 +294,90 @@ async fn _get_user(res: Response) -> Result<Option<OsmUser>> {
 #[cfg(test)]
 mod test {
     use http::response::Builder;
+    use reqwest::Response;
+    use std::time::Duration;
 
     use crate::Result;
 
+    #[actix_web::test]
+    async fn with_retries() -> Result<()> {
+        let mut responses = vec![
+            Builder::new().status(200).body("")?,
+            Builder::new()
+                .status(429)
+                .header("Retry-After", "0")
+                .body("")?,
+        ];
+        let mut attempts = 0;
+        let res = super::with_retries(
+            || {
+                attempts += 1;
+                let res = Response::from(responses.pop().unwrap());
+                async move { Ok(res) }
+            },
+            Duration::ZERO,
+        )
+        .await?;
+        assert_eq!(200, res.status().as_u16());
+        assert_eq!(2, attempts);
+
+        let mut attempts = 0;
+        let res = super::with_retries(
+            || {
+                attempts += 1;
+                let res = Response::from(Builder::new().status(503).body("").unwrap());
+                async move { Ok(res) }
+            },
+            Duration::ZERO,
+        )
+        .await?;
+        assert_eq!(503, res.status().as_u16());
+        assert_eq!(super::MAX_ATTEMPTS, attempts);
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn with_retries_clamps_retry_after() -> Result<()> {
+        let started_at = tokio::time::Instant::now();
+        let mut attempts = 0;
+        let res = super::with_retries(
+            || {
+                attempts += 1;
+                let res = Builder::new()
+                    .status(429)
+                    .header("Retry-After", "3600")
+                    .body("")
+                    .unwrap();
+                let res = Response::from(res);
+                async move { Ok(res) }
+            },
+            Duration::ZERO,
+        )
+        .await?;
+        assert_eq!(429, res.status().as_u16());
+        // Two clamped delays fit into the budget, the third one doesn't
+        assert_eq!(3, attempts);
+        assert_eq!(super::RETRY_BUDGET, started_at.elapsed());
+        Ok(())
+    }
+
+    #[test]
+    fn parse_api_url() {
+        assert_eq!("https://api.openstreetmap.org", super::parse_api_url(None));
+        assert_eq!(
+            "https://api.openstreetmap.org",
+            super::parse_api_url(Some(""))
+        );
+        assert_eq!(
+            "http://localhost:3000",
+            super::parse_api_url(Some("http://localhost:3000/"))
+        );
+        assert_eq!(
+            "https://master.apis.dev.openstreetmap.org",
+            super::parse_api_url(Some("https://master.apis.dev.openstreetmap.org"))
+        );
+    }
+
     #[actix_web::test]
     async fn get_element() -> Result<()> {
         let res_json = r#"
@@ -285,6 +499,13 @@ mod test {
         Ok(())
     }
 
+    #[actix_web::test]
+    async fn get_user_410() -> Result<()> {
+        let res = super::_get_user(Builder::new().status(410).body("")?.into()).await?;
+        assert!(res.is_none());
+        Ok(())
+    }
+
     #[actix_web::test]
     async fn get_user_unexpected_res_code() -> Result<()> {
         let res = super::_get_user(Builder::new().status(304).body("")?.into()).await;
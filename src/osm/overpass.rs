@@ -1,33 +1,99 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::sync::OnceLock;
 
 use crate::{Error, Result};
 use geo::{coord, Coord};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use time::{
-    format_description::well_known::Iso8601, macros::format_description, Date, OffsetDateTime,
-};
+use time::{macros::format_description, Date, Duration, OffsetDateTime, UtcOffset};
 use tracing::info;
 
 static API_URL: &str = "https://overpass-api.de/api/interpreter";
 
-static QUERY: &str = r#"
-    [out:json][timeout:300];
-    nwr["currency:XBT"=yes];
-    out meta geom;
-"#;
+const DEFAULT_OUTDATED_DAYS: i64 = 365;
+
+// Set OUTDATED_DAYS to change how long a verification stays valid
+pub fn outdated_days() -> i64 {
+    static OUTDATED_DAYS: OnceLock<i64> = OnceLock::new();
+    *OUTDATED_DAYS.get_or_init(|| parse_outdated_days(env::var("OUTDATED_DAYS").ok().as_deref()))
+}
+
+fn parse_outdated_days(value: Option<&str>) -> i64 {
+    value
+        .and_then(|it| it.parse().ok())
+        .filter(|it| *it > 0)
+        .unwrap_or(DEFAULT_OUTDATED_DAYS)
+}
+
+// Bitcoin specific date is the most reliable signal, generic dates are used as fallbacks
+pub const VERIFICATION_TAGS: [&str; 3] = ["check_date:currency:XBT", "check_date", "survey:date"];
+
+// Incremental syncs can't detect deleted elements, so we want a full sync at least once a day
+pub const INCREMENTAL_SYNC_MAX_AGE_HOURS: i64 = 24;
+
+#[derive(PartialEq, Debug)]
+pub enum SyncMode {
+    Full,
+    Incremental { since: OffsetDateTime },
+}
+
+impl SyncMode {
+    // Incremental syncs move last_sync forward, so the max age is checked against the last full sync
+    pub fn select(
+        last_sync: Option<OffsetDateTime>,
+        last_full_sync: Option<OffsetDateTime>,
+        now: OffsetDateTime,
+    ) -> SyncMode {
+        match (last_sync, last_full_sync) {
+            (Some(last_sync), Some(last_full_sync))
+                if now - last_full_sync < Duration::hours(INCREMENTAL_SYNC_MAX_AGE_HOURS) =>
+            {
+                SyncMode::Incremental { since: last_sync }
+            }
+            _ => SyncMode::Full,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SyncMode::Full => "full",
+            SyncMode::Incremental { .. } => "incremental",
+        }
+    }
+
+    pub fn query(&self) -> Result<String> {
+        let filter = match self {
+            SyncMode::Full => "".to_string(),
+            SyncMode::Incremental { since } => {
+                let format = format_description!("[year]-[month]-[day]T[hour]:[minute]:[second]Z");
+                format!(
+                    r#"(newer:"{}")"#,
+                    since.to_offset(UtcOffset::UTC).format(format)?
+                )
+            }
+        };
+        Ok(format!(
+            r#"
+                [out:json][timeout:300];
+                nwr["currency:XBT"=yes]{filter};
+                out meta geom;
+            "#
+        ))
+    }
+}
 
 #[derive(Serialize, Deserialize)]
-struct Response {
-    version: f64,
-    generator: String,
-    osm3s: Osm3s,
-    elements: Vec<OverpassElement>,
+pub struct Response {
+    pub version: f64,
+    pub generator: String,
+    pub osm3s: Osm3s,
+    pub elements: Vec<OverpassElement>,
 }
 
 #[derive(Serialize, Deserialize)]
-struct Osm3s {
-    timestamp_osm_base: String,
+pub struct Osm3s {
+    pub timestamp_osm_base: String,
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
@@ -48,6 +114,25 @@ pub struct OverpassElement {
     pub members: Option<Value>,  // for relations only
 }
 
+#[derive(PartialEq, Debug)]
+pub enum ChangeType {
+    Tags,
+    Geometry,
+    TagsAndGeometry,
+    Metadata,
+}
+
+impl ChangeType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChangeType::Tags => "tags",
+            ChangeType::Geometry => "geometry",
+            ChangeType::TagsAndGeometry => "tags_and_geometry",
+            ChangeType::Metadata => "metadata",
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub struct Bounds {
     pub minlon: f64,
@@ -62,10 +147,12 @@ impl OverpassElement {
     }
 
     pub fn up_to_date(&self) -> bool {
-        return match self.days_since_verified() {
-            Some(days) => days < 365,
-            None => false,
-        };
+        self.up_to_date_within(outdated_days())
+    }
+
+    pub fn up_to_date_within(&self, days: i64) -> bool {
+        let min_date = OffsetDateTime::now_utc().saturating_sub(Duration::days(days));
+        self.verification_date().is_some_and(|it| it > min_date)
     }
 
     pub fn days_since_verified(&self) -> Option<i64> {
@@ -74,36 +161,45 @@ impl OverpassElement {
     }
 
     pub fn verification_date(&self) -> Option<OffsetDateTime> {
-        let survey_date = self.tag("survey:date");
-        let check_date = self.tag("check_date");
-        let bitcoin_check_date = self.tag("check_date:currency:XBT");
-        let source_date = self.tag("source:date");
-
-        let mut most_recent_date = "";
         let format = format_description!("[year]-[month]-[day]");
+        VERIFICATION_TAGS
+            .iter()
+            .find_map(|it| Date::parse(self.tag(it), format).ok())
+            .map(|it| it.midnight().assume_utc())
+    }
 
-        if Date::parse(survey_date, format).is_ok() && survey_date > most_recent_date {
-            most_recent_date = survey_date;
-        }
-
-        if Date::parse(check_date, format).is_ok() && check_date > most_recent_date {
-            most_recent_date = check_date;
-        }
-
-        if Date::parse(bitcoin_check_date, format).is_ok() && bitcoin_check_date > most_recent_date
-        {
-            most_recent_date = bitcoin_check_date;
-        }
-
-        if Date::parse(source_date, format).is_ok() && source_date > most_recent_date {
-            most_recent_date = source_date
+    // Metadata fields such as version, changeset and timestamp are bumped on every edit,
+    // even if the edit didn't touch anything we care about
+    pub fn change_type(&self, new: &OverpassElement) -> ChangeType {
+        let tags_changed = self.tags != new.tags;
+        let geometry_changed = self.lat != new.lat
+            || self.lon != new.lon
+            || self.bounds != new.bounds
+            || self.nodes != new.nodes
+            || self.geometry != new.geometry
+            || self.members != new.members;
+        match (tags_changed, geometry_changed) {
+            (true, true) => ChangeType::TagsAndGeometry,
+            (true, false) => ChangeType::Tags,
+            (false, true) => ChangeType::Geometry,
+            (false, false) => ChangeType::Metadata,
         }
+    }
 
-        OffsetDateTime::parse(
-            &format!("{}T00:00:00Z", most_recent_date),
-            &Iso8601::DEFAULT,
-        )
-        .ok()
+    pub fn changed_tags(&self, new: &OverpassElement) -> Vec<String> {
+        let empty = HashMap::new();
+        let old_tags = self.tags.as_ref().unwrap_or(&empty);
+        let new_tags = new.tags.as_ref().unwrap_or(&empty);
+        let mut changed: Vec<String> = old_tags
+            .keys()
+            .chain(new_tags.keys())
+            .filter(|it| old_tags.get(*it) != new_tags.get(*it))
+            .cloned()
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        changed.sort();
+        changed
     }
 
     pub fn coord(&self) -> Coord {
@@ -116,6 +212,17 @@ impl OverpassElement {
         }
     }
 
+    // Same as coord, but doesn't panic on elements missing both coordinates and bounds
+    pub fn try_coord(&self) -> Option<Coord> {
+        match (self.lat, self.lon, &self.bounds) {
+            (Some(lat), Some(lon), _) => Some(coord! { x: lon, y: lat }),
+            (_, _, Some(bounds)) => Some(
+                coord! { x: (bounds.minlon + bounds.maxlon) / 2.0, y: (bounds.minlat + bounds.maxlat) / 2.0 },
+            ),
+            _ => None,
+        }
+    }
+
     pub fn tag(&self, name: &str) -> &str {
         match &self.tags {
             Some(tags) => tags.get(name).map(|it| it.as_str()).unwrap_or(""),
@@ -144,12 +251,15 @@ impl OverpassElement {
     }
 }
 
-pub async fn query_bitcoin_merchants() -> Result<Vec<OverpassElement>> {
-    info!("Querying OSM API, it could take a while...");
+pub async fn query_bitcoin_merchants(mode: &SyncMode) -> Result<Response> {
+    info!(
+        mode = mode.name(),
+        "Querying OSM API, it could take a while..."
+    );
 
     let response = reqwest::Client::new()
         .post(API_URL)
-        .body(QUERY)
+        .body(mode.query()?)
         .send()
         .await?;
 
@@ -157,27 +267,33 @@ pub async fn query_bitcoin_merchants() -> Result<Vec<OverpassElement>> {
 
     let response = response.json::<Response>().await?;
 
-    if response.elements.len() == 0 {
-        Err(Error::OverpassApi(format!(
-            "Got suspicious response: {}",
-            serde_json::to_string_pretty(&response)?
-        )))?
-    }
-
     info!(elements = response.elements.len(), "Fetched elements");
 
-    if response.elements.len() < 5000 {
-        Err(Error::OverpassApi("Data set is most likely invalid".into()))?
+    // Partial data sets are expected to be small, those checks only make sense for full syncs
+    if mode == &SyncMode::Full {
+        if response.elements.len() == 0 {
+            Err(Error::OverpassApi(format!(
+                "Got suspicious response: {}",
+                serde_json::to_string_pretty(&response)?
+            )))?
+        }
+
+        if response.elements.len() < 5000 {
+            Err(Error::OverpassApi("Data set is most likely invalid".into()))?
+        }
     }
 
-    Ok(response.elements)
+    Ok(response)
 }
 
 #[cfg(test)]
 mod test {
     use std::collections::HashMap;
 
-    use super::OverpassElement;
+    use time::{macros::datetime, Duration, OffsetDateTime};
+
+    use super::{ChangeType, OverpassElement, SyncMode};
+    use crate::test::mock_osm_tags;
 
     #[test]
     fn get_tag_value() {
@@ -190,4 +306,189 @@ mod test {
         assert_eq!("bar", element.tag("foo"));
         assert_eq!("", element.tag("missing"));
     }
+
+    #[test]
+    fn up_to_date() {
+        let today = OffsetDateTime::now_utc().date();
+        let fresh = (today - Duration::days(10)).to_string();
+        let stale = (today - Duration::days(400)).to_string();
+        let date_tags = ["check_date:currency:XBT", "check_date", "survey:date"];
+        for (i, freshest) in date_tags.iter().enumerate() {
+            let mut tags = vec![*freshest, fresh.as_str()];
+            for tag in &date_tags[i + 1..] {
+                tags.push(*tag);
+                tags.push(stale.as_str());
+            }
+            let element = OverpassElement {
+                tags: Some(mock_osm_tags(&tags)),
+                ..OverpassElement::mock(1)
+            };
+            assert!(element.up_to_date(), "{freshest} should be picked");
+            assert_eq!(
+                (today - Duration::days(10)).midnight().assume_utc(),
+                element.verification_date().unwrap(),
+            );
+        }
+        let element = OverpassElement {
+            tags: Some(mock_osm_tags(&[
+                "check_date",
+                &stale,
+                "survey:date",
+                &stale,
+            ])),
+            ..OverpassElement::mock(1)
+        };
+        assert!(!element.up_to_date());
+        assert!(!OverpassElement::mock(1).up_to_date());
+    }
+
+    #[test]
+    fn up_to_date_within() {
+        let verified = (OffsetDateTime::now_utc().date() - Duration::days(200)).to_string();
+        let element = OverpassElement {
+            tags: Some(mock_osm_tags(&["check_date", &verified])),
+            ..OverpassElement::mock(1)
+        };
+        assert!(element.up_to_date_within(365));
+        assert!(!element.up_to_date_within(180));
+    }
+
+    #[test]
+    fn parse_outdated_days() {
+        assert_eq!(365, super::parse_outdated_days(None));
+        assert_eq!(180, super::parse_outdated_days(Some("180")));
+        assert_eq!(365, super::parse_outdated_days(Some("-1")));
+        assert_eq!(365, super::parse_outdated_days(Some("half a year")));
+    }
+
+    #[test]
+    fn verification_date_prefers_xbt() {
+        let today = OffsetDateTime::now_utc().date();
+        let newer = today - Duration::days(10);
+        let older = today - Duration::days(20);
+        let element = OverpassElement {
+            tags: Some(mock_osm_tags(&[
+                "check_date:currency:XBT",
+                &newer.to_string(),
+                "check_date",
+                &older.to_string(),
+            ])),
+            ..OverpassElement::mock(1)
+        };
+        assert_eq!(
+            newer.midnight().assume_utc(),
+            element.verification_date().unwrap(),
+        );
+        let element = OverpassElement {
+            tags: Some(mock_osm_tags(&[
+                "check_date:currency:XBT",
+                &older.to_string(),
+                "check_date",
+                &newer.to_string(),
+            ])),
+            ..OverpassElement::mock(1)
+        };
+        assert_eq!(
+            older.midnight().assume_utc(),
+            element.verification_date().unwrap(),
+        );
+    }
+
+    #[test]
+    fn change_type() {
+        let old = OverpassElement::mock(1);
+        let new = OverpassElement {
+            version: Some(2),
+            changeset: Some(2),
+            ..old.clone()
+        };
+        assert_eq!(ChangeType::Metadata, old.change_type(&new));
+        let new = OverpassElement {
+            tags: Some(mock_osm_tags(&["name", "foo"])),
+            ..old.clone()
+        };
+        assert_eq!(ChangeType::Tags, old.change_type(&new));
+        let new = OverpassElement {
+            lat: Some(1.0),
+            ..old.clone()
+        };
+        assert_eq!(ChangeType::Geometry, old.change_type(&new));
+        let new = OverpassElement {
+            lat: Some(1.0),
+            tags: Some(mock_osm_tags(&["name", "foo"])),
+            ..old.clone()
+        };
+        assert_eq!(ChangeType::TagsAndGeometry, old.change_type(&new));
+    }
+
+    #[test]
+    fn changed_tags() {
+        let old = OverpassElement {
+            tags: Some(mock_osm_tags(&[
+                "name",
+                "foo",
+                "phone",
+                "123",
+                "website",
+                "https://example.com",
+            ])),
+            ..OverpassElement::mock(1)
+        };
+        let new = OverpassElement {
+            tags: Some(mock_osm_tags(&[
+                "name",
+                "foo",
+                "phone",
+                "456",
+                "opening_hours",
+                "24/7",
+            ])),
+            ..OverpassElement::mock(1)
+        };
+        assert_eq!(
+            vec!["opening_hours", "phone", "website"],
+            old.changed_tags(&new),
+        );
+        assert!(old.changed_tags(&old).is_empty());
+    }
+
+    #[test]
+    fn select_sync_mode() {
+        let now = datetime!(2024-05-02 12:00 UTC);
+        assert_eq!(SyncMode::Full, SyncMode::select(None, None, now));
+        let old_sync = datetime!(2024-04-30 12:00 UTC);
+        assert_eq!(
+            SyncMode::Full,
+            SyncMode::select(Some(old_sync), Some(old_sync), now),
+        );
+        let last_sync = datetime!(2024-05-02 11:00 UTC);
+        assert_eq!(
+            SyncMode::Incremental { since: last_sync },
+            SyncMode::select(Some(last_sync), Some(last_sync), now),
+        );
+        assert_eq!(
+            SyncMode::Incremental { since: last_sync },
+            SyncMode::select(Some(last_sync), Some(datetime!(2024-05-02 00:00 UTC)), now),
+        );
+        // Recent incremental syncs don't postpone the daily full sync
+        assert_eq!(
+            SyncMode::Full,
+            SyncMode::select(Some(last_sync), Some(old_sync), now),
+        );
+        assert_eq!(SyncMode::Full, SyncMode::select(Some(last_sync), None, now));
+    }
+
+    #[test]
+    fn sync_mode_query() -> crate::Result<()> {
+        assert!(SyncMode::Full
+            .query()?
+            .contains(r#"nwr["currency:XBT"=yes];"#));
+        let mode = SyncMode::Incremental {
+            since: datetime!(2024-05-02 11:00:00.123 UTC),
+        };
+        assert!(mode
+            .query()?
+            .contains(r#"nwr["currency:XBT"=yes](newer:"2024-05-02T11:00:00Z");"#));
+        Ok(())
+    }
 }
@@ -16,6 +16,7 @@ use tracing::debug;
 
 pub struct ReportRepo {
     pool: Arc<Pool>,
+    read_pool: Arc<Pool>,
 }
 
 #[derive(Debug, PartialEq)]
@@ -32,7 +33,17 @@ pub struct Report {
 
 impl ReportRepo {
     pub fn new(pool: &Arc<Pool>) -> Self {
-        Self { pool: pool.clone() }
+        Self {
+            pool: pool.clone(),
+            read_pool: pool.clone(),
+        }
+    }
+
+    pub fn with_read_pool(self, read_pool: &Arc<Pool>) -> Self {
+        Self {
+            read_pool: read_pool.clone(),
+            ..self
+        }
     }
 
     #[cfg(test)]
@@ -53,7 +64,7 @@ impl ReportRepo {
 
     #[cfg(test)]
     pub async fn _select_all(&self, limit: Option<i64>) -> Result<Vec<Report>> {
-        self.pool
+        self.read_pool
             .get()
             .await?
             .interact(move |conn| Report::select_all(limit, conn))
@@ -66,24 +77,66 @@ impl ReportRepo {
         limit: Option<i64>,
     ) -> Result<Vec<Report>> {
         let updated_since = updated_since.clone();
-        self.pool
+        self.read_pool
             .get()
             .await?
             .interact(move |conn| Report::select_updated_since(&updated_since, limit, conn))
             .await?
     }
 
+    pub async fn select_max_updated_at(&self) -> Result<Option<OffsetDateTime>> {
+        self.read_pool
+            .get()
+            .await?
+            .interact(|conn| Report::select_max_updated_at(conn))
+            .await?
+    }
+
+    pub async fn select_by_filter(
+        &self,
+        area_url_alias: Option<&str>,
+        from: Option<Date>,
+        to: Option<Date>,
+        updated_since: &OffsetDateTime,
+        limit: Option<i64>,
+    ) -> Result<Vec<Report>> {
+        let area_url_alias = area_url_alias.map(|it| it.to_string());
+        let updated_since = updated_since.clone();
+        self.read_pool
+            .get()
+            .await?
+            .interact(move |conn| {
+                Report::select_by_filter(
+                    area_url_alias.as_deref(),
+                    from.as_ref(),
+                    to.as_ref(),
+                    &updated_since,
+                    limit,
+                    conn,
+                )
+            })
+            .await?
+    }
+
     pub async fn select_by_id(&self, id: i64) -> Result<Option<Report>> {
-        self.pool
+        self.read_pool
             .get()
             .await?
             .interact(move |conn| Report::select_by_id(id, conn))
             .await?
     }
 
-    #[cfg(test)]
+    pub async fn select_tag_series(&self, area_id: i64, tag: &str) -> Result<Vec<(Date, Value)>> {
+        let tag = tag.to_string();
+        self.read_pool
+            .get()
+            .await?
+            .interact(move |conn| Report::select_tag_series(area_id, &tag, conn))
+            .await?
+    }
+
     pub async fn select_latest_by_area_id(&self, area_id: i64) -> Result<Option<Report>> {
-        self.pool
+        self.read_pool
             .get()
             .await?
             .interact(move |conn| Report::select_latest_by_area_id(area_id, conn))
@@ -222,6 +275,97 @@ impl Report {
             .collect::<Result<Vec<Report>, _>>()?)
     }
 
+    // Date bounds are inclusive
+    // Newest change in the whole table, deleted rows included
+    pub fn select_max_updated_at(conn: &Connection) -> Result<Option<OffsetDateTime>> {
+        let query = r#"
+            SELECT max(updated_at)
+            FROM report
+        "#;
+        Ok(conn.query_row(query, [], |row| row.get(0))?)
+    }
+
+    pub fn select_by_filter(
+        area_url_alias: Option<&str>,
+        from: Option<&Date>,
+        to: Option<&Date>,
+        updated_since: &OffsetDateTime,
+        limit: Option<i64>,
+        conn: &Connection,
+    ) -> Result<Vec<Report>> {
+        let query = r#"
+            SELECT
+                r.rowid,
+                r.area_id,
+                json_extract(a.tags, '$.url_alias'),
+                r.date,
+                r.tags,
+                r.created_at,
+                r.updated_at,
+                r.deleted_at
+            FROM report r
+            LEFT JOIN area a ON a.rowid = r.area_id
+            WHERE r.updated_at > :updated_since
+            AND (:area_url_alias IS NULL OR json_extract(a.tags, '$.url_alias') = :area_url_alias)
+            AND (:from IS NULL OR r.date >= :from)
+            AND (:to IS NULL OR r.date <= :to)
+            ORDER BY r.updated_at, r.rowid
+            LIMIT :limit
+        "#;
+
+        Ok(conn
+            .prepare(query)?
+            .query_map(
+                named_params! {
+                    ":area_url_alias": area_url_alias,
+                    ":from": from.map(|it| it.to_string()),
+                    ":to": to.map(|it| it.to_string()),
+                    ":updated_since": updated_since.format(&Rfc3339)?,
+                    ":limit": limit.unwrap_or(i64::MAX),
+                },
+                mapper(),
+            )?
+            .collect::<Result<Vec<Report>, _>>()?)
+    }
+
+    // Reports missing the tag are skipped
+    pub fn select_tag_series(
+        area_id: i64,
+        tag: &str,
+        conn: &Connection,
+    ) -> Result<Vec<(Date, Value)>> {
+        let query = r#"
+            SELECT
+                date,
+                tags -> :path
+            FROM report
+            WHERE area_id = :area_id
+            AND deleted_at IS NULL
+            AND tags -> :path IS NOT NULL
+            ORDER BY date
+        "#;
+
+        let rows = conn
+            .prepare(query)?
+            .query_map(
+                named_params! {
+                    ":area_id": area_id,
+                    ":path": format!("$.\"{}\"", tag.replace('"', "")),
+                },
+                |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut series = vec![];
+        for (date, value) in rows {
+            series.push((
+                Date::parse(&date, &format_description!("[year]-[month]-[day]"))?,
+                serde_json::from_str(&value)?,
+            ));
+        }
+        Ok(series)
+    }
+
     pub fn select_by_date(
         date: &Date,
         limit: Option<i64>,
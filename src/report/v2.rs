@@ -1,40 +1,61 @@
 use super::Report;
+use crate::area::v2::parse_country_code;
+use crate::area::AreaRepo;
 use crate::report::model::ReportRepo;
+use crate::server::last_modified;
+use crate::server::strict;
+use crate::server::timestamp;
 use crate::Error;
 use actix_web::get;
+use actix_web::route;
 use actix_web::web::Data;
 use actix_web::web::Json;
 use actix_web::web::Path;
 use actix_web::web::Query;
 use actix_web::web::Redirect;
+use actix_web::CustomizeResponder;
 use actix_web::Either;
+use actix_web::HttpRequest;
 use serde::Deserialize;
 use serde::Serialize;
 use serde_json::Map;
 use serde_json::Value;
 use std::collections::HashMap;
-use time::format_description::well_known::Rfc3339;
+use time::macros::format_description;
+use time::Date;
 use time::Duration;
 use time::OffsetDateTime;
 
 #[derive(Deserialize)]
 pub struct GetArgs {
     #[serde(default)]
-    #[serde(with = "time::serde::rfc3339::option")]
+    #[serde(with = "crate::server::updated_since::option")]
     updated_since: Option<OffsetDateTime>,
     limit: Option<i64>,
     compress: Option<bool>,
+    area_id: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
 }
 
+const GET_ARGS: [&str; 6] = [
+    "updated_since",
+    "limit",
+    "compress",
+    "area_id",
+    "from",
+    "to",
+];
+
 #[derive(Serialize, Deserialize)]
 pub struct GetItem {
     pub id: i64,
     pub area_id: String,
     pub date: String,
     pub tags: Map<String, Value>,
-    #[serde(with = "time::serde::rfc3339")]
+    #[serde(with = "crate::server::timestamp")]
     pub created_at: OffsetDateTime,
-    #[serde(with = "time::serde::rfc3339")]
+    #[serde(with = "crate::server::timestamp")]
     pub updated_at: OffsetDateTime,
     pub deleted_at: String,
 }
@@ -56,7 +77,7 @@ impl Into<GetItem> for Report {
             updated_at: self.updated_at,
             deleted_at: self
                 .deleted_at
-                .map(|it| it.format(&Rfc3339).unwrap())
+                .map(|it| timestamp::format(&it))
                 .unwrap_or_default()
                 .into(),
         }
@@ -69,82 +90,131 @@ impl Into<Json<GetItem>> for Report {
     }
 }
 
-#[get("")]
+#[route("", method = "GET", method = "HEAD")]
 async fn get(
+    req: HttpRequest,
     args: Query<GetArgs>,
     repo: Data<ReportRepo>,
-) -> Result<Either<Json<Vec<GetItem>>, Redirect>, Error> {
-    if args.limit.is_none() && args.updated_since.is_none() {
+) -> Result<Either<CustomizeResponder<Json<Vec<GetItem>>>, Redirect>, Error> {
+    strict::check(req.query_string(), &GET_ARGS)?;
+    let filtered = args.area_id.is_some() || args.from.is_some() || args.to.is_some();
+
+    if args.limit.is_none() && args.updated_since.is_none() && !filtered {
         return Ok(Either::Right(
             Redirect::to("https://static.btcmap.org/api/v2/reports.json").permanent(),
         ));
     }
 
-    if args.compress.unwrap_or(false) {
-        let res: Vec<GetItem> = match &args.updated_since {
-            Some(updated_since) => repo
-                .select_updated_since(updated_since, args.limit)
-                .await?
-                .into_iter()
-                .map(|it| it.into())
-                .collect(),
-            None => repo
-                .select_updated_since(
-                    &OffsetDateTime::now_utc()
-                        .checked_sub(Duration::days(7))
-                        .unwrap(),
-                    args.limit,
-                )
-                .await?
-                .into_iter()
-                .map(|it| it.into())
-                .collect(),
-        };
+    let from = args.from.as_deref().map(parse_date).transpose()?;
+    let to = args.to.as_deref().map(parse_date).transpose()?;
 
-        let mut map: HashMap<String, Vec<GetItem>> = HashMap::new();
+    // Unfiltered requests are sync requests, so they only need recent changes by default
+    let updated_since = match args.updated_since {
+        Some(updated_since) => updated_since,
+        None if filtered => OffsetDateTime::UNIX_EPOCH,
+        None => OffsetDateTime::now_utc()
+            .checked_sub(Duration::days(7))
+            .unwrap(),
+    };
 
-        for item in res {
-            if !map.contains_key(&item.area_id) {
-                map.insert(item.area_id.clone(), vec![]);
-            }
+    let res: Vec<GetItem> = repo
+        .select_by_filter(
+            args.area_id.as_deref(),
+            from,
+            to,
+            &updated_since,
+            args.limit,
+        )
+        .await?
+        .into_iter()
+        .map(|it| it.into())
+        .collect();
 
-            let prev_entries = map.get_mut(&item.area_id).unwrap();
+    // The default window of sync requests moves with time, so it can lose reports without any
+    // change to the table
+    let last_modified = match args.updated_since.is_some() || filtered {
+        true => repo.select_max_updated_at().await?,
+        false => None,
+    };
 
-            if prev_entries.last().is_none() || prev_entries.last().unwrap().tags != item.tags {
-                prev_entries.push(item);
-            }
+    if !args.compress.unwrap_or(false) {
+        return Ok(Either::Left(last_modified::customize(
+            Json(res),
+            last_modified,
+        )));
+    }
+
+    let mut map: HashMap<String, Vec<GetItem>> = HashMap::new();
+
+    for item in res {
+        if !map.contains_key(&item.area_id) {
+            map.insert(item.area_id.clone(), vec![]);
         }
 
-        let mut compressed_res: Vec<GetItem> = vec![];
+        let prev_entries = map.get_mut(&item.area_id).unwrap();
 
-        for (_, mut v) in map {
-            compressed_res.append(&mut v);
+        if prev_entries.last().is_none() || prev_entries.last().unwrap().tags != item.tags {
+            prev_entries.push(item);
         }
+    }
 
-        compressed_res.sort_by_key(|it| it.updated_at);
-
-        Ok(Either::Left(Json(compressed_res)))
-    } else {
-        Ok(Either::Left(Json(match &args.updated_since {
-            Some(updated_since) => repo
-                .select_updated_since(updated_since, args.limit)
-                .await?
-                .into_iter()
-                .map(|it| it.into())
-                .collect(),
-            None => repo
-                .select_updated_since(
-                    &OffsetDateTime::now_utc()
-                        .checked_sub(Duration::days(7))
-                        .unwrap(),
-                    args.limit,
-                )
-                .await?
-                .into_iter()
-                .map(|it| it.into())
-                .collect(),
-        })))
+    let mut compressed_res: Vec<GetItem> = vec![];
+
+    for (_, mut v) in map {
+        compressed_res.append(&mut v);
     }
+
+    compressed_res.sort_by_key(|it| it.updated_at);
+
+    Ok(Either::Left(last_modified::customize(
+        Json(compressed_res),
+        last_modified,
+    )))
+}
+
+pub fn parse_date(date: &str) -> Result<Date, Error> {
+    Date::parse(date, format_description!("[year]-[month]-[day]"))
+        .map_err(|_| Error::HttpBadRequest(format!("Invalid date: {date}, expected YYYY-MM-DD")))
+}
+
+#[get("country/{code}")]
+pub async fn get_by_country(
+    code: Path<String>,
+    area_repo: Data<AreaRepo>,
+    report_repo: Data<ReportRepo>,
+) -> Result<Json<GetItem>, Error> {
+    let code = parse_country_code(&code)?;
+    let area = area_repo
+        .select_by_url_alias(&code.to_lowercase())
+        .await?
+        .ok_or(Error::HttpNotFound(format!(
+            "Country with code = {code} doesn't exist"
+        )))?;
+    report_repo
+        .select_latest_by_area_id(area.id)
+        .await?
+        .map(|it| it.into())
+        .ok_or(Error::HttpNotFound(format!(
+            "There are no reports for country with code = {code}"
+        )))
+}
+
+#[get("global/latest")]
+pub async fn get_global_latest(
+    area_repo: Data<AreaRepo>,
+    report_repo: Data<ReportRepo>,
+) -> Result<Json<GetItem>, Error> {
+    let area = area_repo
+        .select_by_url_alias("earth")
+        .await?
+        .ok_or(Error::HttpNotFound("Global area doesn't exist".into()))?;
+    report_repo
+        .select_latest_by_area_id(area.id)
+        .await?
+        .map(|it| it.into())
+        .ok_or(Error::HttpNotFound(
+            "There are no global reports yet".into(),
+        ))
 }
 
 #[get("{id}")]
@@ -161,7 +231,7 @@ pub async fn get_by_id(id: Path<i64>, repo: Data<ReportRepo>) -> Result<Json<Get
 #[cfg(test)]
 mod test {
     use crate::report::v2::GetItem;
-    use crate::test::mock_state;
+    use crate::test::{mock_state, State};
     use crate::Result;
     use actix_web::test::TestRequest;
     use actix_web::web::{scope, Data};
@@ -207,6 +277,39 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    async fn get_by_country() -> Result<()> {
+        let state = mock_state().await;
+        let mut area_tags = Map::new();
+        area_tags.insert("url_alias".into(), "de".into());
+        let area = state.area_repo.insert(&area_tags).await?;
+        state
+            .report_repo
+            .insert(area.id, &date!(2023 - 05 - 06), &Map::new())
+            .await?;
+        let latest = state
+            .report_repo
+            .insert(area.id, &date!(2023 - 05 - 07), &Map::new())
+            .await?;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.area_repo))
+                .app_data(Data::new(state.report_repo))
+                .service(super::get_by_country),
+        )
+        .await;
+        let req = TestRequest::get().uri("/country/DE").to_request();
+        let res: GetItem = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(latest.id, res.id);
+        let req = TestRequest::get().uri("/country/fr").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(404, res.status().as_u16());
+        let req = TestRequest::get().uri("/country/d3").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(400, res.status().as_u16());
+        Ok(())
+    }
+
     #[test]
     async fn get_with_limit() -> Result<()> {
         let state = mock_state().await;
@@ -272,4 +375,130 @@ mod test {
         assert_eq!(res.len(), 1);
         Ok(())
     }
+
+    async fn mock_filter_reports(state: &State) -> Result<()> {
+        for url_alias in ["de", "fr"] {
+            let mut area_tags = Map::new();
+            area_tags.insert("url_alias".into(), url_alias.into());
+            let area = state.area_repo.insert(&area_tags).await?;
+            for date in [
+                date!(2023 - 05 - 06),
+                date!(2023 - 05 - 07),
+                date!(2023 - 05 - 08),
+            ] {
+                let report = state
+                    .report_repo
+                    .insert(area.id, &date, &Map::new())
+                    .await?;
+                state
+                    .report_repo
+                    .set_updated_at(report.id, &datetime!(2023-05-09 00:00:00 UTC))
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    async fn get_by_area_id() -> Result<()> {
+        let state = mock_state().await;
+        mock_filter_reports(&state).await?;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.report_repo))
+                .service(scope("/").service(super::get)),
+        )
+        .await;
+        let req = TestRequest::get().uri("/?area_id=fr").to_request();
+        let res: Vec<GetItem> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(3, res.len());
+        assert!(res.iter().all(|it| it.area_id == "fr"));
+        Ok(())
+    }
+
+    #[test]
+    async fn get_from() -> Result<()> {
+        let state = mock_state().await;
+        mock_filter_reports(&state).await?;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.report_repo))
+                .service(scope("/").service(super::get)),
+        )
+        .await;
+        let req = TestRequest::get().uri("/?from=2023-05-07").to_request();
+        let res: Vec<GetItem> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(4, res.len());
+        assert!(res.iter().all(|it| it.date.as_str() >= "2023-05-07"));
+        let req = TestRequest::get().uri("/?from=yesterday").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(400, res.status().as_u16());
+        Ok(())
+    }
+
+    #[test]
+    async fn get_to() -> Result<()> {
+        let state = mock_state().await;
+        mock_filter_reports(&state).await?;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.report_repo))
+                .service(scope("/").service(super::get)),
+        )
+        .await;
+        let req = TestRequest::get().uri("/?to=2023-05-06").to_request();
+        let res: Vec<GetItem> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(2, res.len());
+        assert!(res.iter().all(|it| it.date == "2023-05-06"));
+        Ok(())
+    }
+
+    #[test]
+    async fn get_with_filter_and_limit() -> Result<()> {
+        let state = mock_state().await;
+        mock_filter_reports(&state).await?;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.report_repo))
+                .service(scope("/").service(super::get)),
+        )
+        .await;
+        let req = TestRequest::get()
+            .uri("/?area_id=de&from=2023-05-07&to=2023-05-08&limit=1")
+            .to_request();
+        let res: Vec<GetItem> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(1, res.len());
+        assert_eq!("de", res[0].area_id);
+        Ok(())
+    }
+
+    #[test]
+    async fn get_global_latest() -> Result<()> {
+        let state = mock_state().await;
+        let mut area_tags = Map::new();
+        area_tags.insert("url_alias".into(), "earth".into());
+        let area = state.area_repo.insert(&area_tags).await?;
+        let report_repo = Data::new(state.report_repo);
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.area_repo))
+                .app_data(report_repo.clone())
+                .service(super::get_global_latest),
+        )
+        .await;
+        let req = TestRequest::get().uri("/global/latest").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(404, res.status().as_u16());
+        report_repo
+            .insert(area.id, &date!(2023 - 05 - 06), &Map::new())
+            .await?;
+        let latest = report_repo
+            .insert(area.id, &date!(2023 - 05 - 07), &Map::new())
+            .await?;
+        let req = TestRequest::get().uri("/global/latest").to_request();
+        let res: GetItem = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(latest.id, res.id);
+        assert_eq!("2023-05-07", res.date);
+        Ok(())
+    }
 }
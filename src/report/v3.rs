@@ -1,11 +1,13 @@
 use super::Report;
 use crate::report::model::ReportRepo;
+use crate::server::strict;
 use crate::Error;
 use actix_web::get;
 use actix_web::web::Data;
 use actix_web::web::Json;
 use actix_web::web::Path;
 use actix_web::web::Query;
+use actix_web::HttpRequest;
 use serde::Deserialize;
 use serde::Serialize;
 use serde_json::Map;
@@ -19,6 +21,8 @@ pub struct GetArgs {
     limit: i64,
 }
 
+const GET_ARGS: [&str; 2] = ["updated_since", "limit"];
+
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
 pub struct GetItem {
     pub id: i64,
@@ -71,7 +75,12 @@ impl Into<Json<GetItem>> for Report {
 }
 
 #[get("")]
-async fn get(args: Query<GetArgs>, repo: Data<ReportRepo>) -> Result<Json<Vec<GetItem>>, Error> {
+async fn get(
+    req: HttpRequest,
+    args: Query<GetArgs>,
+    repo: Data<ReportRepo>,
+) -> Result<Json<Vec<GetItem>>, Error> {
+    strict::check(req.query_string(), &GET_ARGS)?;
     Ok(Json(
         repo.select_updated_since(&args.updated_since, Some(args.limit))
             .await?
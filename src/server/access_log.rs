@@ -0,0 +1,127 @@
+use std::env;
+use tracing::info;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AccessLogFormat {
+    Text,
+    Json,
+}
+
+impl AccessLogFormat {
+    // Set ACCESS_LOG_FORMAT=json to get one JSON object per line
+    pub fn from_env() -> AccessLogFormat {
+        match env::var("ACCESS_LOG_FORMAT").as_deref() {
+            Ok("json") => AccessLogFormat::Json,
+            _ => AccessLogFormat::Text,
+        }
+    }
+}
+
+pub struct AccessLogRecord {
+    pub method: String,
+    pub path: String,
+    pub query_string: String,
+    pub status: u16,
+    pub latency_sec: f64,
+    pub bytes: Option<u64>,
+}
+
+impl AccessLogRecord {
+    pub fn to_text(&self) -> String {
+        let path = if self.query_string.is_empty() {
+            self.path.clone()
+        } else {
+            format!("{}?{}", self.path, self.query_string)
+        };
+        let bytes = self.bytes.map(|it| it.to_string()).unwrap_or("-".into());
+        format!(
+            "{} {} {} {:.3}s {}",
+            self.method, path, self.status, self.latency_sec, bytes,
+        )
+    }
+
+    // Goes through tracing like the rest of the logs, so it can be filtered by the access_log target.
+    // JSON records are passed as fields, so they end up as fields of the JSON log line
+    pub fn log(&self, format: AccessLogFormat) {
+        match format {
+            AccessLogFormat::Text => info!(target: "access_log", "{}", self.to_text()),
+            AccessLogFormat::Json => info!(
+                target: "access_log",
+                method = self.method.as_str(),
+                path = self.path.as_str(),
+                query_string = self.query_string.as_str(),
+                status = self.status,
+                latency_sec = self.latency_sec,
+                bytes = self.bytes,
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AccessLogFormat, AccessLogRecord};
+    use serde_json::Value;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::fmt::MakeWriter;
+    use tracing_subscriber::util::SubscriberInitExt;
+
+    fn record() -> AccessLogRecord {
+        AccessLogRecord {
+            method: "GET".into(),
+            path: "/v2/elements".into(),
+            query_string: "limit=1".into(),
+            status: 200,
+            latency_sec: 0.0123,
+            bytes: Some(42),
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for Buffer {
+        type Writer = Buffer;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    #[test]
+    fn log_json() {
+        let buffer = Buffer::default();
+        let subscriber = tracing_subscriber::fmt()
+            .json()
+            .with_writer(buffer.clone())
+            .finish();
+        let guard = subscriber.set_default();
+        record().log(AccessLogFormat::Json);
+        drop(guard);
+        let line = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let json: Value = serde_json::from_str(line.trim()).unwrap();
+        assert_eq!("access_log", json["target"]);
+        let fields = &json["fields"];
+        assert_eq!("GET", fields["method"]);
+        assert_eq!("/v2/elements", fields["path"]);
+        assert_eq!(200, fields["status"]);
+        assert_eq!(0.0123, fields["latency_sec"]);
+        assert_eq!(42, fields["bytes"]);
+    }
+
+    #[test]
+    fn to_text() {
+        assert_eq!("GET /v2/elements?limit=1 200 0.012s 42", record().to_text());
+    }
+}
@@ -0,0 +1,123 @@
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::ServiceResponse;
+use actix_web::http::header::{
+    HeaderValue, HttpDate, LastModified, IF_MODIFIED_SINCE, LAST_MODIFIED,
+};
+use actix_web::http::StatusCode;
+use actix_web::{CustomizeResponder, Responder};
+use std::time::SystemTime;
+use time::{Duration, OffsetDateTime};
+
+// List endpoints pass the newest updated_at of the whole table rather than of the returned page,
+// so rows leaving a page or a filter still move the date. Lists which can change just because time
+// passes have no reliable date and pass None
+pub fn customize<R: Responder>(
+    res: R,
+    last_modified: Option<OffsetDateTime>,
+) -> CustomizeResponder<R> {
+    match last_modified.and_then(|it| http_date(it, OffsetDateTime::now_utc())) {
+        Some(last_modified) => res.customize().insert_header(LastModified(last_modified)),
+        None => res.customize(),
+    }
+}
+
+// HTTP dates have no fractions of a second, so the date is rounded up. Until that second is over,
+// rows can still change without moving the date, so such responses have no header
+fn http_date(last_modified: OffsetDateTime, now: OffsetDateTime) -> Option<HttpDate> {
+    let last_modified = match last_modified.nanosecond() {
+        0 => last_modified,
+        nanos => last_modified + Duration::nanoseconds(1_000_000_000 - nanos as i64),
+    };
+    if last_modified > now {
+        return None;
+    }
+    Some(HttpDate::from(SystemTime::from(last_modified)))
+}
+
+// Clients which already have the latest version get an empty 304 instead of the full list
+pub fn not_modified<B: MessageBody + 'static>(res: ServiceResponse<B>) -> ServiceResponse<BoxBody> {
+    let last_modified = parse_date(res.headers().get(LAST_MODIFIED));
+    let if_modified_since = parse_date(res.request().headers().get(IF_MODIFIED_SINCE));
+    match (last_modified, if_modified_since) {
+        (Some(last_modified), Some(if_modified_since))
+            if res.status() == StatusCode::OK && last_modified <= if_modified_since =>
+        {
+            let mut res = res.map_body(|_, _| BoxBody::new(()));
+            *res.response_mut().status_mut() = StatusCode::NOT_MODIFIED;
+            res
+        }
+        _ => res.map_into_boxed_body(),
+    }
+}
+
+fn parse_date(value: Option<&HeaderValue>) -> Option<SystemTime> {
+    value
+        .and_then(|it| it.to_str().ok())
+        .and_then(|it| it.parse::<HttpDate>().ok())
+        .map(SystemTime::from)
+}
+
+#[cfg(test)]
+mod test {
+    use actix_web::dev::Service;
+    use actix_web::http::header::{IF_MODIFIED_SINCE, LAST_MODIFIED};
+    use actix_web::test::TestRequest;
+    use actix_web::web::Json;
+    use actix_web::{get, test, App, Responder};
+    use futures_util::future::FutureExt;
+    use time::macros::datetime;
+
+    #[test]
+    async fn http_date() {
+        let now = datetime!(2024-01-02 03:04:10 UTC);
+        let date = |it| super::http_date(it, now).map(|it| it.to_string());
+        assert_eq!(
+            Some("Tue, 02 Jan 2024 03:04:05 GMT".into()),
+            date(datetime!(2024-01-02 03:04:05 UTC)),
+        );
+        assert_eq!(
+            Some("Tue, 02 Jan 2024 03:04:06 GMT".into()),
+            date(datetime!(2024-01-02 03:04:05.001 UTC)),
+        );
+        assert_eq!(
+            Some("Tue, 02 Jan 2024 03:04:10 GMT".into()),
+            date(datetime!(2024-01-02 03:04:09.5 UTC)),
+        );
+        assert_eq!(None, date(datetime!(2024-01-02 03:04:10.5 UTC)));
+    }
+
+    #[get("/")]
+    async fn get() -> impl Responder {
+        super::customize(Json(vec![1, 2]), Some(datetime!(2024-01-02 03:04:05 UTC)))
+    }
+
+    #[test]
+    async fn not_modified() {
+        let app = test::init_service(
+            App::new()
+                .wrap_fn(|req, srv| srv.call(req).map(|res| res.map(super::not_modified)))
+                .service(get),
+        )
+        .await;
+        let req = TestRequest::get().uri("/").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(200, res.status().as_u16());
+        assert_eq!(
+            "Tue, 02 Jan 2024 03:04:05 GMT",
+            res.headers().get(LAST_MODIFIED).unwrap()
+        );
+        let req = TestRequest::get()
+            .uri("/")
+            .insert_header((IF_MODIFIED_SINCE, "Tue, 02 Jan 2024 03:04:05 GMT"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(304, res.status().as_u16());
+        assert!(test::read_body(res).await.is_empty());
+        let req = TestRequest::get()
+            .uri("/")
+            .insert_header((IF_MODIFIED_SINCE, "Mon, 01 Jan 2024 00:00:00 GMT"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(200, res.status().as_u16());
+    }
+}
@@ -0,0 +1,46 @@
+// Used when a list request has no limit or its limit isn't positive
+pub const DEFAULT_LIMIT: i64 = 1000;
+
+// Keeps a single request from loading a whole table into memory
+pub const MAX_LIMIT: i64 = 5000;
+
+pub fn resolve_limit(raw: Option<i64>) -> i64 {
+    match raw {
+        Some(limit) if limit > 0 => limit.min(MAX_LIMIT),
+        _ => DEFAULT_LIMIT,
+    }
+}
+
+// Clients sync by passing the newest updated_at they have, which isn't a unique cursor. Rows sharing
+// a timestamp at a page boundary would be skipped forever, so these requests have no default limit
+pub fn resolve_sync_limit(raw: Option<i64>) -> Option<i64> {
+    raw.filter(|it| *it > 0).map(|it| it.min(MAX_LIMIT))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DEFAULT_LIMIT, MAX_LIMIT};
+
+    #[test]
+    fn resolve_limit() {
+        assert_eq!(DEFAULT_LIMIT, super::resolve_limit(None));
+        assert_eq!(DEFAULT_LIMIT, super::resolve_limit(Some(i64::MIN)));
+        assert_eq!(DEFAULT_LIMIT, super::resolve_limit(Some(-1)));
+        assert_eq!(DEFAULT_LIMIT, super::resolve_limit(Some(0)));
+        assert_eq!(1, super::resolve_limit(Some(1)));
+        assert_eq!(MAX_LIMIT - 1, super::resolve_limit(Some(MAX_LIMIT - 1)));
+        assert_eq!(MAX_LIMIT, super::resolve_limit(Some(MAX_LIMIT)));
+        assert_eq!(MAX_LIMIT, super::resolve_limit(Some(MAX_LIMIT + 1)));
+        assert_eq!(MAX_LIMIT, super::resolve_limit(Some(i64::MAX)));
+    }
+
+    #[test]
+    fn resolve_sync_limit() {
+        assert_eq!(None, super::resolve_sync_limit(None));
+        assert_eq!(None, super::resolve_sync_limit(Some(i64::MIN)));
+        assert_eq!(None, super::resolve_sync_limit(Some(-1)));
+        assert_eq!(None, super::resolve_sync_limit(Some(0)));
+        assert_eq!(Some(1), super::resolve_sync_limit(Some(1)));
+        assert_eq!(Some(MAX_LIMIT), super::resolve_sync_limit(Some(i64::MAX)));
+    }
+}
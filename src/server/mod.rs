@@ -4,29 +4,77 @@ use crate::auth::AuthService;
 use crate::element::ElementRepo;
 use crate::event::model::EventRepo;
 use crate::report::model::ReportRepo;
+use crate::sync::status::SyncStatusRepo;
 use crate::user::UserRepo;
-use crate::{area, element, error, user};
-use crate::{event, tile};
+use crate::{area, element, error, sync, user};
+use crate::{event, openapi, tile};
 use crate::{report, Result};
+use access_log::{AccessLogFormat, AccessLogRecord};
 use actix_governor::{Governor, GovernorConfigBuilder, KeyExtractor, SimpleKeyExtractionError};
-use actix_web::dev::{Service, ServiceRequest};
+use actix_web::body::{BodySize, MessageBody};
+use actix_web::dev::{Server, Service, ServiceRequest};
 use actix_web::http::header::HeaderValue;
+use actix_web::rt::signal;
 use actix_web::web::scope;
+use actix_web::web::to;
+use actix_web::web::JsonConfig;
 use actix_web::web::QueryConfig;
+use actix_web::web::ServiceConfig;
 use actix_web::{
     middleware::{Compress, NormalizePath},
     web::Data,
     App, HttpServer,
 };
+use deadpool_sqlite::Pool;
 use futures_util::future::FutureExt;
+use std::future::{pending, Future};
+use std::net::TcpListener;
 use std::sync::Arc;
 use time::OffsetDateTime;
-use tracing::info;
+use tracing::{error, info};
+
+mod access_log;
+pub mod last_modified;
+pub mod limit;
+pub mod strict;
+pub mod timestamp;
+pub mod updated_since;
+pub mod version;
+
+// Total number of rows matching list filters, regardless of limit
+pub const TOTAL_COUNT_HEADER: &str = "X-Total-Count";
+
+// Tag edits are small, areas need more room because of geo_json
+pub const MAX_JSON_PAYLOAD: usize = 256 * 1024;
+const MAX_AREA_JSON_PAYLOAD: usize = 2 * 1024 * 1024;
+
+// Gives in-flight requests such as tag patches a chance to finish during deploys
+const SHUTDOWN_TIMEOUT_SECS: u64 = 30;
 
 pub async fn run() -> Result<()> {
-    // All the worker threads are sharing a single connection pool
+    // All the worker threads are sharing the same connection pools
     let pool = Arc::new(db::pool()?);
+    // Read-only connections can't set up WAL, so the writer should open the database first
+    pool.get().await?;
+    let read_pool = Arc::new(db::read_only_pool()?);
+    let listener = TcpListener::bind(("127.0.0.1", 8000))?;
+    serve(
+        server(pool, read_pool, listener, |_| {})?,
+        wait_for_shutdown_signal(),
+    )
+    .await
+}
 
+// Extra routes are only used by tests, so they can exercise the same server setup as production
+fn server<R>(
+    pool: Arc<Pool>,
+    read_pool: Arc<Pool>,
+    listener: TcpListener,
+    extra_routes: R,
+) -> Result<Server>
+where
+    R: Fn(&mut ServiceConfig) + Send + Clone + 'static,
+{
     let rate_limit_conf = GovernorConfigBuilder::default()
         .per_second(1)
         .burst_size(30)
@@ -41,15 +89,21 @@ pub async fn run() -> Result<()> {
         .finish()
         .unwrap();
 
-    HttpServer::new(move || {
-        let auth_service = AuthService::new(&pool);
-        let area_repo = AreaRepo::new(&pool);
-        let element_repo = ElementRepo::new(&pool);
-        let event_repo = EventRepo::new(&pool);
-        let report_repo = ReportRepo::new(&pool);
-        let user_repo = UserRepo::new(&pool);
+    let server = HttpServer::new(move || {
+        let auth_service = AuthService::new(&pool).with_read_pool(&read_pool);
+        let area_repo = AreaRepo::new(&pool).with_read_pool(&read_pool);
+        let element_repo = ElementRepo::new(&pool).with_read_pool(&read_pool);
+        let event_repo = EventRepo::new(&pool).with_read_pool(&read_pool);
+        let report_repo = ReportRepo::new(&pool).with_read_pool(&read_pool);
+        let user_repo = UserRepo::new(&pool).with_read_pool(&read_pool);
+        let sync_status_repo = SyncStatusRepo::new(&read_pool);
+        let access_log_format = AccessLogFormat::from_env();
         App::new()
             .wrap_fn(|req, srv| {
+                srv.call(req)
+                    .map(|res| res.map(last_modified::not_modified))
+            })
+            .wrap_fn(move |req, srv| {
                 let req_query_string = req.query_string().to_string();
                 let req_method = req.method().as_str().to_string();
                 let req_path = req.path().to_string();
@@ -69,6 +123,19 @@ pub async fn run() -> Result<()> {
                     if let Ok(res) = res.as_ref() {
                         let res_status = res.status().as_u16();
                         let res_time_sec = (OffsetDateTime::now_utc() - req_time).as_seconds_f64();
+                        let res_bytes = match res.response().body().size() {
+                            BodySize::Sized(bytes) => Some(bytes),
+                            _ => None,
+                        };
+                        AccessLogRecord {
+                            method: req_method.clone(),
+                            path: req_path.clone(),
+                            query_string: req_query_string.clone(),
+                            status: res_status,
+                            latency_sec: res_time_sec,
+                            bytes: res_bytes,
+                        }
+                        .log(access_log_format);
                         if res_time_sec > 5.0 {
                             info!(
                                 req_query_string,
@@ -93,22 +160,42 @@ pub async fn run() -> Result<()> {
             .app_data(Data::new(event_repo))
             .app_data(Data::new(report_repo))
             .app_data(Data::new(user_repo))
+            .app_data(Data::new(sync_status_repo))
             .app_data(QueryConfig::default().error_handler(error::query_error_handler))
+            .app_data(json_config(MAX_JSON_PAYLOAD))
+            .default_service(to(error::not_found_handler))
+            .configure(extra_routes.clone())
             .service(
                 scope("tiles")
                     .wrap(Governor::new(&tile_rate_limit_conf))
                     .service(tile::controller::get),
             )
+            .service(openapi::controller::get)
+            .service(
+                scope("feeds")
+                    .wrap(Governor::new(&rate_limit_conf))
+                    .service(event::feed::get_events),
+            )
             .service(
                 scope("v2")
                     .wrap(Governor::new(&rate_limit_conf))
+                    .service(element::v2::get_ndjson)
                     .service(
                         scope("elements")
                             .service(element::admin::patch)
+                            .service(element::admin::undelete)
+                            .service(element::admin::verify)
                             .service(element::admin::post_tags)
                             .service(element::admin::patch_tags)
                             .service(element::v2::get)
-                            .service(element::v2::get_by_osm_type_and_id),
+                            .service(element::v2::search)
+                            .service(element::v2::get_batch)
+                            .service(element::v2::get_heatmap)
+                            .service(element::v2::get_stats)
+                            .service(element::v2::get_by_osm_type_and_id)
+                            .service(element::v2::get_diff)
+                            .service(element::v2::get_tag_history)
+                            .service(element::v2::get_areas),
                     )
                     .service(
                         scope("events")
@@ -123,17 +210,35 @@ pub async fn run() -> Result<()> {
                     )
                     .service(
                         scope("areas")
+                            .app_data(json_config(MAX_AREA_JSON_PAYLOAD))
                             .service(area::admin::post)
                             .service(area::admin::patch)
                             .service(area::admin::delete)
+                            .service(area::admin::delete_tag)
                             .service(area::v2::get)
+                            .service(area::v2::search)
+                            .service(area::v2::get_containing)
+                            .service(area::v2::get_events)
+                            .service(area::v2::get_elements)
+                            .service(area::v2::get_stats)
+                            .service(area::v2::get_report_series)
+                            .service(area::v2::get_report_changes)
                             .service(area::v2::get_by_url_alias),
                     )
                     .service(
                         scope("reports")
                             .service(report::v2::get)
+                            .service(report::v2::get_by_country)
+                            .service(report::v2::get_global_latest)
                             .service(report::v2::get_by_id),
-                    ),
+                    )
+                    .service(
+                        scope("tiles")
+                            .service(tile::v2::get)
+                            .service(tile::v2::get_mvt),
+                    )
+                    .service(scope("sync").service(sync::v2::get_status))
+                    .service(version::get),
             )
             .service(
                 scope("v3")
@@ -146,9 +251,11 @@ pub async fn run() -> Result<()> {
                     )
                     .service(
                         scope("areas")
+                            .app_data(json_config(MAX_AREA_JSON_PAYLOAD))
                             .service(area::admin::post)
                             .service(area::admin::patch)
                             .service(area::admin::delete)
+                            .service(area::admin::delete_tag)
                             .service(area::v3::get),
                     )
                     .service(
@@ -166,12 +273,20 @@ pub async fn run() -> Result<()> {
             .service(
                 scope("")
                     .wrap(Governor::new(&rate_limit_conf))
+                    .service(element::v2::get_ndjson)
                     .service(
                         scope("elements")
                             .service(element::admin::post_tags)
                             .service(element::admin::patch_tags)
                             .service(element::v2::get)
-                            .service(element::v2::get_by_osm_type_and_id),
+                            .service(element::v2::search)
+                            .service(element::v2::get_batch)
+                            .service(element::v2::get_heatmap)
+                            .service(element::v2::get_stats)
+                            .service(element::v2::get_by_osm_type_and_id)
+                            .service(element::v2::get_diff)
+                            .service(element::v2::get_tag_history)
+                            .service(element::v2::get_areas),
                     )
                     .service(
                         scope("events")
@@ -186,26 +301,91 @@ pub async fn run() -> Result<()> {
                     )
                     .service(
                         scope("areas")
+                            .app_data(json_config(MAX_AREA_JSON_PAYLOAD))
                             .service(area::admin::post)
                             .service(area::admin::patch)
                             .service(area::admin::delete)
+                            .service(area::admin::delete_tag)
                             .service(area::v2::get)
+                            .service(area::v2::search)
+                            .service(area::v2::get_containing)
+                            .service(area::v2::get_events)
+                            .service(area::v2::get_elements)
+                            .service(area::v2::get_stats)
+                            .service(area::v2::get_report_series)
+                            .service(area::v2::get_report_changes)
                             .service(area::v2::get_by_url_alias),
                     )
                     .service(
                         scope("reports")
                             .service(report::v2::get)
+                            .service(report::v2::get_by_country)
+                            .service(report::v2::get_global_latest)
                             .service(report::v2::get_by_id),
                     ),
             )
     })
-    .bind(("127.0.0.1", 8000))?
-    .run()
-    .await?;
+    .listen(listener)?
+    .shutdown_timeout(SHUTDOWN_TIMEOUT_SECS)
+    .disable_signals()
+    .run();
+
+    Ok(server)
+}
+
+// Once shutdown resolves, in-flight requests get up to SHUTDOWN_TIMEOUT_SECS to finish
+async fn serve(server: Server, shutdown: impl Future<Output = ()> + 'static) -> Result<()> {
+    let server_handle = server.handle();
+
+    actix_web::rt::spawn(async move {
+        shutdown.await;
+        info!(
+            timeout_secs = SHUTDOWN_TIMEOUT_SECS,
+            "Got shutdown signal, waiting for in-flight requests",
+        );
+        server_handle.stop(true).await;
+    });
+
+    server.await?;
+    info!("Server stopped");
 
     Ok(())
 }
 
+async fn wait_for_shutdown_signal() {
+    tokio::select! {
+        _ = wait_for_sigint() => info!("Got SIGINT"),
+        _ = wait_for_sigterm() => info!("Got SIGTERM"),
+    }
+}
+
+// A signal which can't be listened to never arrives, the server can still be stopped by the other one
+async fn wait_for_sigint() {
+    if let Err(e) = signal::ctrl_c().await {
+        error!(?e, "Failed to listen for SIGINT");
+        pending::<()>().await;
+    }
+}
+
+async fn wait_for_sigterm() {
+    match signal::unix::signal(signal::unix::SignalKind::terminate()) {
+        Ok(mut sigterm) => {
+            sigterm.recv().await;
+        }
+        Err(e) => {
+            error!(?e, "Failed to listen for SIGTERM");
+            pending::<()>().await;
+        }
+    }
+}
+
+// Oversized bodies are rejected with 413 before they're fully read
+pub fn json_config(limit: usize) -> JsonConfig {
+    JsonConfig::default()
+        .limit(limit)
+        .error_handler(error::json_error_handler)
+}
+
 #[cfg(not(debug_assertions))]
 pub fn get_key_extractor() -> RealIpKeyExtractor {
     RealIpKeyExtractor
@@ -232,3 +412,76 @@ impl KeyExtractor for RealIpKeyExtractor {
             })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::osm::overpass::OverpassElement;
+    use crate::test::mock_state;
+    use crate::Result;
+    use actix_web::rt::time::sleep;
+    use actix_web::{rt, web};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[actix_web::test]
+    async fn graceful_stop_waits_for_in_flight_requests() -> Result<()> {
+        let state = mock_state().await;
+        let started = Arc::new(AtomicBool::new(false));
+        let handler_started = started.clone();
+        let listener = TcpListener::bind(("127.0.0.1", 0))?;
+        let addr = listener.local_addr()?;
+        let server = super::server(state.pool.clone(), state.pool, listener, move |cfg| {
+            let started = handler_started.clone();
+            cfg.route(
+                "/slow",
+                web::get().to(move || {
+                    let started = started.clone();
+                    async move {
+                        started.store(true, Ordering::SeqCst);
+                        sleep(Duration::from_millis(500)).await;
+                        "done"
+                    }
+                }),
+            );
+        })?;
+        let res = rt::spawn(reqwest::get(format!("http://{addr}/slow")));
+        // Stands in for the shutdown signal, it arrives while the request is in flight
+        let shutdown = async move {
+            while !started.load(Ordering::SeqCst) {
+                sleep(Duration::from_millis(10)).await;
+            }
+        };
+        super::serve(server, shutdown).await?;
+        let res = res.await.unwrap()?;
+        assert_eq!(200, res.status().as_u16());
+        assert_eq!("done", res.text().await?);
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn head_has_same_headers_as_get() -> Result<()> {
+        let state = mock_state().await;
+        state.element_repo.insert(&OverpassElement::mock(1)).await?;
+        let listener = TcpListener::bind(("127.0.0.1", 0))?;
+        let addr = listener.local_addr()?;
+        let server = super::server(state.pool.clone(), state.pool, listener, |_| {})?;
+        let server_handle = server.handle();
+        rt::spawn(server);
+        let url = format!("http://{addr}/v2/elements?limit=100");
+        let client = reqwest::Client::new();
+        let get = client.get(&url).send().await?;
+        let head = client.head(&url).send().await?;
+        server_handle.stop(true).await;
+        assert_eq!(200, head.status().as_u16());
+        let mut get_headers = get.headers().clone();
+        let mut head_headers = head.headers().clone();
+        get_headers.remove("date");
+        head_headers.remove("date");
+        assert_eq!(get_headers, head_headers);
+        assert!(head_headers.contains_key("content-length"));
+        assert!(head.bytes().await?.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,39 @@
+use crate::Error;
+use actix_web::web::Query;
+
+pub const PARAM: &str = "strict";
+
+// Unknown query params are ignored by default, so old clients keep working when a param is
+// removed. Clients can pass strict=true to have typos like limti=10 rejected instead
+pub fn check(query_string: &str, known: &[&str]) -> Result<(), Error> {
+    let params = Query::<Vec<(String, String)>>::from_query(query_string)
+        .map_err(|e| Error::HttpBadRequest(e.to_string()))?
+        .into_inner();
+    if !params.iter().any(|(k, v)| k == PARAM && v == "true") {
+        return Ok(());
+    }
+    for (name, _) in &params {
+        if name != PARAM && !known.contains(&name.as_str()) {
+            Err(Error::HttpBadRequest(format!(
+                "Unknown query parameter: {name}, supported parameters: {}",
+                known.join(", "),
+            )))?
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn check() {
+        let known = ["updated_since", "limit"];
+        assert!(super::check("", &known).is_ok());
+        assert!(super::check("limit=1", &known).is_ok());
+        assert!(super::check("limti=1", &known).is_ok());
+        assert!(super::check("limti=1&strict=false", &known).is_ok());
+        assert!(super::check("limit=1&strict=true", &known).is_ok());
+        assert!(super::check("limti=1&strict=true", &known).is_err());
+        assert!(super::check("strict=true&limti=1", &known).is_err());
+    }
+}
@@ -0,0 +1,82 @@
+use serde::{Deserializer, Serializer};
+use time::format_description::FormatItem;
+use time::macros::format_description;
+use time::{OffsetDateTime, UtcOffset};
+
+// SQLite defaults store milliseconds while timestamps set from Rust have nanoseconds, so every
+// response timestamp is converted to UTC with millisecond precision
+const FORMAT: &[FormatItem<'static>] =
+    format_description!("[year]-[month]-[day]T[hour]:[minute]:[second].[subsecond digits:3]Z");
+
+pub fn format(value: &OffsetDateTime) -> String {
+    value.to_offset(UtcOffset::UTC).format(FORMAT).unwrap()
+}
+
+pub fn serialize<S: Serializer>(value: &OffsetDateTime, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format(value))
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<OffsetDateTime, D::Error> {
+    time::serde::rfc3339::deserialize(deserializer)
+}
+
+pub mod option {
+    use serde::{Deserializer, Serializer};
+    use time::OffsetDateTime;
+
+    pub fn serialize<S: Serializer>(
+        value: &Option<OffsetDateTime>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(value) => super::serialize(value, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<OffsetDateTime>, D::Error> {
+        time::serde::rfc3339::option::deserialize(deserializer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde::Serialize;
+    use serde_json::json;
+    use time::macros::datetime;
+    use time::OffsetDateTime;
+
+    #[test]
+    fn format() {
+        assert_eq!(
+            "2024-01-01T00:00:00.000Z",
+            super::format(&datetime!(2024-01-01 00:00 UTC)),
+        );
+        assert_eq!(
+            "2024-01-01T10:30:00.123Z",
+            super::format(&datetime!(2024-01-01 12:30:00.123456789 +02:00)),
+        );
+    }
+
+    #[test]
+    fn option() {
+        #[derive(Serialize)]
+        struct Item {
+            #[serde(with = "super::option")]
+            value: Option<OffsetDateTime>,
+        }
+        assert_eq!(
+            json!({ "value": "2024-01-01T10:30:00.123Z" }),
+            serde_json::to_value(Item {
+                value: Some(datetime!(2024-01-01 12:30:00.123456789 +02:00)),
+            })
+            .unwrap(),
+        );
+        assert_eq!(
+            json!({ "value": null }),
+            serde_json::to_value(Item { value: None }).unwrap(),
+        );
+    }
+}
@@ -0,0 +1,55 @@
+use time::format_description::well_known::Rfc3339;
+use time::macros::format_description;
+use time::{Date, OffsetDateTime};
+
+// Older clients send plain dates, which are treated as midnight UTC
+pub fn parse(value: &str) -> Option<OffsetDateTime> {
+    OffsetDateTime::parse(value, &Rfc3339).ok().or_else(|| {
+        Date::parse(value, format_description!("[year]-[month]-[day]"))
+            .ok()
+            .map(|it| it.midnight().assume_utc())
+    })
+}
+
+pub mod option {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer};
+    use time::OffsetDateTime;
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<OffsetDateTime>, D::Error> {
+        let Some(value) = Option::<String>::deserialize(deserializer)? else {
+            return Ok(None);
+        };
+        match super::parse(&value) {
+            Some(updated_since) => Ok(Some(updated_since)),
+            None => Err(D::Error::custom(format!(
+                "expected RFC 3339 datetime or YYYY-MM-DD date, got {value}"
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use time::macros::datetime;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            Some(datetime!(2022-01-10 00:00 UTC)),
+            super::parse("2022-01-10"),
+        );
+        assert_eq!(
+            Some(datetime!(2022-01-10 00:00 UTC)),
+            super::parse("2022-01-10T00:00:00Z"),
+        );
+        assert_eq!(
+            Some(datetime!(2022-01-10 03:00 UTC)),
+            super::parse("2022-01-10T05:00:00+02:00"),
+        );
+        assert_eq!(None, super::parse("2022-01-10 00:00"));
+        assert_eq!(None, super::parse("yesterday"));
+    }
+}
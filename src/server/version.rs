@@ -0,0 +1,47 @@
+use actix_web::get;
+use actix_web::web::Json;
+use serde::Deserialize;
+use serde::Serialize;
+use time::OffsetDateTime;
+
+// Both values are set by build.rs
+const GIT_COMMIT: &str = env!("GIT_COMMIT");
+const BUILD_TIMESTAMP: &str = env!("BUILD_TIMESTAMP");
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct GetItem {
+    pub version: String,
+    pub git_commit: String,
+    #[serde(with = "crate::server::timestamp")]
+    pub build_timestamp: OffsetDateTime,
+}
+
+#[get("version")]
+pub async fn get() -> Json<GetItem> {
+    let build_timestamp = BUILD_TIMESTAMP
+        .parse()
+        .ok()
+        .and_then(|it| OffsetDateTime::from_unix_timestamp(it).ok())
+        .unwrap_or(OffsetDateTime::UNIX_EPOCH);
+    Json(GetItem {
+        version: env!("CARGO_PKG_VERSION").into(),
+        git_commit: GIT_COMMIT.into(),
+        build_timestamp,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::GetItem;
+    use actix_web::test::TestRequest;
+    use actix_web::{test, App};
+
+    #[test]
+    async fn get() {
+        let app = test::init_service(App::new().service(super::get)).await;
+        let req = TestRequest::get().uri("/version").to_request();
+        let res: GetItem = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(env!("CARGO_PKG_VERSION"), res.version);
+        assert!(!res.git_commit.is_empty());
+    }
+}
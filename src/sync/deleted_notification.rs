@@ -0,0 +1,92 @@
+use crate::Result;
+use rusqlite::{named_params, Connection, OptionalExtension, Row};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tracing::debug;
+
+#[derive(PartialEq, Debug)]
+pub struct DeletedNotification {
+    pub element_id: i64,
+    pub notified_at: OffsetDateTime,
+}
+
+const TABLE: &str = "deleted_element_notification";
+const ALL_COLUMNS: &str = "element_id, notified_at";
+const COL_ELEMENT_ID: &str = "element_id";
+const COL_NOTIFIED_AT: &str = "notified_at";
+
+impl DeletedNotification {
+    pub fn upsert(element_id: i64, notified_at: &OffsetDateTime, conn: &Connection) -> Result<()> {
+        let query = format!(
+            r#"
+                INSERT INTO {TABLE} ({COL_ELEMENT_ID}, {COL_NOTIFIED_AT})
+                VALUES (:element_id, :notified_at)
+                ON CONFLICT ({COL_ELEMENT_ID}) DO UPDATE SET {COL_NOTIFIED_AT} = excluded.{COL_NOTIFIED_AT}
+            "#
+        );
+        debug!(query);
+        conn.execute(
+            &query,
+            named_params! {
+                ":element_id": element_id,
+                ":notified_at": notified_at.format(&Rfc3339)?,
+            },
+        )?;
+        Ok(())
+    }
+
+    pub fn select_by_element_id(
+        element_id: i64,
+        conn: &Connection,
+    ) -> Result<Option<DeletedNotification>> {
+        let query = format!(
+            r#"
+                SELECT {ALL_COLUMNS}
+                FROM {TABLE}
+                WHERE {COL_ELEMENT_ID} = :element_id
+            "#
+        );
+        debug!(query);
+        Ok(conn
+            .query_row(
+                &query,
+                named_params! { ":element_id": element_id },
+                Self::mapper(),
+            )
+            .optional()?)
+    }
+
+    const fn mapper() -> fn(&Row) -> rusqlite::Result<DeletedNotification> {
+        |row: &Row| -> rusqlite::Result<DeletedNotification> {
+            Ok(DeletedNotification {
+                element_id: row.get(0)?,
+                notified_at: row.get(1)?,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::DeletedNotification;
+    use crate::element::Element;
+    use crate::osm::overpass::OverpassElement;
+    use crate::{test::mock_conn, Result};
+    use time::macros::datetime;
+
+    #[test]
+    fn upsert() -> Result<()> {
+        let conn = mock_conn();
+        let element = Element::insert(&OverpassElement::mock(1), &conn)?;
+        assert!(DeletedNotification::select_by_element_id(element.id, &conn)?.is_none());
+        DeletedNotification::upsert(element.id, &datetime!(2024-05-01 00:00 UTC), &conn)?;
+        DeletedNotification::upsert(element.id, &datetime!(2024-05-03 00:00 UTC), &conn)?;
+        assert_eq!(
+            DeletedNotification {
+                element_id: element.id,
+                notified_at: datetime!(2024-05-03 00:00 UTC),
+            },
+            DeletedNotification::select_by_element_id(element.id, &conn)?.unwrap(),
+        );
+        Ok(())
+    }
+}
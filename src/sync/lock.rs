@@ -0,0 +1,142 @@
+use crate::Result;
+use rusqlite::{named_params, Connection, ErrorCode, OptionalExtension};
+use time::{format_description::well_known::Rfc3339, Duration, OffsetDateTime};
+use tracing::debug;
+
+const TABLE: &str = "lock";
+const COL_NAME: &str = "name";
+const COL_OWNER: &str = "owner";
+const COL_ACQUIRED_AT: &str = "acquired_at";
+
+// Returns the owner token if the lock was acquired, and None if it's held by someone else. Locks
+// older than stale_after are assumed to be left over by a crashed process and can be taken over
+pub fn acquire(
+    name: &str,
+    now: &OffsetDateTime,
+    stale_after: Duration,
+    conn: &Connection,
+) -> Result<Option<String>> {
+    let query = format!(
+        r#"
+            INSERT INTO {TABLE} ({COL_NAME}, {COL_OWNER}, {COL_ACQUIRED_AT})
+            VALUES (:name, hex(randomblob(16)), :acquired_at)
+            ON CONFLICT ({COL_NAME}) DO UPDATE SET
+                {COL_OWNER} = excluded.{COL_OWNER},
+                {COL_ACQUIRED_AT} = excluded.{COL_ACQUIRED_AT}
+            WHERE {COL_ACQUIRED_AT} < :stale_before
+            RETURNING {COL_OWNER}
+        "#
+    );
+    debug!(query);
+    // Whole seconds keep the dates in the same format, so they can be compared as strings
+    let now = now.replace_nanosecond(0).unwrap();
+    let res = conn
+        .query_row(
+            &query,
+            named_params! {
+                ":name": name,
+                ":acquired_at": now.format(&Rfc3339)?,
+                ":stale_before": (now - stale_after).format(&Rfc3339)?,
+            },
+            |row| row.get(0),
+        )
+        .optional();
+    match res {
+        Ok(owner) => Ok(owner),
+        // The lock holder might be in the middle of a long write transaction
+        Err(rusqlite::Error::SqliteFailure(e, _)) if e.code == ErrorCode::DatabaseBusy => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// Long running holders should call it between steps, so their lock doesn't look stale. Returns
+// false if the lock was taken over in the meantime
+pub fn refresh(name: &str, owner: &str, now: &OffsetDateTime, conn: &Connection) -> Result<bool> {
+    let query = format!(
+        r#"
+            UPDATE {TABLE}
+            SET {COL_ACQUIRED_AT} = :acquired_at
+            WHERE {COL_NAME} = :name AND {COL_OWNER} = :owner
+        "#
+    );
+    debug!(query);
+    let now = now.replace_nanosecond(0).unwrap();
+    let changes = conn.execute(
+        &query,
+        named_params! {
+            ":name": name,
+            ":owner": owner,
+            ":acquired_at": now.format(&Rfc3339)?,
+        },
+    )?;
+    Ok(changes == 1)
+}
+
+// A lock which was taken over belongs to someone else, so it's left as is
+pub fn release(name: &str, owner: &str, conn: &Connection) -> Result<()> {
+    let query = format!(
+        r#"
+            DELETE FROM {TABLE}
+            WHERE {COL_NAME} = :name AND {COL_OWNER} = :owner
+        "#
+    );
+    debug!(query);
+    conn.execute(&query, named_params! { ":name": name, ":owner": owner })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{test::mock_conn, Result};
+    use time::macros::datetime;
+    use time::Duration;
+
+    #[test]
+    fn acquire() -> Result<()> {
+        let conn = mock_conn();
+        let now = datetime!(2024-05-01 12:00 UTC);
+        let stale_after = Duration::hours(2);
+        let owner = super::acquire("sync", &now, stale_after, &conn)?.unwrap();
+        assert!(super::acquire("sync", &now, stale_after, &conn)?.is_none());
+        assert!(super::acquire("other", &now, stale_after, &conn)?.is_some());
+        super::release("sync", &owner, &conn)?;
+        assert!(super::acquire("sync", &now, stale_after, &conn)?.is_some());
+        let later = now + Duration::hours(1);
+        assert!(super::acquire("sync", &later, stale_after, &conn)?.is_none());
+        let much_later = now + Duration::hours(3);
+        assert!(super::acquire("sync", &much_later, stale_after, &conn)?.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn refresh() -> Result<()> {
+        let conn = mock_conn();
+        let now = datetime!(2024-05-01 12:00 UTC);
+        let stale_after = Duration::hours(2);
+        let owner = super::acquire("sync", &now, stale_after, &conn)?.unwrap();
+        let refreshed_at = now + Duration::hours(2);
+        assert!(super::refresh("sync", &owner, &refreshed_at, &conn)?);
+        let later = now + Duration::hours(3);
+        assert!(super::acquire("sync", &later, stale_after, &conn)?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn stale_holder() -> Result<()> {
+        let conn = mock_conn();
+        let now = datetime!(2024-05-01 12:00 UTC);
+        let stale_after = Duration::hours(2);
+        let stale_owner = super::acquire("sync", &now, stale_after, &conn)?.unwrap();
+        let later = now + Duration::hours(3);
+        let owner = super::acquire("sync", &later, stale_after, &conn)?.unwrap();
+        assert_ne!(stale_owner, owner);
+        // The slow holder wakes up, it can't extend or release a lock it no longer holds
+        assert!(!super::refresh("sync", &stale_owner, &later, &conn)?);
+        super::release("sync", &stale_owner, &conn)?;
+        assert!(super::acquire("sync", &later, stale_after, &conn)?.is_none());
+        assert!(super::refresh("sync", &owner, &later, &conn)?);
+        super::release("sync", &owner, &conn)?;
+        assert!(super::acquire("sync", &later, stale_after, &conn)?.is_some());
+        Ok(())
+    }
+}
@@ -1 +1,6 @@
+pub mod deleted_notification;
+pub mod lock;
+pub mod schedule;
+pub mod status;
 pub mod users;
+pub mod v2;
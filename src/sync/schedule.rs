@@ -0,0 +1,111 @@
+use crate::command::{db, sync};
+use crate::Result;
+use std::env;
+use std::future::Future;
+use std::thread;
+use std::time::Duration;
+use tokio::runtime;
+use tokio::time::MissedTickBehavior;
+use tracing::error;
+use tracing::info;
+
+const DEFAULT_INTERVAL_SECS: u64 = 600;
+
+pub fn interval() -> Duration {
+    Duration::from_secs(parse_interval_secs(
+        env::var("SYNC_INTERVAL_SECS").ok().as_deref(),
+    ))
+}
+
+fn parse_interval_secs(value: Option<&str>) -> u64 {
+    value
+        .and_then(|it| it.parse().ok())
+        .filter(|it| *it > 0)
+        .unwrap_or(DEFAULT_INTERVAL_SECS)
+}
+
+// Sync does blocking database work and long Overpass requests, so it gets its own thread and
+// runtime. Running it on the main arbiter would stall shutdown signal handling until a run ends
+pub fn spawn() {
+    let interval = interval();
+    info!(interval_secs = interval.as_secs(), "Scheduling sync");
+    let res = thread::Builder::new().name("sync".into()).spawn(move || {
+        let rt = match runtime::Builder::new_current_thread().enable_all().build() {
+            Ok(rt) => rt,
+            Err(e) => {
+                error!(?e, "Failed to start sync runtime");
+                return;
+            }
+        };
+        rt.block_on(run_every(interval, || async {
+            sync::run(db::open_connection()?, true).await
+        }));
+    });
+    if let Err(e) = res {
+        error!(?e, "Failed to start sync thread");
+    }
+}
+
+// Runs never overlap, a slow run delays the next one instead of queueing the missed ticks
+async fn run_every<F, Fut>(interval: Duration, mut f: F)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    let mut interval = tokio::time::interval(interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        if let Err(e) = f().await {
+            error!(?e, "Scheduled sync failed");
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Error;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::{task, time};
+
+    #[tokio::test(start_paused = true)]
+    async fn run_every() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let counter = runs.clone();
+        let task = tokio::spawn(super::run_every(Duration::from_secs(600), move || {
+            let run = counter.fetch_add(1, Ordering::SeqCst) + 1;
+            async move {
+                if run == 1 {
+                    Err(Error::OverpassApi("Overpass is down".into()))
+                } else {
+                    Ok(())
+                }
+            }
+        }));
+        // The first tick fires right away
+        task::yield_now().await;
+        assert_eq!(1, runs.load(Ordering::SeqCst));
+        time::advance(Duration::from_secs(599)).await;
+        task::yield_now().await;
+        assert_eq!(1, runs.load(Ordering::SeqCst));
+        // Failed runs don't stop the schedule
+        time::advance(Duration::from_secs(1)).await;
+        task::yield_now().await;
+        assert_eq!(2, runs.load(Ordering::SeqCst));
+        time::advance(Duration::from_secs(600)).await;
+        task::yield_now().await;
+        assert_eq!(3, runs.load(Ordering::SeqCst));
+        task.abort();
+    }
+
+    #[test]
+    fn parse_interval_secs() {
+        assert_eq!(600, super::parse_interval_secs(None));
+        assert_eq!(60, super::parse_interval_secs(Some("60")));
+        assert_eq!(600, super::parse_interval_secs(Some("0")));
+        assert_eq!(600, super::parse_interval_secs(Some("-1")));
+        assert_eq!(600, super::parse_interval_secs(Some("garbage")));
+    }
+}
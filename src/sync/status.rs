@@ -0,0 +1,226 @@
+use crate::{Error, Result};
+use deadpool_sqlite::Pool;
+use rusqlite::{named_params, Connection, OptionalExtension, Row};
+use std::sync::Arc;
+use std::time::Duration;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tracing::debug;
+
+// Statuses are written by sync, the server only needs to read them
+pub struct SyncStatusRepo {
+    read_pool: Arc<Pool>,
+}
+
+#[derive(PartialEq, Debug)]
+pub struct SyncStatus {
+    pub id: i64,
+    pub mode: String,
+    pub timestamp_osm_base: OffsetDateTime,
+    pub created_at: OffsetDateTime,
+    // Missing for syncs which finished before these stats were recorded
+    pub duration_secs: Option<f64>,
+    pub elements: Option<i64>,
+    pub elements_per_second: Option<f64>,
+}
+
+impl SyncStatusRepo {
+    pub fn new(read_pool: &Arc<Pool>) -> Self {
+        Self {
+            read_pool: read_pool.clone(),
+        }
+    }
+
+    pub async fn select_latest(&self) -> Result<Option<SyncStatus>> {
+        self.read_pool
+            .get()
+            .await?
+            .interact(|conn| SyncStatus::select_latest(conn))
+            .await?
+    }
+}
+
+const TABLE: &str = "sync_status";
+const ALL_COLUMNS: &str =
+    "id, mode, timestamp_osm_base, created_at, duration_secs, elements, elements_per_second";
+const COL_ID: &str = "id";
+const COL_MODE: &str = "mode";
+const COL_TIMESTAMP_OSM_BASE: &str = "timestamp_osm_base";
+const COL_DURATION_SECS: &str = "duration_secs";
+const COL_ELEMENTS: &str = "elements";
+const COL_ELEMENTS_PER_SECOND: &str = "elements_per_second";
+
+impl SyncStatus {
+    pub fn insert(
+        mode: &str,
+        timestamp_osm_base: &OffsetDateTime,
+        duration: Duration,
+        elements: i64,
+        conn: &Connection,
+    ) -> Result<SyncStatus> {
+        let query = format!(
+            r#"
+                INSERT INTO {TABLE} (
+                    {COL_MODE},
+                    {COL_TIMESTAMP_OSM_BASE},
+                    {COL_DURATION_SECS},
+                    {COL_ELEMENTS},
+                    {COL_ELEMENTS_PER_SECOND}
+                ) VALUES (
+                    :mode,
+                    :timestamp_osm_base,
+                    :duration_secs,
+                    :elements,
+                    :elements_per_second
+                )
+            "#
+        );
+        debug!(query);
+        let duration_secs = duration.as_secs_f64();
+        let elements_per_second = if duration_secs > 0.0 {
+            Some(elements as f64 / duration_secs)
+        } else {
+            None
+        };
+        conn.execute(
+            &query,
+            named_params! {
+                ":mode": mode,
+                ":timestamp_osm_base": timestamp_osm_base.format(&Rfc3339)?,
+                ":duration_secs": duration_secs,
+                ":elements": elements,
+                ":elements_per_second": elements_per_second,
+            },
+        )?;
+        Ok(SyncStatus::select_by_id(conn.last_insert_rowid(), conn)?
+            .ok_or(Error::Rusqlite(rusqlite::Error::QueryReturnedNoRows))?)
+    }
+
+    pub fn select_latest(conn: &Connection) -> Result<Option<SyncStatus>> {
+        let query = format!(
+            r#"
+                SELECT {ALL_COLUMNS}
+                FROM {TABLE}
+                ORDER BY {COL_ID} DESC
+                LIMIT 1
+            "#
+        );
+        debug!(query);
+        Ok(conn.query_row(&query, [], Self::mapper()).optional()?)
+    }
+
+    pub fn select_latest_by_mode(mode: &str, conn: &Connection) -> Result<Option<SyncStatus>> {
+        let query = format!(
+            r#"
+                SELECT {ALL_COLUMNS}
+                FROM {TABLE}
+                WHERE {COL_MODE} = :mode
+                ORDER BY {COL_ID} DESC
+                LIMIT 1
+            "#
+        );
+        debug!(query);
+        Ok(conn
+            .query_row(&query, named_params! { ":mode": mode }, Self::mapper())
+            .optional()?)
+    }
+
+    pub fn select_by_id(id: i64, conn: &Connection) -> Result<Option<SyncStatus>> {
+        let query = format!(
+            r#"
+                SELECT {ALL_COLUMNS}
+                FROM {TABLE}
+                WHERE {COL_ID} = :id
+            "#
+        );
+        debug!(query);
+        Ok(conn
+            .query_row(&query, named_params! { ":id": id }, Self::mapper())
+            .optional()?)
+    }
+
+    const fn mapper() -> fn(&Row) -> rusqlite::Result<SyncStatus> {
+        |row: &Row| -> rusqlite::Result<SyncStatus> {
+            Ok(SyncStatus {
+                id: row.get(0)?,
+                mode: row.get(1)?,
+                timestamp_osm_base: row.get(2)?,
+                created_at: row.get(3)?,
+                duration_secs: row.get(4)?,
+                elements: row.get(5)?,
+                elements_per_second: row.get(6)?,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SyncStatus;
+    use crate::{test::mock_conn, Result};
+    use std::time::Duration;
+    use time::macros::datetime;
+
+    #[test]
+    fn insert() -> Result<()> {
+        let conn = mock_conn();
+        let status = SyncStatus::insert(
+            "full",
+            &datetime!(2024-05-01 00:00 UTC),
+            Duration::from_secs(4),
+            10,
+            &conn,
+        )?;
+        assert_eq!(status, SyncStatus::select_by_id(status.id, &conn)?.unwrap());
+        assert_eq!(Some(4.0), status.duration_secs);
+        assert_eq!(Some(10), status.elements);
+        assert_eq!(Some(2.5), status.elements_per_second);
+        Ok(())
+    }
+
+    #[test]
+    fn select_latest() -> Result<()> {
+        let conn = mock_conn();
+        assert!(SyncStatus::select_latest(&conn)?.is_none());
+        SyncStatus::insert(
+            "full",
+            &datetime!(2024-05-01 00:00 UTC),
+            Duration::ZERO,
+            0,
+            &conn,
+        )?;
+        let latest = SyncStatus::insert(
+            "incremental",
+            &datetime!(2024-05-02 00:00 UTC),
+            Duration::ZERO,
+            0,
+            &conn,
+        )?;
+        assert_eq!(latest, SyncStatus::select_latest(&conn)?.unwrap());
+        Ok(())
+    }
+
+    #[test]
+    fn select_latest_by_mode() -> Result<()> {
+        let conn = mock_conn();
+        assert!(SyncStatus::select_latest_by_mode("full", &conn)?.is_none());
+        let full = SyncStatus::insert(
+            "full",
+            &datetime!(2024-05-01 00:00 UTC),
+            Duration::ZERO,
+            0,
+            &conn,
+        )?;
+        SyncStatus::insert(
+            "incremental",
+            &datetime!(2024-05-02 00:00 UTC),
+            Duration::ZERO,
+            0,
+            &conn,
+        )?;
+        assert_eq!(
+            full,
+            SyncStatus::select_latest_by_mode("full", &conn)?.unwrap()
+        );
+        Ok(())
+    }
+}
@@ -1,4 +1,5 @@
 use crate::osm::osm;
+use crate::osm::osm::OsmUser;
 use crate::user::User;
 use crate::Connection;
 use crate::Result;
@@ -12,7 +13,8 @@ use tracing::error;
 use tracing::info;
 use tracing::warn;
 
-pub async fn run(conn: Connection) -> Result<()> {
+// Users are refreshed once a day, unless refresh_all is set
+pub async fn run(conn: Connection, refresh_all: bool) -> Result<()> {
     let users = User::select_all(None, &conn)?;
 
     for (i, user) in users.iter().enumerate() {
@@ -27,7 +29,7 @@ pub async fn run(conn: Connection) -> Result<()> {
             .add(time::Duration::days(-1))
             .format(&Rfc3339)?;
 
-        if user.tags.contains_key("osm:sync:date") {
+        if !refresh_all && user.tags.contains_key("osm:sync:date") {
             let last_sync_date = user.tags["osm:sync:date"].as_str().unwrap();
             if last_sync_date > yesterday.as_str() {
                 info!(user.osm_data.id, "Last sync date is fresh enough, skipping");
@@ -36,28 +38,7 @@ pub async fn run(conn: Connection) -> Result<()> {
         }
 
         match osm::get_user(user.osm_data.id).await {
-            Ok(new_osm_data) => match new_osm_data {
-                Some(new_osm_data) => {
-                    if new_osm_data != user.osm_data {
-                        info!(
-                            old_osm_data = serde_json::to_string(&user.osm_data)?,
-                            new_osm_data = serde_json::to_string(&new_osm_data)?,
-                            "Change detected",
-                        );
-                        User::set_osm_data(user.id, &new_osm_data, &conn)?;
-                    } else {
-                        info!("No changes detected")
-                    }
-
-                    let now = OffsetDateTime::now_utc();
-                    let now: String = now.format(&Rfc3339)?;
-                    user.set_tag("osm:sync:date", &Value::String(now), &conn)?;
-                }
-                None => {
-                    warn!(user.osm_data.id, "User no longer exists on OSM");
-                    user.set_tag("osm:missing", &Value::Bool(true), &conn)?;
-                }
-            },
+            Ok(new_osm_data) => save_osm_data(user, new_osm_data, &conn)?,
             Err(e) => error!("Failed to fetch user {} {}", user.osm_data.id, e),
         }
 
@@ -67,3 +48,78 @@ pub async fn run(conn: Connection) -> Result<()> {
 
     Ok(())
 }
+
+fn save_osm_data(user: &User, new_osm_data: Option<OsmUser>, conn: &Connection) -> Result<()> {
+    match new_osm_data {
+        Some(new_osm_data) => {
+            if new_osm_data != user.osm_data {
+                info!(
+                    old_osm_data = serde_json::to_string(&user.osm_data)?,
+                    new_osm_data = serde_json::to_string(&new_osm_data)?,
+                    "Change detected",
+                );
+                if new_osm_data.display_name != user.osm_data.display_name {
+                    info!(
+                        user.osm_data.id,
+                        old_display_name = user.osm_data.display_name,
+                        new_display_name = new_osm_data.display_name,
+                        "User was renamed",
+                    );
+                }
+                User::set_osm_data(user.id, &new_osm_data, conn)?;
+            } else {
+                info!("No changes detected")
+            }
+
+            let now = OffsetDateTime::now_utc();
+            let now: String = now.format(&Rfc3339)?;
+            user.set_tag("osm:sync:date", &Value::String(now), conn)?;
+        }
+        // OSM answers with 404 or 410 for deleted accounts, we keep the user so the events still
+        // have an author
+        None => {
+            warn!(user.osm_data.id, "User no longer exists on OSM");
+            user.set_tag("osm:missing", &Value::Bool(true), conn)?;
+            if user.deleted_at.is_none() {
+                User::set_deleted_at(user.id, Some(OffsetDateTime::now_utc()), conn)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::osm::osm::OsmUser;
+    use crate::test::mock_conn;
+    use crate::user::User;
+    use crate::Result;
+    use serde_json::Value;
+
+    #[test]
+    fn save_osm_data() -> Result<()> {
+        let conn = mock_conn();
+        let user = User::insert(1, &OsmUser::mock(), &conn)?;
+        let renamed = OsmUser {
+            display_name: "satoshi".into(),
+            ..OsmUser::mock()
+        };
+        super::save_osm_data(&user, Some(renamed), &conn)?;
+        let user = User::select_by_id(user.id, &conn)?.unwrap();
+        assert_eq!("satoshi", user.osm_data.display_name);
+        assert!(user.tags.contains_key("osm:sync:date"));
+        assert!(user.deleted_at.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn save_osm_data_deleted_account() -> Result<()> {
+        let conn = mock_conn();
+        let user = User::insert(1, &OsmUser::mock(), &conn)?;
+        super::save_osm_data(&user, None, &conn)?;
+        let user = User::select_by_id(user.id, &conn)?.unwrap();
+        assert_eq!(Some(&Value::Bool(true)), user.tags.get("osm:missing"));
+        assert!(user.deleted_at.is_some());
+        Ok(())
+    }
+}
@@ -0,0 +1,90 @@
+use super::status::SyncStatus;
+use super::status::SyncStatusRepo;
+use crate::Error;
+use actix_web::get;
+use actix_web::web::Data;
+use actix_web::web::Json;
+use serde::Deserialize;
+use serde::Serialize;
+use time::OffsetDateTime;
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct GetStatusItem {
+    pub mode: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub timestamp_osm_base: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    pub duration_secs: Option<f64>,
+    pub elements: Option<i64>,
+    pub elements_per_second: Option<f64>,
+}
+
+impl Into<GetStatusItem> for SyncStatus {
+    fn into(self) -> GetStatusItem {
+        GetStatusItem {
+            mode: self.mode,
+            timestamp_osm_base: self.timestamp_osm_base,
+            created_at: self.created_at,
+            duration_secs: self.duration_secs,
+            elements: self.elements,
+            elements_per_second: self.elements_per_second,
+        }
+    }
+}
+
+#[get("status")]
+pub async fn get_status(repo: Data<SyncStatusRepo>) -> Result<Json<GetStatusItem>, Error> {
+    repo.select_latest()
+        .await?
+        .map(|it| Json(it.into()))
+        .ok_or(Error::HttpNotFound("There are no finished syncs".into()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::GetStatusItem;
+    use crate::sync::status::{SyncStatus, SyncStatusRepo};
+    use crate::test::mock_state;
+    use crate::Result;
+    use actix_web::test::TestRequest;
+    use actix_web::web::Data;
+    use actix_web::{test, App};
+    use std::time::Duration;
+    use time::macros::datetime;
+
+    #[test]
+    async fn get_status() -> Result<()> {
+        let state = mock_state().await;
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(SyncStatusRepo::new(&state.pool)))
+                .service(super::get_status),
+        )
+        .await;
+        let req = TestRequest::get().uri("/status").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(404, res.status().as_u16());
+        state
+            .pool
+            .get()
+            .await?
+            .interact(|conn| {
+                SyncStatus::insert(
+                    "incremental",
+                    &datetime!(2024-05-01 00:00 UTC),
+                    Duration::from_secs(2),
+                    10,
+                    conn,
+                )
+            })
+            .await??;
+        let req = TestRequest::get().uri("/status").to_request();
+        let res: GetStatusItem = test::call_and_read_body_json(&app, req).await;
+        assert_eq!("incremental", res.mode);
+        assert_eq!(Some(2.0), res.duration_secs);
+        assert_eq!(Some(10), res.elements);
+        assert_eq!(Some(5.0), res.elements_per_second);
+        Ok(())
+    }
+}
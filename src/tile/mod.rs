@@ -1 +1,3 @@
 pub mod controller;
+pub mod mvt;
+pub mod v2;
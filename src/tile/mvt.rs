@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+pub const EXTENT: u32 = 4096;
+
+const WIRE_TYPE_VARINT: u64 = 0;
+const WIRE_TYPE_LEN: u64 = 2;
+
+const TILE_LAYERS: u64 = 3;
+const LAYER_NAME: u64 = 1;
+const LAYER_FEATURES: u64 = 2;
+const LAYER_KEYS: u64 = 3;
+const LAYER_VALUES: u64 = 4;
+const LAYER_EXTENT: u64 = 5;
+const LAYER_VERSION: u64 = 15;
+const FEATURE_ID: u64 = 1;
+const FEATURE_TAGS: u64 = 2;
+const FEATURE_TYPE: u64 = 3;
+const FEATURE_GEOMETRY: u64 = 4;
+const VALUE_STRING: u64 = 1;
+
+const GEOM_TYPE_POINT: u64 = 1;
+const COMMAND_MOVE_TO: u32 = 1;
+
+// Minimal Mapbox Vector Tile encoder, only supports a single layer of point features with string
+// properties, see https://github.com/mapbox/vector-tile-spec/tree/master/2.1
+pub struct Layer {
+    name: String,
+    features: Vec<u8>,
+    keys: Vec<String>,
+    values: Vec<String>,
+    key_indexes: HashMap<String, u32>,
+    value_indexes: HashMap<String, u32>,
+}
+
+impl Layer {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.into(),
+            features: vec![],
+            keys: vec![],
+            values: vec![],
+            key_indexes: HashMap::new(),
+            value_indexes: HashMap::new(),
+        }
+    }
+
+    // Coordinates are in tile space, from 0 to EXTENT
+    pub fn add_point(&mut self, id: u64, x: i32, y: i32, properties: &[(&str, &str)]) {
+        let mut tags = vec![];
+        for (key, value) in properties {
+            tags.push(index(key, &mut self.keys, &mut self.key_indexes));
+            tags.push(index(value, &mut self.values, &mut self.value_indexes));
+        }
+        let geometry = [COMMAND_MOVE_TO | (1 << 3), zigzag(x), zigzag(y)];
+        let mut feature = vec![];
+        write_varint_field(&mut feature, FEATURE_ID, id);
+        write_packed_field(&mut feature, FEATURE_TAGS, &tags);
+        write_varint_field(&mut feature, FEATURE_TYPE, GEOM_TYPE_POINT);
+        write_packed_field(&mut feature, FEATURE_GEOMETRY, &geometry);
+        write_len_field(&mut self.features, LAYER_FEATURES, &feature);
+    }
+
+    pub fn into_tile(self) -> Vec<u8> {
+        let mut layer = vec![];
+        write_varint_field(&mut layer, LAYER_VERSION, 2);
+        write_len_field(&mut layer, LAYER_NAME, self.name.as_bytes());
+        layer.extend(self.features);
+        for key in &self.keys {
+            write_len_field(&mut layer, LAYER_KEYS, key.as_bytes());
+        }
+        for value in &self.values {
+            let mut encoded = vec![];
+            write_len_field(&mut encoded, VALUE_STRING, value.as_bytes());
+            write_len_field(&mut layer, LAYER_VALUES, &encoded);
+        }
+        write_varint_field(&mut layer, LAYER_EXTENT, EXTENT as u64);
+        let mut tile = vec![];
+        write_len_field(&mut tile, TILE_LAYERS, &layer);
+        tile
+    }
+}
+
+// Projects a coordinate to Web Mercator and returns its position within the z/x/y tile
+pub fn tile_coord(lon: f64, lat: f64, z: u8, x: u32, y: u32) -> (i32, i32) {
+    let n = (1u32 << z) as f64;
+    let lat = lat.to_radians();
+    let global_x = (lon + 180.0) / 360.0 * n;
+    let global_y = (1.0 - (lat.tan() + 1.0 / lat.cos()).ln() / PI) / 2.0 * n;
+    let extent = EXTENT as f64;
+    (
+        ((global_x - x as f64) * extent).round() as i32,
+        ((global_y - y as f64) * extent).round() as i32,
+    )
+}
+
+fn index(value: &str, values: &mut Vec<String>, indexes: &mut HashMap<String, u32>) -> u32 {
+    *indexes.entry(value.into()).or_insert_with(|| {
+        values.push(value.into());
+        values.len() as u32 - 1
+    })
+}
+
+fn zigzag(value: i32) -> u32 {
+    ((value << 1) ^ (value >> 31)) as u32
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn write_varint_field(buf: &mut Vec<u8>, field: u64, value: u64) {
+    write_varint(buf, (field << 3) | WIRE_TYPE_VARINT);
+    write_varint(buf, value);
+}
+
+fn write_len_field(buf: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+    write_varint(buf, (field << 3) | WIRE_TYPE_LEN);
+    write_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+fn write_packed_field(buf: &mut Vec<u8>, field: u64, values: &[u32]) {
+    let mut packed = vec![];
+    for value in values {
+        write_varint(&mut packed, *value as u64);
+    }
+    write_len_field(buf, field, &packed);
+}
+
+#[cfg(test)]
+pub mod test {
+    // Returns (field, wire type, varint value or bytes) for each top level field of a message
+    pub fn decode(mut buf: &[u8]) -> Vec<(u64, u64, u64, Vec<u8>)> {
+        let mut res = vec![];
+        while !buf.is_empty() {
+            let key = read_varint(&mut buf);
+            match key & 0x7 {
+                super::WIRE_TYPE_VARINT => {
+                    res.push((key >> 3, key & 0x7, read_varint(&mut buf), vec![]))
+                }
+                super::WIRE_TYPE_LEN => {
+                    let len = read_varint(&mut buf) as usize;
+                    res.push((key >> 3, key & 0x7, 0, buf[..len].to_vec()));
+                    buf = &buf[len..];
+                }
+                wire_type => panic!("Unexpected wire type {wire_type}"),
+            }
+        }
+        res
+    }
+
+    fn read_varint(buf: &mut &[u8]) -> u64 {
+        let mut res = 0;
+        let mut shift = 0;
+        loop {
+            let byte = buf[0];
+            *buf = &buf[1..];
+            res |= ((byte & 0x7f) as u64) << shift;
+            if byte < 0x80 {
+                return res;
+            }
+            shift += 7;
+        }
+    }
+
+    #[test]
+    fn into_tile() {
+        let mut layer = super::Layer::new("elements");
+        layer.add_point(1, 10, 20, &[("category", "atm")]);
+        layer.add_point(2, 30, 40, &[("category", "atm"), ("name", "Cafe")]);
+        let tile = decode(&layer.into_tile());
+        assert_eq!(1, tile.len());
+        assert_eq!(super::TILE_LAYERS, tile[0].0);
+        let layer = decode(&tile[0].3);
+        let fields = |field| layer.iter().filter(|it| it.0 == field).collect::<Vec<_>>();
+        assert_eq!(2, fields(super::LAYER_VERSION)[0].2);
+        assert_eq!(b"elements".to_vec(), fields(super::LAYER_NAME)[0].3);
+        assert_eq!(2, fields(super::LAYER_FEATURES).len());
+        assert_eq!(2, fields(super::LAYER_KEYS).len());
+        assert_eq!(2, fields(super::LAYER_VALUES).len());
+        let feature = decode(&fields(super::LAYER_FEATURES)[1].3);
+        assert_eq!((super::FEATURE_ID, 2), (feature[0].0, feature[0].2));
+        // category=atm and name=Cafe, both key and value indexes are reused
+        assert_eq!(vec![0, 0, 1, 1], feature[1].3);
+        // MoveTo(1) followed by zigzag encoded 30 and 40
+        assert_eq!(vec![9, 60, 80], feature[3].3);
+    }
+
+    #[test]
+    fn tile_coord() {
+        assert_eq!((0, 0), super::tile_coord(-180.0, 85.0511287798, 0, 0, 0));
+        assert_eq!((2048, 2048), super::tile_coord(0.0, 0.0, 0, 0, 0));
+        assert_eq!((0, 0), super::tile_coord(0.0, 0.0, 1, 1, 1));
+    }
+
+    #[test]
+    fn zigzag() {
+        assert_eq!(0, super::zigzag(0));
+        assert_eq!(1, super::zigzag(-1));
+        assert_eq!(2, super::zigzag(1));
+        assert_eq!(3, super::zigzag(-2));
+    }
+}
@@ -0,0 +1,215 @@
+use crate::element::Element;
+use crate::element::ElementRepo;
+use crate::tile::mvt;
+use crate::tile::mvt::Layer;
+use crate::Error;
+use actix_web::get;
+use actix_web::http::StatusCode;
+use actix_web::web::Data;
+use actix_web::web::Path;
+use actix_web::HttpResponse;
+use geojson::Feature;
+use geojson::FeatureCollection;
+use geojson::Geometry;
+use serde_json::Map;
+use serde_json::Value;
+use std::f64::consts::PI;
+
+const MAX_ZOOM: u8 = 22;
+
+#[get("{z}/{x}/{y}.geojson")]
+pub async fn get(
+    path: Path<(u8, u32, u32)>,
+    repo: Data<ElementRepo>,
+) -> Result<HttpResponse, Error> {
+    let (z, x, y) = path.into_inner();
+    let (min_lon, min_lat, max_lon, max_lat) = tile_bbox(z, x, y)?;
+    let features = repo
+        .select_by_bbox(min_lon, min_lat, max_lon, max_lat)
+        .await?
+        .into_iter()
+        .filter(|it| it.deleted_at.is_none())
+        .filter_map(feature)
+        .collect();
+    let collection = FeatureCollection {
+        bbox: None,
+        features,
+        foreign_members: None,
+    };
+    Ok(HttpResponse::build(StatusCode::OK)
+        .content_type("application/geo+json")
+        .body(collection.to_string()))
+}
+
+#[get("{z}/{x}/{y}.mvt")]
+pub async fn get_mvt(
+    path: Path<(u8, u32, u32)>,
+    repo: Data<ElementRepo>,
+) -> Result<HttpResponse, Error> {
+    let (z, x, y) = path.into_inner();
+    let (min_lon, min_lat, max_lon, max_lat) = tile_bbox(z, x, y)?;
+    let mut layer = Layer::new("elements");
+    for element in repo
+        .select_by_bbox(min_lon, min_lat, max_lon, max_lat)
+        .await?
+        .into_iter()
+        .filter(|it| it.deleted_at.is_none())
+    {
+        let Some(coord) = element.overpass_data.try_coord() else {
+            continue;
+        };
+        let (tile_x, tile_y) = mvt::tile_coord(coord.x, coord.y, z, x, y);
+        let id = element.overpass_data.btcmap_id();
+        let mut properties = vec![("id", id.as_str())];
+        for tag in [
+            "name",
+            "payment:onchain",
+            "payment:lightning",
+            "payment:lightning_contactless",
+        ] {
+            let value = element.overpass_data.tag(tag);
+            if !value.is_empty() {
+                properties.push((tag, value));
+            }
+        }
+        for tag in ["icon:android", "category"] {
+            if let Some(value) = element.tags.get(tag).and_then(|it| it.as_str()) {
+                properties.push((tag, value));
+            }
+        }
+        layer.add_point(element.id as u64, tile_x, tile_y, &properties);
+    }
+    Ok(HttpResponse::build(StatusCode::OK)
+        .content_type("application/vnd.mapbox-vector-tile")
+        .body(layer.into_tile()))
+}
+
+// Slippy map tile bounds as (min_lon, min_lat, max_lon, max_lat), see
+// https://wiki.openstreetmap.org/wiki/Slippy_map_tilenames
+fn tile_bbox(z: u8, x: u32, y: u32) -> Result<(f64, f64, f64, f64), Error> {
+    if z > MAX_ZOOM || x >= 1 << z || y >= 1 << z {
+        return Err(Error::HttpBadRequest(format!(
+            "Tile {z}/{x}/{y} doesn't exist"
+        )));
+    }
+    let n = (1u32 << z) as f64;
+    let lon = |x: u32| x as f64 / n * 360.0 - 180.0;
+    let lat = |y: u32| (PI * (1.0 - 2.0 * y as f64 / n)).sinh().atan().to_degrees();
+    Ok((lon(x), lat(y + 1), lon(x + 1), lat(y)))
+}
+
+fn feature(element: Element) -> Option<Feature> {
+    let coord = element.overpass_data.try_coord()?;
+    let mut properties = Map::new();
+    properties.insert("id".into(), element.overpass_data.btcmap_id().into());
+    properties.insert(
+        "name".into(),
+        element.overpass_data.tag("name").to_string().into(),
+    );
+    for tag in ["icon:android", "category"] {
+        if let Some(value) = element.tags.get(tag) {
+            properties.insert(tag.into(), value.clone());
+        }
+    }
+    Some(Feature {
+        bbox: None,
+        geometry: Some(Geometry::new(geojson::Value::Point(vec![coord.x, coord.y]))),
+        id: None,
+        properties: Some(properties),
+        foreign_members: None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::osm::overpass::OverpassElement;
+    use crate::test::mock_osm_tags;
+    use crate::test::mock_state;
+    use crate::tile::mvt::test::decode;
+    use crate::Result;
+    use actix_web::test::TestRequest;
+    use actix_web::web::Data;
+    use actix_web::{test, App};
+    use serde_json::Value;
+
+    #[test]
+    async fn get() -> Result<()> {
+        let state = mock_state().await;
+        for (id, lon, lat) in [(1, 10.0, 10.0), (2, -10.0, 10.0), (3, 10.0, -10.0)] {
+            state
+                .element_repo
+                .insert(&OverpassElement {
+                    lon: Some(lon),
+                    lat: Some(lat),
+                    ..OverpassElement::mock(id)
+                })
+                .await?;
+        }
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.element_repo))
+                .service(super::get),
+        )
+        .await;
+        // North-east quarter of the world
+        let req = TestRequest::get().uri("/1/1/0.geojson").to_request();
+        let res: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!("FeatureCollection", res["type"]);
+        let features = res["features"].as_array().unwrap();
+        assert_eq!(1, features.len());
+        assert_eq!("node:1", features[0]["properties"]["id"]);
+        assert_eq!(
+            serde_json::json!([10.0, 10.0]),
+            features[0]["geometry"]["coordinates"],
+        );
+        let req = TestRequest::get().uri("/1/2/0.geojson").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(400, res.status().as_u16());
+        Ok(())
+    }
+
+    #[test]
+    async fn get_mvt() -> Result<()> {
+        let state = mock_state().await;
+        for (id, lon, lat) in [(1, 10.0, 10.0), (2, 20.0, 20.0), (3, -10.0, 10.0)] {
+            state
+                .element_repo
+                .insert(&OverpassElement {
+                    lon: Some(lon),
+                    lat: Some(lat),
+                    tags: Some(mock_osm_tags(&["payment:lightning", "yes"])),
+                    ..OverpassElement::mock(id)
+                })
+                .await?;
+        }
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state.element_repo))
+                .service(super::get_mvt),
+        )
+        .await;
+        let req = TestRequest::get().uri("/1/1/0.mvt").to_request();
+        let res = test::call_and_read_body(&app, req).await;
+        let tile = decode(&res);
+        assert_eq!(1, tile.len());
+        let features = decode(&tile[0].3)
+            .into_iter()
+            .filter(|it| it.0 == 2)
+            .count();
+        assert_eq!(2, features);
+        Ok(())
+    }
+
+    #[test]
+    async fn tile_bbox() -> Result<()> {
+        let (min_lon, min_lat, max_lon, max_lat) = super::tile_bbox(0, 0, 0)?;
+        assert_eq!((-180.0, 180.0), (min_lon, max_lon));
+        assert!((max_lat - 85.0511).abs() < 0.0001);
+        assert!((min_lat + 85.0511).abs() < 0.0001);
+        let (min_lon, min_lat, max_lon, max_lat) = super::tile_bbox(1, 1, 0)?;
+        assert_eq!((0.0, 0.0, 180.0), (min_lon, min_lat, max_lon));
+        assert!((max_lat - 85.0511).abs() < 0.0001);
+        assert!(super::tile_bbox(23, 0, 0).is_err());
+        Ok(())
+    }
+}
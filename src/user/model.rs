@@ -9,6 +9,7 @@ use tracing::debug;
 
 pub struct UserRepo {
     pool: Arc<Pool>,
+    read_pool: Arc<Pool>,
 }
 
 pub struct User {
@@ -22,16 +23,24 @@ pub struct User {
 
 impl UserRepo {
     pub fn new(pool: &Arc<Pool>) -> Self {
-        Self { pool: pool.clone() }
+        Self {
+            pool: pool.clone(),
+            read_pool: pool.clone(),
+        }
     }
 
-    #[cfg(test)]
-    pub fn mock() -> Self {
+    pub fn with_read_pool(self, read_pool: &Arc<Pool>) -> Self {
         Self {
-            pool: Arc::new(crate::test::mock_pool()),
+            read_pool: read_pool.clone(),
+            ..self
         }
     }
 
+    #[cfg(test)]
+    pub fn mock() -> Self {
+        Self::new(&Arc::new(crate::test::mock_pool()))
+    }
+
     #[cfg(test)]
     pub async fn insert(&self, id: i64, osm_data: &OsmUser) -> Result<User> {
         let osm_data = osm_data.clone();
@@ -43,7 +52,7 @@ impl UserRepo {
     }
 
     pub async fn select_all(&self, limit: Option<i64>) -> Result<Vec<User>> {
-        self.pool
+        self.read_pool
             .get()
             .await?
             .interact(move |conn| User::select_all(limit, conn))
@@ -56,15 +65,31 @@ impl UserRepo {
         limit: Option<i64>,
     ) -> Result<Vec<User>> {
         let updated_since = updated_since.clone();
-        self.pool
+        self.read_pool
             .get()
             .await?
             .interact(move |conn| User::select_updated_since(&updated_since, limit, conn))
             .await?
     }
 
+    pub async fn select_count(&self, updated_since: Option<OffsetDateTime>) -> Result<i64> {
+        self.read_pool
+            .get()
+            .await?
+            .interact(move |conn| User::select_count(updated_since.as_ref(), conn))
+            .await?
+    }
+
+    pub async fn select_max_updated_at(&self) -> Result<Option<OffsetDateTime>> {
+        self.read_pool
+            .get()
+            .await?
+            .interact(|conn| User::select_max_updated_at(conn))
+            .await?
+    }
+
     pub async fn select_by_id(&self, id: i64) -> Result<Option<User>> {
-        self.pool
+        self.read_pool
             .get()
             .await?
             .interact(move |conn| User::select_by_id(id, conn))
@@ -138,6 +163,33 @@ impl User {
             .collect::<Result<Vec<_>, _>>()?)
     }
 
+    // Newest change in the whole table, deleted rows included
+    pub fn select_max_updated_at(conn: &Connection) -> Result<Option<OffsetDateTime>> {
+        let query = r#"
+            SELECT max(updated_at)
+            FROM user
+        "#;
+        Ok(conn.query_row(query, [], |row| row.get(0))?)
+    }
+
+    pub fn select_count(updated_since: Option<&OffsetDateTime>, conn: &Connection) -> Result<i64> {
+        let query = r#"
+            SELECT count(*)
+            FROM user
+            WHERE updated_at > :updated_since
+        "#;
+        let updated_since = match updated_since {
+            Some(updated_since) => updated_since.format(&Rfc3339)?,
+            None => "".into(),
+        };
+
+        Ok(conn.query_row(
+            query,
+            named_params! { ":updated_since": updated_since },
+            |row| row.get(0),
+        )?)
+    }
+
     pub fn select_updated_since(
         updated_since: &OffsetDateTime,
         limit: Option<i64>,
@@ -233,6 +285,29 @@ impl User {
         Ok(())
     }
 
+    pub fn set_deleted_at(
+        id: i64,
+        deleted_at: Option<OffsetDateTime>,
+        conn: &Connection,
+    ) -> Result<User> {
+        let query = r#"
+            UPDATE user
+            SET deleted_at = :deleted_at
+            WHERE rowid = :id
+        "#;
+
+        conn.execute(
+            query,
+            named_params! {
+                ":id": id,
+                ":deleted_at": deleted_at.map(|it| it.format(&Rfc3339)).transpose()?,
+            },
+        )?;
+
+        Ok(User::select_by_id(id, &conn)?
+            .ok_or(Error::Rusqlite(rusqlite::Error::QueryReturnedNoRows))?)
+    }
+
     #[cfg(test)]
     pub fn _set_updated_at(
         id: i64,
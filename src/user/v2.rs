@@ -1,37 +1,48 @@
 use crate::osm::osm::OsmUser;
+use crate::server::last_modified;
+use crate::server::limit::resolve_limit;
+use crate::server::limit::resolve_sync_limit;
+use crate::server::strict;
+use crate::server::timestamp;
+use crate::server::TOTAL_COUNT_HEADER;
 use crate::user::User;
 use crate::user::UserRepo;
 use crate::Error;
 use actix_web::get;
+use actix_web::route;
 use actix_web::web::Data;
 use actix_web::web::Json;
 use actix_web::web::Path;
 use actix_web::web::Query;
 use actix_web::web::Redirect;
+use actix_web::CustomizeResponder;
 use actix_web::Either;
+use actix_web::HttpRequest;
+use actix_web::Responder;
 use serde::Deserialize;
 use serde::Serialize;
 use serde_json::Map;
 use serde_json::Value;
-use time::format_description::well_known::Rfc3339;
 use time::OffsetDateTime;
 
 #[derive(Deserialize)]
 pub struct GetArgs {
     #[serde(default)]
-    #[serde(with = "time::serde::rfc3339::option")]
+    #[serde(with = "crate::server::updated_since::option")]
     updated_since: Option<OffsetDateTime>,
     limit: Option<i64>,
 }
 
+const GET_ARGS: [&str; 2] = ["updated_since", "limit"];
+
 #[derive(Serialize, Deserialize)]
 pub struct GetItem {
     pub id: i64,
     pub osm_json: OsmUser,
     pub tags: Map<String, Value>,
-    #[serde(with = "time::serde::rfc3339")]
+    #[serde(with = "crate::server::timestamp")]
     pub created_at: OffsetDateTime,
-    #[serde(with = "time::serde::rfc3339")]
+    #[serde(with = "crate::server::timestamp")]
     pub updated_at: OffsetDateTime,
     pub deleted_at: String,
 }
@@ -46,7 +57,7 @@ impl Into<GetItem> for User {
             updated_at: self.updated_at,
             deleted_at: self
                 .deleted_at
-                .map(|it| it.format(&Rfc3339).unwrap())
+                .map(|it| timestamp::format(&it))
                 .unwrap_or_default()
                 .into(),
         }
@@ -59,31 +70,41 @@ impl Into<Json<GetItem>> for User {
     }
 }
 
-#[get("")]
+#[route("", method = "GET", method = "HEAD")]
 async fn get(
+    req: HttpRequest,
     args: Query<GetArgs>,
     repo: Data<UserRepo>,
-) -> Result<Either<Json<Vec<GetItem>>, Redirect>, Error> {
+) -> Result<Either<CustomizeResponder<Json<Vec<GetItem>>>, Redirect>, Error> {
+    strict::check(req.query_string(), &GET_ARGS)?;
     if args.limit.is_none() && args.updated_since.is_none() {
         return Ok(Either::Right(
             Redirect::to("https://static.btcmap.org/api/v2/users.json").permanent(),
         ));
     }
 
-    Ok(Either::Left(Json(match &args.updated_since {
+    let total = repo.select_count(args.updated_since).await?;
+
+    let items: Vec<GetItem> = match &args.updated_since {
         Some(updated_since) => repo
-            .select_updated_since(updated_since, args.limit)
+            .select_updated_since(updated_since, resolve_sync_limit(args.limit))
             .await?
             .into_iter()
             .map(|it| it.into())
             .collect(),
         None => repo
-            .select_all(args.limit)
+            .select_all(Some(resolve_limit(args.limit)))
             .await?
             .into_iter()
             .map(|it| it.into())
             .collect(),
-    })))
+    };
+
+    let last_modified = repo.select_max_updated_at().await?;
+    Ok(Either::Left(
+        last_modified::customize(Json(items), last_modified)
+            .insert_header((TOTAL_COUNT_HEADER, total)),
+    ))
 }
 
 #[get("{id}")]
@@ -1,4 +1,5 @@
 use crate::osm::osm::OsmUser;
+use crate::server::strict;
 use crate::user::User;
 use crate::user::UserRepo;
 use crate::Error;
@@ -7,6 +8,7 @@ use actix_web::web::Data;
 use actix_web::web::Json;
 use actix_web::web::Path;
 use actix_web::web::Query;
+use actix_web::HttpRequest;
 use serde::Deserialize;
 use serde::Serialize;
 use serde_json::Map;
@@ -20,6 +22,8 @@ pub struct GetArgs {
     limit: i64,
 }
 
+const GET_ARGS: [&str; 2] = ["updated_since", "limit"];
+
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
 pub struct GetItem {
     pub id: i64,
@@ -64,7 +68,12 @@ impl Into<Json<GetItem>> for User {
 }
 
 #[get("")]
-async fn get(args: Query<GetArgs>, repo: Data<UserRepo>) -> Result<Json<Vec<GetItem>>, Error> {
+async fn get(
+    req: HttpRequest,
+    args: Query<GetArgs>,
+    repo: Data<UserRepo>,
+) -> Result<Json<Vec<GetItem>>, Error> {
+    strict::check(req.query_string(), &GET_ARGS)?;
     Ok(Json(
         repo.select_updated_since(&args.updated_since, Some(args.limit))
             .await?